@@ -1,10 +1,492 @@
 // src/actions.rs
-use crate::models::Task; // Add this line
+use std::collections::HashSet;
 
-#[derive(Debug)]
+use chrono::{Local, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::dates;
+use crate::models::{self, renumber, Priority, Task};
+
+#[derive(Debug, Error)]
+pub enum ActionError {
+    #[error("task {} no longer exists", short(.0))]
+    Missing(Uuid),
+    /// The other list's half of a move isn't the next thing to undo or
+    /// redo there.
+    #[error("this move also changed list {list}, which has changed since; {verb} the changes made there first")]
+    OtherList { list: String, verb: &'static str },
+}
+
+/// A change to the task lists that can be undone and redone. Each variant
+/// carries enough of the old and new state to go both ways.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Action {
     Add(Task),
     Delete(Task),
-    Done(u32),
-    Archive(usize),
+    Done(Uuid),
+    /// A recurring task was completed and `next` added after it.
+    Recur {
+        done: Uuid,
+        next: Task,
+    },
+    /// The tasks moved to the archive, as they were in the active list.
+    Archive(Vec<Task>),
+    Edit {
+        before: Box<Task>,
+        after: Box<Task>,
+    },
+    Tags {
+        uuid: Uuid,
+        before: Vec<String>,
+        after: Vec<String>,
+    },
+    Priority {
+        uuid: Uuid,
+        before: Priority,
+        after: Priority,
+    },
+    /// Tasks read from another app, added to the end of either list.
+    Import {
+        tasks: Vec<Task>,
+        archived: Vec<Task>,
+    },
+    /// Both lists as a whole, before and after merging in the changes made
+    /// to another copy of them.
+    Merge {
+        before: Vec<Task>,
+        before_archived: Vec<Task>,
+        after: Vec<Task>,
+        after_archived: Vec<Task>,
+    },
+    /// Tasks moved to another list, as they were here. That list records
+    /// the matching `MoveIn`, and the two are undone and redone together.
+    MoveOut {
+        tasks: Vec<Task>,
+        to: String,
+    },
+    /// Tasks moved here from another list, as they were added here.
+    MoveIn {
+        tasks: Vec<Task>,
+        from: String,
+    },
+    /// Several changes made by one command, undone and redone together.
+    Batch(Vec<Action>),
+}
+
+impl Action {
+    /// Reverts the action. On an error both lists are left as they were.
+    pub fn undo(&self, tasks: &mut Vec<Task>, archived: &mut Vec<Task>) -> Result<(), ActionError> {
+        self.apply(tasks, archived, true)
+    }
+
+    /// Applies the action again. On an error both lists are left as they were.
+    pub fn redo(&self, tasks: &mut Vec<Task>, archived: &mut Vec<Task>) -> Result<(), ActionError> {
+        self.apply(tasks, archived, false)
+    }
+
+    /// Undoes or redoes the action on copies of the lists, which replace
+    /// them only once every step has worked.
+    fn apply(&self, tasks: &mut Vec<Task>, archived: &mut Vec<Task>, undo: bool) -> Result<(), ActionError> {
+        let (mut new_tasks, mut new_archived) = (tasks.clone(), archived.clone());
+        if undo {
+            self.undo_steps(&mut new_tasks, &mut new_archived)?;
+        } else {
+            self.redo_steps(&mut new_tasks, &mut new_archived)?;
+        }
+        *tasks = new_tasks;
+        *archived = new_archived;
+        Ok(())
+    }
+
+    fn undo_steps(&self, tasks: &mut Vec<Task>, archived: &mut Vec<Task>) -> Result<(), ActionError> {
+        match self {
+            Action::Add(task) => {
+                let index = position(tasks, task.uuid)?;
+                tasks.remove(index);
+                renumber(tasks);
+            }
+            Action::Delete(task) => {
+                insert_at_id(tasks, task.clone());
+                renumber(tasks);
+            }
+            Action::Done(uuid) => {
+                let index = position(tasks, *uuid)?;
+                tasks[index].completed = false;
+                tasks[index].completed_at = None;
+            }
+            Action::Recur { done, next } => {
+                let index = position(tasks, next.uuid)?;
+                tasks.remove(index);
+                renumber(tasks);
+                let index = position(tasks, *done)?;
+                tasks[index].completed = false;
+                tasks[index].completed_at = None;
+            }
+            Action::Archive(moved) => {
+                for task in moved {
+                    let index = position(archived, task.uuid)?;
+                    archived.remove(index);
+                }
+                for task in moved {
+                    insert_at_id(tasks, task.clone());
+                }
+                renumber(tasks);
+                renumber(archived);
+            }
+            Action::Edit { before, .. } => {
+                let index = position(tasks, before.uuid)?;
+                tasks[index] = Task {
+                    id: tasks[index].id,
+                    ..(**before).clone()
+                };
+            }
+            Action::Tags { uuid, before, .. } => {
+                let index = position(tasks, *uuid)?;
+                tasks[index].tags = before.clone();
+            }
+            Action::Priority { uuid, before, .. } => {
+                let index = position(tasks, *uuid)?;
+                tasks[index].priority = before.clone();
+            }
+            Action::Import {
+                tasks: added,
+                archived: added_archived,
+            } => {
+                for task in added {
+                    let index = position(tasks, task.uuid)?;
+                    tasks.remove(index);
+                }
+                for task in added_archived {
+                    let index = position(archived, task.uuid)?;
+                    archived.remove(index);
+                }
+                renumber(tasks);
+                renumber(archived);
+            }
+            Action::Merge {
+                before,
+                before_archived,
+                ..
+            } => {
+                *tasks = before.clone();
+                *archived = before_archived.clone();
+            }
+            Action::MoveOut { tasks: moved, .. } => {
+                for task in moved {
+                    insert_at_id(tasks, task.clone());
+                }
+                renumber(tasks);
+            }
+            Action::MoveIn { tasks: moved, .. } => {
+                for task in moved {
+                    let index = position(tasks, task.uuid)?;
+                    tasks.remove(index);
+                }
+                renumber(tasks);
+            }
+            Action::Batch(actions) => {
+                for action in actions.iter().rev() {
+                    action.undo_steps(tasks, archived)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn redo_steps(&self, tasks: &mut Vec<Task>, archived: &mut Vec<Task>) -> Result<(), ActionError> {
+        match self {
+            Action::Add(task) => {
+                insert_at_id(tasks, task.clone());
+                renumber(tasks);
+            }
+            Action::Delete(task) => {
+                let index = position(tasks, task.uuid)?;
+                tasks.remove(index);
+                renumber(tasks);
+            }
+            Action::Done(uuid) => {
+                let index = position(tasks, *uuid)?;
+                tasks[index].completed = true;
+                tasks[index].completed_at = Some(Utc::now());
+            }
+            Action::Recur { done, next } => {
+                let index = position(tasks, *done)?;
+                tasks[index].completed = true;
+                tasks[index].completed_at = Some(Utc::now());
+                insert_at_id(tasks, next.clone());
+                renumber(tasks);
+            }
+            Action::Archive(moved) => {
+                for task in moved {
+                    let index = position(tasks, task.uuid)?;
+                    archived.push(tasks.remove(index));
+                }
+                renumber(tasks);
+                renumber(archived);
+            }
+            Action::Edit { after, .. } => {
+                let index = position(tasks, after.uuid)?;
+                tasks[index] = Task {
+                    id: tasks[index].id,
+                    ..(**after).clone()
+                };
+            }
+            Action::Tags { uuid, after, .. } => {
+                let index = position(tasks, *uuid)?;
+                tasks[index].tags = after.clone();
+            }
+            Action::Priority { uuid, after, .. } => {
+                let index = position(tasks, *uuid)?;
+                tasks[index].priority = after.clone();
+            }
+            Action::Import {
+                tasks: added,
+                archived: added_archived,
+            } => {
+                for task in added {
+                    insert_at_id(tasks, task.clone());
+                }
+                for task in added_archived {
+                    insert_at_id(archived, task.clone());
+                }
+                renumber(tasks);
+                renumber(archived);
+            }
+            Action::Merge {
+                after, after_archived, ..
+            } => {
+                *tasks = after.clone();
+                *archived = after_archived.clone();
+            }
+            Action::MoveOut { tasks: moved, .. } => {
+                for task in moved {
+                    let index = position(tasks, task.uuid)?;
+                    tasks.remove(index);
+                }
+                renumber(tasks);
+            }
+            Action::MoveIn { tasks: moved, .. } => {
+                for task in moved {
+                    insert_at_id(tasks, task.clone());
+                }
+                renumber(tasks);
+            }
+            Action::Batch(actions) => {
+                for action in actions {
+                    action.redo_steps(tasks, archived)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The tasks and the other list of a move between lists, also when it
+    /// is part of a batch, and whether they were moved out of this list.
+    pub fn list_move(&self) -> Option<(&[Task], &str, bool)> {
+        match self {
+            Action::MoveOut { tasks, to } => Some((tasks, to, true)),
+            Action::MoveIn { tasks, from } => Some((tasks, from, false)),
+            Action::Batch(actions) => actions.iter().find_map(Action::list_move),
+            _ => None,
+        }
+    }
+
+    /// The narrowest action that turns `before` into `after`, or `None` when
+    /// nothing changed.
+    pub fn for_edit(before: &Task, after: &Task) -> Option<Action> {
+        let tags_changed = after.tags != before.tags;
+        let priority_changed = after.priority != before.priority;
+        let other_changed = after.description != before.description
+            || after.due_date != before.due_date
+            || after.recurrence != before.recurrence
+            || after.depends_on != before.depends_on
+            || after.no_priority != before.no_priority;
+
+        match (other_changed, tags_changed, priority_changed) {
+            (false, false, false) => None,
+            (false, true, false) => Some(Action::Tags {
+                uuid: before.uuid,
+                before: before.tags.clone(),
+                after: after.tags.clone(),
+            }),
+            (false, false, true) => Some(Action::Priority {
+                uuid: before.uuid,
+                before: before.priority.clone(),
+                after: after.priority.clone(),
+            }),
+            _ => Some(Action::Edit {
+                before: Box::new(before.clone()),
+                after: Box::new(after.clone()),
+            }),
+        }
+    }
+
+    /// One line summary for `todo history` and status messages.
+    pub fn describe(&self) -> String {
+        match self {
+            Action::Add(task) => format!("Added task '{}'", task.description),
+            Action::Delete(task) => format!("Deleted task '{}'", task.description),
+            Action::Done(uuid) => format!("Marked task {} as done", short(uuid)),
+            Action::Recur { done, next } => format!(
+                "Marked task {} as done and added the next one, due {}",
+                short(done),
+                next.due_date.map(|d| dates::format(&d)).unwrap_or_default()
+            ),
+            Action::Archive(moved) => format!("Archived {} tasks", moved.len()),
+            Action::Edit { after, .. } => format!("Edited task '{}'", after.description),
+            Action::Tags { uuid, before, after } => {
+                format!("Changed tags of task {} from {:?} to {:?}", short(uuid), before, after)
+            }
+            Action::Priority { uuid, before, after } => {
+                format!(
+                    "Changed priority of task {} from {:?} to {:?}",
+                    short(uuid),
+                    before,
+                    after
+                )
+            }
+            Action::Import { tasks, archived } => {
+                format!("Imported {} tasks and {} archived tasks", tasks.len(), archived.len())
+            }
+            Action::Merge { .. } => "Merged changes made elsewhere".into(),
+            Action::MoveOut { tasks, to } => format!("Moved {} to list {}", moved(tasks), to),
+            Action::MoveIn { tasks, from } => format!("Moved {} here from list {}", moved(tasks), from),
+            Action::Batch(actions) => match actions.split_first() {
+                Some((first, [])) => first.describe(),
+                Some((first, rest)) => format!("{} and {} more", first.describe(), rest.len()),
+                None => "Nothing".into(),
+            },
+        }
+    }
+}
+
+/// Marks `tasks[index]` done. A recurring task gets its next instance added
+/// right after it, due on the rule's next date after the current due date
+/// (or after today when it has none), unless its parent task is done.
+/// Returns the action to record, or `None` if the task was already done.
+pub fn complete(tasks: &mut Vec<Task>, index: usize) -> Option<Action> {
+    let task = &mut tasks[index];
+    if task.completed {
+        return None;
+    }
+    task.completed = true;
+    task.completed_at = Some(Utc::now());
+    // A repeating step of a finished project ends with it.
+    let parent_done = task
+        .parent
+        .and_then(|p| models::position(tasks, p))
+        .is_some_and(|i| tasks[i].completed);
+    let task = &tasks[index];
+    let rule = match &task.recurrence {
+        Some(rule) if !parent_done => rule,
+        _ => return Some(Action::Done(task.uuid)),
+    };
+
+    let today = Local::now().date_naive();
+    let due = task.due_date.map(|d| d.naive_utc());
+    let Some(next_date) = rule.next_due(due.map_or(today, |d| d.date()), today) else {
+        return Some(Action::Done(task.uuid));
+    };
+    let time = due.map_or(NaiveTime::MIN, |d| d.time());
+    let recurrence = due.map_or_else(|| rule.clone(), |d| rule.anchored(d.date(), next_date));
+    let next = Task {
+        uuid: Uuid::new_v4(),
+        id: task.id + 1,
+        due_date: Some(next_date.and_time(time).and_utc()),
+        recurrence: Some(recurrence),
+        completed: false,
+        created: Some(Utc::now()),
+        completed_at: None,
+        ical_uid: None,
+        ..task.clone()
+    };
+    let done = task.uuid;
+    insert_at_id(tasks, next.clone());
+    renumber(tasks);
+    Some(Action::Recur { done, next })
+}
+
+/// Marks `tasks[index]` done, and with `subtasks` every unfinished task
+/// below it too, as a single action.
+pub fn complete_tree(tasks: &mut Vec<Task>, index: usize, subtasks: bool) -> Option<Action> {
+    let mut uuids = vec![tasks[index].uuid];
+    if subtasks {
+        uuids.extend(models::descendants(tasks, tasks[index].uuid));
+    }
+
+    let mut done = Vec::new();
+    for uuid in uuids {
+        // Recurring tasks add their next instance, which shifts indices.
+        if let Some(action) = models::position(tasks, uuid).and_then(|i| complete(tasks, i)) {
+            done.push(action);
+        }
+    }
+    match done.len() {
+        0 => None,
+        1 => done.pop(),
+        _ => Some(Action::Batch(done)),
+    }
+}
+
+/// Number of unfinished tasks below `uuid`.
+pub fn unfinished_subtasks(tasks: &[Task], uuid: Uuid) -> usize {
+    models::descendants(tasks, uuid)
+        .into_iter()
+        .filter_map(|u| models::position(tasks, u))
+        .filter(|&i| !tasks[i].completed)
+        .count()
+}
+
+/// Moves finished work to the archive. Subtasks go with their top-level
+/// task, so a tree is archived once every task in it is done and is never
+/// split between the lists.
+pub fn archive(tasks: &mut Vec<Task>, archived: &mut Vec<Task>) -> Option<Action> {
+    let mut moving = HashSet::new();
+    let rows = models::tree(tasks, &HashSet::new());
+    for root in rows.iter().filter(|row| row.depth == 0) {
+        let uuid = tasks[root.index].uuid;
+        let mut tree = models::descendants(tasks, uuid);
+        tree.push(uuid);
+        if tree
+            .iter()
+            .filter_map(|u| models::position(tasks, *u))
+            .all(|i| tasks[i].completed)
+        {
+            moving.extend(tree);
+        }
+    }
+    if moving.is_empty() {
+        return None;
+    }
+
+    let (moved, kept): (Vec<Task>, Vec<Task>) = tasks.drain(..).partition(|t| moving.contains(&t.uuid));
+    *tasks = kept;
+    archived.extend(moved.iter().cloned());
+    renumber(tasks);
+    renumber(archived);
+    Some(Action::Archive(moved))
+}
+
+fn position(tasks: &[Task], uuid: Uuid) -> Result<usize, ActionError> {
+    models::position(tasks, uuid).ok_or(ActionError::Missing(uuid))
+}
+
+/// Puts a task back at the display position it had when it was removed.
+fn insert_at_id(tasks: &mut Vec<Task>, task: Task) {
+    let index = (task.id as usize).saturating_sub(1).min(tasks.len());
+    tasks.insert(index, task);
+}
+
+fn short(uuid: &Uuid) -> String {
+    uuid.simple().to_string()[..8].to_string()
+}
+
+/// `task 'description'`, or `3 tasks` for a task moved with its subtasks.
+fn moved(tasks: &[Task]) -> String {
+    match tasks {
+        [task] => format!("task '{}'", task.description),
+        _ => format!("{} tasks", tasks.len()),
+    }
 }
@@ -1,10 +1,29 @@
 // src/cli.rs
-use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::exchange::Format as ExchangeFormat;
+use crate::models::Priority;
+use crate::output::{Field, Format};
+use crate::recurrence::Recurrence;
+use crate::sort::SortSpec;
+use crate::store::{Backend, LIST_ENV, STORE_ENV};
+use crate::sync::Prefer;
 
 #[derive(Parser)]
 #[command(name = "todo")]
 #[command(about = "A Rust-powered todo app", version)]
 pub struct Cli {
+    /// Path of the task store (default: the per-user data directory)
+    #[arg(long, global = true, env = STORE_ENV, value_name = "PATH")]
+    pub store: Option<PathBuf>,
+    /// Task list to work on (default: the default list)
+    #[arg(short, long, global = true, env = LIST_ENV, value_name = "NAME")]
+    pub list: Option<String>,
+    /// Offer again to copy a tasks.json left in the current directory by older versions
+    #[arg(long, global = true)]
+    pub migrate: bool,
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -15,38 +34,257 @@ pub enum Commands {
     Add {
         #[arg(short = 'D', long)]
         description: String,
-        #[arg(short, long)]
+        #[arg(
+            short,
+            long,
+            allow_hyphen_values = true,
+            help = "Due date: 2024-12-31, 2024-12-31T14:00, tomorrow, fri, +3d, in 2 weeks, eom..."
+        )]
         due_date: Option<String>,
         #[arg(short, long)]
         tags: Vec<String>,
         #[arg(short, long)]
-        priority: Option<String>, // Add this line
+        priority: Option<Priority>,
+        #[arg(
+            short,
+            long,
+            value_name = "RULE",
+            help = "Repeat: daily, weekly on mon,thu, monthly on 15, every 3 days, weekdays, or an RRULE like FREQ=WEEKLY;BYDAY=MO"
+        )]
+        recur: Option<Recurrence>,
+        #[arg(
+            long,
+            value_name = "ID",
+            help = "Make this a subtask of another task (display index or id prefix)"
+        )]
+        parent: Option<String>,
+        #[arg(long, value_name = "ID", help = "A task that must be done first (repeatable)")]
+        depends: Vec<String>,
     },
     /// List all tasks
-    List{
-        #[arg(short = 'd', long, help = "Sort by due date")]
+    List {
+        #[arg(short = 'd', long, help = "Sort by due date (same as --sort due+)")]
         sort_by_due_date: bool,
         #[arg(short = 't', long, help = "Filter by tags", num_args = 1..)]
         tags: Option<Vec<String>>,
-        #[arg(short = 'p', long, help = "Sort by priority")]
+        #[arg(
+            short = 'p',
+            long,
+            help = "Sort by priority, highest first (same as --sort priority-)"
+        )]
         sort_by_priority: bool,
+        #[arg(short, long, value_name = "KEYS", conflicts_with_all = ["sort_by_due_date", "sort_by_priority"], help = "Sort keys with directions, e.g. priority-,due+,created (keys: id, priority, due, created, completed, description)")]
+        sort: Option<SortSpec>,
+        #[arg(
+            short = 'a',
+            long,
+            help = "Only show unfinished tasks that aren't waiting for others"
+        )]
+        actionable: bool,
+        /// Filter, e.g. 'tag:work and (priority>=medium or due<+3d) and not done'; put words starting with
+        /// '-' after `--`
+        #[arg(value_name = "FILTER")]
+        query: Vec<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Show the most urgent unfinished tasks
+    Next {
+        #[arg(short = 'n', long, default_value_t = 10, help = "How many tasks to show")]
+        count: usize,
+    },
+    /// Undo the last N changes
+    Undo {
+        #[arg(default_value_t = 1)]
+        count: usize,
+    },
+    /// Redo the last N undone changes
+    Redo {
+        #[arg(default_value_t = 1)]
+        count: usize,
+    },
+    /// Show the changes that can be undone and redone
+    History,
+    /// Show the task lists and how many tasks each holds
+    Lists,
+    /// Move a task, with its subtasks, to another list
+    Move {
+        #[arg(short, long, help = "Display index or unique id prefix")]
+        id: String,
+        #[arg(long, value_name = "LIST", help = "List to move it to; a new name starts a new list")]
+        to: String,
     },
-    /// undo
-    Undo,
     /// Mark as done
     Done {
+        #[arg(short, long, help = "Display index or unique id prefix")]
+        id: String,
+        #[arg(short, long, help = "Also mark unfinished subtasks as done without asking")]
+        recursive: bool,
+    },
+    /// Change a task's description, due date, priority or tags
+    Modify {
+        #[arg(short, long, help = "Display index or unique id prefix")]
+        id: String,
+        #[arg(short = 'D', long)]
+        description: Option<String>,
+        #[arg(
+            short,
+            long,
+            allow_hyphen_values = true,
+            conflicts_with = "clear_due",
+            help = "New due date, in any format `add` accepts"
+        )]
+        due: Option<String>,
+        #[arg(long, help = "Remove the due date")]
+        clear_due: bool,
         #[arg(short, long)]
-        id: u32,
+        priority: Option<Priority>,
+        #[arg(short, long, value_name = "TAG")]
+        add_tag: Vec<String>,
+        #[arg(short, long, value_name = "TAG")]
+        remove_tag: Vec<String>,
+        #[arg(
+            long,
+            value_name = "RULE",
+            conflicts_with = "clear_recur",
+            help = "New recurrence rule, in any format `add` accepts"
+        )]
+        recur: Option<Recurrence>,
+        #[arg(long, help = "Stop the task from repeating")]
+        clear_recur: bool,
+        #[arg(long, value_name = "ID", help = "Add a task that must be done first")]
+        depends: Vec<String>,
+        #[arg(long, value_name = "ID", help = "Drop a dependency")]
+        remove_depends: Vec<String>,
+    },
+    /// Show what a task waits for and what waits for it
+    Deps {
+        #[arg(short, long, help = "Display index or unique id prefix")]
+        id: String,
     },
     /// Delete a task
     Delete {
-        #[arg(short, long)]
-        id: u32,
+        #[arg(short, long, help = "Display index or unique id prefix")]
+        id: String,
     },
     /// Acheive the things done
     Archive,
     /// List the Acheived things
-    ListArchived,
+    ListArchived {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Write the tasks in another app's format
+    Export {
+        #[arg(short, long, value_enum)]
+        format: ExchangeFormat,
+        #[arg(short, long, value_name = "FILE", help = "Write here instead of to stdout")]
+        output: Option<PathBuf>,
+        #[arg(short, long, help = "Include archived tasks")]
+        archived: bool,
+    },
+    /// Add the tasks from another app's file, skipping ones already here
+    Import {
+        #[arg(value_name = "FILE", help = "File to read, or - for stdin")]
+        file: PathBuf,
+        #[arg(
+            long,
+            value_enum,
+            help = "Format of the file (default: from its extension; .ics, .json for Taskwarrior, todo.txt otherwise)"
+        )]
+        from: Option<ExchangeFormat>,
+    },
+    /// Sync the active tasks with another place
+    Sync {
+        #[command(subcommand)]
+        target: SyncTarget,
+    },
+    /// Roll the task store back to one of its backups
+    Restore {
+        #[arg(short, long, value_name = "N", help = "Backup to restore, 1 being the most recent")]
+        backup: usize,
+    },
+    /// Squash the operation log of the oplog backend
+    Compact,
+    /// Copy the tasks into a store with another backend
+    Migrate {
+        #[arg(long, value_enum, help = "Backend to copy the tasks to")]
+        to: Backend,
+    },
     /// TUI obviously
     Tui,
 }
+
+#[derive(Subcommand)]
+pub enum SyncTarget {
+    /// Push and pull tasks to and from the CalDAV collection in the config
+    Caldav {
+        #[arg(
+            long,
+            value_enum,
+            help = "Settle tasks changed on both sides this way instead of reporting them as conflicts"
+        )]
+        prefer: Option<Prefer>,
+        #[arg(short = 'n', long, help = "Show what would change without changing anything")]
+        dry_run: bool,
+    },
+    /// Merge the tasks with the git repository in the config and push them
+    Git {
+        #[arg(
+            long,
+            value_enum,
+            help = "Settle changes made on both sides this way instead of keeping the local one"
+        )]
+        prefer: Option<Prefer>,
+        #[arg(short = 'n', long, help = "Show what would change without changing anything")]
+        dry_run: bool,
+    },
+}
+
+/// Output options shared by the list commands.
+#[derive(Args)]
+pub struct OutputArgs {
+    #[arg(short = 'f', long, value_enum, help = "Print for scripts instead of people")]
+    pub format: Option<Format>,
+    #[arg(
+        long,
+        value_name = "FIELDS",
+        value_delimiter = ',',
+        requires = "format",
+        help = "Fields to print, e.g. id,description,due_date (default: all for json, id to completed otherwise)"
+    )]
+    pub fields: Option<Vec<Field>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(args: &[&str]) -> (Vec<String>, Option<SortSpec>, Option<Format>) {
+        let cli = Cli::try_parse_from(["todo", "list"].iter().chain(args)).expect("parses");
+        match cli.command {
+            Some(Commands::List {
+                query, sort, output, ..
+            }) => (query, sort, output.format),
+            _ => panic!("not a list command"),
+        }
+    }
+
+    #[test]
+    fn flags_after_the_filter_are_flags() {
+        let (query, _, format) = list(&["tag:work", "--format", "json"]);
+        assert_eq!(query, ["tag:work"]);
+        assert_eq!(format, Some(Format::Json));
+
+        let (query, sort, _) = list(&["due:..", "--sort", "priority-"]);
+        assert_eq!(query, ["due:.."]);
+        assert_eq!(sort.map(|s| s.to_string()).as_deref(), Some("priority-"));
+    }
+
+    #[test]
+    fn filter_words_with_a_dash_go_after_double_dash() {
+        let (query, _, format) = list(&["-f", "csv", "--", "-urgent", "and", "not", "done"]);
+        assert_eq!(query, ["-urgent", "and", "not", "done"]);
+        assert_eq!(format, Some(Format::Csv));
+    }
+}
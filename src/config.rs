@@ -0,0 +1,789 @@
+// src/config.rs
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crossterm::event::KeyCode;
+use directories::BaseDirs;
+use mlua::{Lua, Table, Value};
+use ratatui::style::Color;
+use thiserror::Error;
+
+use crate::sort::{SortError, SortSpec};
+use crate::store::{Backend, DEFAULT_LIST};
+use crate::urgency::Coefficients;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("could not read config file {path}: {source}")]
+    Io { path: PathBuf, source: std::io::Error },
+    #[error("could not evaluate config file {path}: {source}")]
+    Lua { path: PathBuf, source: mlua::Error },
+    #[error("{key}: expected {expected}, found {found}")]
+    Type {
+        key: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[error("{key}: unknown option")]
+    UnknownKey { key: String },
+    #[error("{key} must be set")]
+    Missing { key: String },
+    #[error("{key} must be set to sync the {list} list; each list needs a collection of its own")]
+    NoCollection { key: String, list: String },
+    #[error("{key}: invalid color {value:?} (use a name like \"yellow\" or a hex value like \"#3498db\")")]
+    InvalidColor { key: String, value: String },
+    #[error("{key}: invalid key {value:?} (use a single character or a name like \"enter\", \"up\", \"tab\")")]
+    InvalidKey { key: String, value: String },
+    #[error("{key}: invalid backend {value:?} (expected \"json\", \"sqlite\" or \"oplog\")")]
+    InvalidBackend { key: String, value: String },
+    #[error("{key}: {source}")]
+    InvalidSort { key: String, source: SortError },
+    #[error("keybindings.{first} and keybindings.{second} are both bound to {key:?}")]
+    DuplicateKey {
+        first: &'static str,
+        second: &'static str,
+        key: String,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub colors: Colors,
+    pub keybindings: Keybindings,
+    pub store: StoreConfig,
+    pub sort: SortConfig,
+    pub urgency: Coefficients,
+    pub sync: SyncConfig,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SyncConfig {
+    pub caldav: Option<CaldavConfig>,
+    pub git: Option<GitConfig>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CaldavConfig {
+    /// The calendar collection, e.g.
+    /// `http://localhost:5232/user/tasks/`.
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// A shell command printing the password, e.g. `pass show caldav`.
+    pub password_command: Option<String>,
+    /// The collections of named lists, by list; `url` is the default list's.
+    pub lists: HashMap<String, String>,
+}
+
+impl CaldavConfig {
+    /// The settings for syncing `list`, which must have a collection.
+    pub fn for_list(&self, list: &str) -> Result<CaldavConfig, ConfigError> {
+        if list == DEFAULT_LIST {
+            return Ok(self.clone());
+        }
+        let url = self.lists.get(list).ok_or_else(|| ConfigError::NoCollection {
+            key: format!("sync.caldav.lists.{}", list),
+            list: list.into(),
+        })?;
+        Ok(CaldavConfig {
+            url: url.clone(),
+            ..self.clone()
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GitConfig {
+    /// A clone of the repository to keep the tasks in, e.g. `~/dotfiles`.
+    pub repo: PathBuf,
+    /// Where the tasks go in it.
+    pub file: String,
+    pub remote: String,
+    /// The repository's current branch when not set.
+    pub branch: Option<String>,
+}
+
+impl GitConfig {
+    /// The settings for syncing `list`. Named lists go in files of their
+    /// own next to `file`: `todo/tasks.json` becomes `todo/tasks.work.json`.
+    pub fn for_list(&self, list: &str) -> GitConfig {
+        if list == DEFAULT_LIST {
+            return self.clone();
+        }
+        let path = Path::new(&self.file);
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match path.extension() {
+            Some(extension) => format!("{}.{}.{}", stem, list, extension.to_string_lossy()),
+            None => format!("{}.{}", stem, list),
+        };
+        GitConfig {
+            file: path.with_file_name(name).to_string_lossy().into_owned(),
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SortConfig {
+    /// Order of `todo list` and the TUI when nothing else is asked for.
+    pub default: SortSpec,
+    /// Orders the TUI sort key cycles through, after `default`.
+    pub modes: Vec<SortSpec>,
+}
+
+impl Default for SortConfig {
+    fn default() -> Self {
+        SortConfig {
+            default: SortSpec::default(),
+            modes: SortSpec::default_modes(),
+        }
+    }
+}
+
+impl SortConfig {
+    /// `default` followed by the other modes, without repeats.
+    pub fn cycle(&self) -> Vec<SortSpec> {
+        let mut cycle = vec![self.default.clone()];
+        for mode in &self.modes {
+            if !cycle.contains(mode) {
+                cycle.push(mode.clone());
+            }
+        }
+        cycle
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StoreConfig {
+    /// `"json"`, `"sqlite"`, or `"oplog"` for a store in a folder synced
+    /// between devices.
+    pub backend: Backend,
+    /// How many previous versions of the store to keep as `tasks.json.1`
+    /// through `tasks.json.N`.
+    pub backups: usize,
+    /// How many actions `todo undo` can go back.
+    pub undo_levels: usize,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        StoreConfig {
+            backend: Backend::Json,
+            backups: 3,
+            undo_levels: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Colors {
+    /// Selected tab and selected task.
+    pub primary: Color,
+    /// Due date label.
+    pub warning: Color,
+    /// Overdue due dates.
+    pub error: Color,
+    /// Popup background.
+    pub background: Color,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Colors {
+            primary: Color::Yellow,
+            warning: Color::Magenta,
+            error: Color::Red,
+            background: Color::DarkGray,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    Quit,
+    NewTask,
+    NewSubtask,
+    EditTask,
+    MarkDone,
+    DeleteTask,
+    ArchiveTasks,
+    NavigateUp,
+    NavigateDown,
+    SwitchTabLeft,
+    SwitchTabRight,
+    NextList,
+    ToggleFold,
+    Filter,
+    CycleSort,
+    Undo,
+    Redo,
+}
+
+impl KeyAction {
+    pub const ALL: [KeyAction; 17] = [
+        KeyAction::Quit,
+        KeyAction::NewTask,
+        KeyAction::NewSubtask,
+        KeyAction::EditTask,
+        KeyAction::MarkDone,
+        KeyAction::DeleteTask,
+        KeyAction::ArchiveTasks,
+        KeyAction::NavigateUp,
+        KeyAction::NavigateDown,
+        KeyAction::SwitchTabLeft,
+        KeyAction::SwitchTabRight,
+        KeyAction::NextList,
+        KeyAction::ToggleFold,
+        KeyAction::Filter,
+        KeyAction::CycleSort,
+        KeyAction::Undo,
+        KeyAction::Redo,
+    ];
+
+    /// The name used for this action in the `keybindings` table.
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyAction::Quit => "quit",
+            KeyAction::NewTask => "new_task",
+            KeyAction::NewSubtask => "new_subtask",
+            KeyAction::EditTask => "edit_task",
+            KeyAction::MarkDone => "mark_done",
+            KeyAction::DeleteTask => "delete_task",
+            KeyAction::ArchiveTasks => "archive_tasks",
+            KeyAction::NavigateUp => "navigate_up",
+            KeyAction::NavigateDown => "navigate_down",
+            KeyAction::SwitchTabLeft => "switch_tab_left",
+            KeyAction::SwitchTabRight => "switch_tab_right",
+            KeyAction::NextList => "next_list",
+            KeyAction::ToggleFold => "toggle_fold",
+            KeyAction::Filter => "filter",
+            KeyAction::CycleSort => "cycle_sort",
+            KeyAction::Undo => "undo",
+            KeyAction::Redo => "redo",
+        }
+    }
+
+    fn default_key(self) -> KeyCode {
+        match self {
+            KeyAction::Quit => KeyCode::Char('q'),
+            KeyAction::NewTask => KeyCode::Char('a'),
+            KeyAction::NewSubtask => KeyCode::Char('A'),
+            KeyAction::EditTask => KeyCode::Char('e'),
+            KeyAction::MarkDone => KeyCode::Char('D'),
+            KeyAction::DeleteTask => KeyCode::Char('d'),
+            KeyAction::ArchiveTasks => KeyCode::Char('r'),
+            KeyAction::NavigateUp => KeyCode::Char('k'),
+            KeyAction::NavigateDown => KeyCode::Char('j'),
+            KeyAction::SwitchTabLeft => KeyCode::Char('h'),
+            KeyAction::SwitchTabRight => KeyCode::Char('l'),
+            KeyAction::NextList => KeyCode::Tab,
+            KeyAction::ToggleFold => KeyCode::Char('z'),
+            KeyAction::Filter => KeyCode::Char('/'),
+            KeyAction::CycleSort => KeyCode::Char('s'),
+            KeyAction::Undo => KeyCode::Char('u'),
+            KeyAction::Redo => KeyCode::Char('U'),
+        }
+    }
+
+    fn from_name(name: &str) -> Option<KeyAction> {
+        KeyAction::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Keybindings {
+    bindings: Vec<(KeyAction, KeyCode)>,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Keybindings {
+            bindings: KeyAction::ALL
+                .into_iter()
+                .map(|action| (action, action.default_key()))
+                .collect(),
+        }
+    }
+}
+
+impl Keybindings {
+    /// Looks up the action bound to `code`. The arrow keys always navigate,
+    /// whatever the letters are bound to.
+    pub fn action(&self, code: KeyCode) -> Option<KeyAction> {
+        if let Some((action, _)) = self.bindings.iter().find(|(_, key)| *key == code) {
+            return Some(*action);
+        }
+        match code {
+            KeyCode::Up => Some(KeyAction::NavigateUp),
+            KeyCode::Down => Some(KeyAction::NavigateDown),
+            KeyCode::Left => Some(KeyAction::SwitchTabLeft),
+            KeyCode::Right => Some(KeyAction::SwitchTabRight),
+            _ => None,
+        }
+    }
+
+    fn set(&mut self, action: KeyAction, code: KeyCode) {
+        if let Some(binding) = self.bindings.iter_mut().find(|(a, _)| *a == action) {
+            binding.1 = code;
+        }
+    }
+
+    fn check_duplicates(&self) -> Result<(), ConfigError> {
+        for (i, (first, key)) in self.bindings.iter().enumerate() {
+            if let Some((second, _)) = self.bindings[i + 1..].iter().find(|(_, k)| k == key) {
+                return Err(ConfigError::DuplicateKey {
+                    first: first.name(),
+                    second: second.name(),
+                    key: key_name(*key),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Name of the per-user config and data directories. `ProjectDirs` would
+/// lowercase it on Linux, which doesn't match the documented path.
+pub const APP_DIR: &str = "noteSage";
+
+/// `~/.config/noteSage` on Linux, or the platform equivalent.
+pub fn config_dir() -> Option<PathBuf> {
+    BaseDirs::new().map(|dirs| dirs.config_dir().join(APP_DIR))
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.lua"))
+}
+
+impl Config {
+    /// Loads the user's config file, falling back to the defaults when there
+    /// is none.
+    pub fn load() -> Result<Config, ConfigError> {
+        match config_path() {
+            Some(path) if path.exists() => Config::from_file(&path),
+            _ => Ok(Config::default()),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let source = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let lua = Lua::new();
+        let value: Value = lua
+            .load(&source)
+            .set_name(path.display().to_string())
+            .eval()
+            .map_err(|source| ConfigError::Lua {
+                path: path.to_path_buf(),
+                source,
+            })?;
+
+        let table = expect_table("config", value)?;
+        Config::from_table(table)
+    }
+
+    fn from_table(table: Table) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+
+        for (key, value) in entries("", table)? {
+            match key.as_str() {
+                "ui" => {
+                    for (key, value) in entries("ui", expect_table("ui", value)?)? {
+                        match key.as_str() {
+                            "colors" => config.colors = parse_colors(value)?,
+                            _ => return Err(unknown(&["ui", &key])),
+                        }
+                    }
+                }
+                "keybindings" => config.keybindings = parse_keybindings(value)?,
+                "store" => config.store = parse_store(value)?,
+                "sort" => config.sort = parse_sort(value)?,
+                "urgency" => config.urgency = parse_urgency(value)?,
+                "sync" => config.sync = parse_sync(value)?,
+                _ => return Err(unknown(&[&key])),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn parse_colors(value: Value) -> Result<Colors, ConfigError> {
+    let mut colors = Colors::default();
+
+    for (key, value) in entries("ui.colors", expect_table("ui.colors", value)?)? {
+        let full_key = format!("ui.colors.{}", key);
+        let slot = match key.as_str() {
+            "primary" => &mut colors.primary,
+            "warning" => &mut colors.warning,
+            "error" => &mut colors.error,
+            "background" => &mut colors.background,
+            _ => return Err(ConfigError::UnknownKey { key: full_key }),
+        };
+        let text = expect_string(&full_key, value)?;
+        *slot = parse_color(&text).ok_or(ConfigError::InvalidColor {
+            key: full_key,
+            value: text,
+        })?;
+    }
+
+    Ok(colors)
+}
+
+fn parse_keybindings(value: Value) -> Result<Keybindings, ConfigError> {
+    let mut keybindings = Keybindings::default();
+
+    for (key, value) in entries("keybindings", expect_table("keybindings", value)?)? {
+        let full_key = format!("keybindings.{}", key);
+        let action = KeyAction::from_name(&key).ok_or(ConfigError::UnknownKey { key: full_key.clone() })?;
+        let text = expect_string(&full_key, value)?;
+        let code = parse_key(&text).ok_or(ConfigError::InvalidKey {
+            key: full_key,
+            value: text,
+        })?;
+        keybindings.set(action, code);
+    }
+
+    keybindings.check_duplicates()?;
+    Ok(keybindings)
+}
+
+fn parse_store(value: Value) -> Result<StoreConfig, ConfigError> {
+    let mut store = StoreConfig::default();
+
+    for (key, value) in entries("store", expect_table("store", value)?)? {
+        let full_key = format!("store.{}", key);
+        match key.as_str() {
+            "backend" => {
+                store.backend = match expect_string(&full_key, value)?.as_str() {
+                    "json" => Backend::Json,
+                    "sqlite" => Backend::Sqlite,
+                    "oplog" => Backend::Oplog,
+                    other => {
+                        return Err(ConfigError::InvalidBackend {
+                            key: full_key,
+                            value: other.to_string(),
+                        })
+                    }
+                }
+            }
+            "backups" => store.backups = expect_count(&full_key, value)?,
+            "undo_levels" => store.undo_levels = expect_count(&full_key, value)?,
+            _ => return Err(ConfigError::UnknownKey { key: full_key }),
+        }
+    }
+
+    Ok(store)
+}
+
+fn parse_sort(value: Value) -> Result<SortConfig, ConfigError> {
+    let mut sort = SortConfig::default();
+    let spec = |key: &str, value: Value| -> Result<SortSpec, ConfigError> {
+        expect_string(key, value)?
+            .parse()
+            .map_err(|source| ConfigError::InvalidSort {
+                key: key.into(),
+                source,
+            })
+    };
+
+    for (key, value) in entries("sort", expect_table("sort", value)?)? {
+        let full_key = format!("sort.{}", key);
+        match key.as_str() {
+            "default" => sort.default = spec(&full_key, value)?,
+            "modes" => {
+                sort.modes = expect_table(&full_key, value)?
+                    .sequence_values::<Value>()
+                    .enumerate()
+                    .map(|(i, value)| {
+                        let key = format!("{}[{}]", full_key, i + 1);
+                        let value = value.map_err(|source| ConfigError::Lua {
+                            path: config_path().unwrap_or_default(),
+                            source,
+                        })?;
+                        spec(&key, value)
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            _ => return Err(ConfigError::UnknownKey { key: full_key }),
+        }
+    }
+
+    Ok(sort)
+}
+
+fn parse_urgency(value: Value) -> Result<Coefficients, ConfigError> {
+    let mut urgency = Coefficients::default();
+
+    for (key, value) in entries("urgency", expect_table("urgency", value)?)? {
+        let full_key = format!("urgency.{}", key);
+        match key.as_str() {
+            "priority" => {
+                for (key, value) in entries(&full_key, expect_table(&full_key, value)?)? {
+                    let full_key = format!("urgency.priority.{}", key);
+                    let slot = match key.as_str() {
+                        "high" => &mut urgency.priority_high,
+                        "medium" => &mut urgency.priority_medium,
+                        "low" => &mut urgency.priority_low,
+                        _ => return Err(ConfigError::UnknownKey { key: full_key }),
+                    };
+                    *slot = expect_number(&full_key, value)?;
+                }
+            }
+            "due" => urgency.due = expect_number(&full_key, value)?,
+            "age" => urgency.age = expect_number(&full_key, value)?,
+            "age_max" => urgency.age_max = expect_number(&full_key, value)?,
+            "tags" => urgency.tags = expect_number(&full_key, value)?,
+            "blocked" => urgency.blocked = expect_number(&full_key, value)?,
+            "blocking" => urgency.blocking = expect_number(&full_key, value)?,
+            "tag" => {
+                urgency.tag.clear();
+                for (tag, value) in entries(&full_key, expect_table(&full_key, value)?)? {
+                    let number = expect_number(&format!("{}.{}", full_key, tag), value)?;
+                    urgency.tag.insert(tag, number);
+                }
+            }
+            _ => return Err(ConfigError::UnknownKey { key: full_key }),
+        }
+    }
+
+    Ok(urgency)
+}
+
+fn parse_sync(value: Value) -> Result<SyncConfig, ConfigError> {
+    let mut sync = SyncConfig::default();
+
+    for (key, value) in entries("sync", expect_table("sync", value)?)? {
+        let full_key = format!("sync.{}", key);
+        match key.as_str() {
+            "caldav" => {
+                let mut caldav = CaldavConfig::default();
+                let mut url = None;
+                for (key, value) in entries(&full_key, expect_table(&full_key, value)?)? {
+                    let full_key = format!("sync.caldav.{}", key);
+                    match key.as_str() {
+                        "url" => url = Some(expect_string(&full_key, value)?),
+                        "username" => caldav.username = Some(expect_string(&full_key, value)?),
+                        "password" => caldav.password = Some(expect_string(&full_key, value)?),
+                        "password_command" => caldav.password_command = Some(expect_string(&full_key, value)?),
+                        "lists" => {
+                            for (list, value) in entries(&full_key, expect_table(&full_key, value)?)? {
+                                let url = expect_string(&format!("{}.{}", full_key, list), value)?;
+                                caldav.lists.insert(list, url);
+                            }
+                        }
+                        _ => return Err(ConfigError::UnknownKey { key: full_key }),
+                    }
+                }
+                caldav.url = url.ok_or(ConfigError::Missing {
+                    key: "sync.caldav.url".into(),
+                })?;
+                sync.caldav = Some(caldav);
+            }
+            "git" => {
+                let mut repo = None;
+                let mut git = GitConfig {
+                    repo: PathBuf::new(),
+                    file: "tasks.json".into(),
+                    remote: "origin".into(),
+                    branch: None,
+                };
+                for (key, value) in entries(&full_key, expect_table(&full_key, value)?)? {
+                    let full_key = format!("sync.git.{}", key);
+                    match key.as_str() {
+                        "repo" => repo = Some(expand_home(&expect_string(&full_key, value)?)),
+                        "file" => git.file = expect_string(&full_key, value)?,
+                        "remote" => git.remote = expect_string(&full_key, value)?,
+                        "branch" => git.branch = Some(expect_string(&full_key, value)?),
+                        _ => return Err(ConfigError::UnknownKey { key: full_key }),
+                    }
+                }
+                git.repo = repo.ok_or(ConfigError::Missing {
+                    key: "sync.git.repo".into(),
+                })?;
+                sync.git = Some(git);
+            }
+            _ => return Err(ConfigError::UnknownKey { key: full_key }),
+        }
+    }
+
+    Ok(sync)
+}
+
+/// `~/dotfiles` in the home directory.
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), BaseDirs::new()) {
+        (Some(rest), Some(dirs)) => dirs.home_dir().join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Accepts the terminal color names ("yellow", "dark_gray", ...) and
+/// `#rrggbb` / `#rgb` hex values.
+pub fn parse_color(text: &str) -> Option<Color> {
+    if let Some(hex) = text.strip_prefix('#') {
+        let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+        return match hex.len() {
+            6 if hex.is_ascii() => Some(Color::Rgb(
+                channel(&hex[0..2])?,
+                channel(&hex[2..4])?,
+                channel(&hex[4..6])?,
+            )),
+            3 if hex.is_ascii() => Some(Color::Rgb(
+                channel(&hex[0..1])? * 17,
+                channel(&hex[1..2])? * 17,
+                channel(&hex[2..3])? * 17,
+            )),
+            _ => None,
+        };
+    }
+
+    let name = text.to_lowercase().replace(['_', '-', ' '], "");
+    let color = match name.as_str() {
+        "reset" | "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return None,
+    };
+    Some(color)
+}
+
+/// Accepts a single character or one of the special key names.
+pub fn parse_key(text: &str) -> Option<KeyCode> {
+    let mut chars = text.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+
+    let code = match text.to_lowercase().as_str() {
+        "space" => KeyCode::Char(' '),
+        "enter" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backtab" | "shift-tab" => KeyCode::BackTab,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        other => {
+            let n: u8 = other.strip_prefix('f')?.parse().ok()?;
+            if (1..=12).contains(&n) {
+                KeyCode::F(n)
+            } else {
+                return None;
+            }
+        }
+    };
+    Some(code)
+}
+
+/// Human readable name of a key, the inverse of `parse_key`.
+pub fn key_name(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(' ') => "space".into(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("f{}", n),
+        other => format!("{:?}", other).to_lowercase(),
+    }
+}
+
+fn entries<'lua>(prefix: &str, table: Table<'lua>) -> Result<Vec<(String, Value<'lua>)>, ConfigError> {
+    let mut entries = Vec::new();
+    for pair in table.pairs::<Value, Value>() {
+        let (key, value) = pair.map_err(|source| ConfigError::Lua {
+            path: config_path().unwrap_or_default(),
+            source,
+        })?;
+        let key = match key {
+            Value::String(s) => s.to_string_lossy().into_owned(),
+            other => {
+                return Err(ConfigError::Type {
+                    key: if prefix.is_empty() {
+                        "config".into()
+                    } else {
+                        prefix.into()
+                    },
+                    expected: "string keys",
+                    found: other.type_name(),
+                })
+            }
+        };
+        entries.push((key, value));
+    }
+    Ok(entries)
+}
+
+fn expect_table<'lua>(key: &str, value: Value<'lua>) -> Result<Table<'lua>, ConfigError> {
+    match value {
+        Value::Table(table) => Ok(table),
+        other => Err(ConfigError::Type {
+            key: key.into(),
+            expected: "a table",
+            found: other.type_name(),
+        }),
+    }
+}
+
+fn expect_string(key: &str, value: Value) -> Result<String, ConfigError> {
+    match value {
+        Value::String(s) => Ok(s.to_string_lossy().into_owned()),
+        other => Err(ConfigError::Type {
+            key: key.into(),
+            expected: "a string",
+            found: other.type_name(),
+        }),
+    }
+}
+
+fn expect_count(key: &str, value: Value) -> Result<usize, ConfigError> {
+    let count = match value {
+        Value::Integer(n) => usize::try_from(n).ok(),
+        Value::Number(n) if n.fract() == 0.0 && n >= 0.0 => Some(n as usize),
+        _ => None,
+    };
+    count.ok_or(ConfigError::Type {
+        key: key.into(),
+        expected: "a non-negative integer",
+        found: value.type_name(),
+    })
+}
+
+fn expect_number(key: &str, value: Value) -> Result<f64, ConfigError> {
+    match value {
+        Value::Integer(n) => Ok(n as f64),
+        Value::Number(n) => Ok(n),
+        other => Err(ConfigError::Type {
+            key: key.into(),
+            expected: "a number",
+            found: other.type_name(),
+        }),
+    }
+}
+
+fn unknown(path: &[&str]) -> ConfigError {
+    ConfigError::UnknownKey { key: path.join(".") }
+}
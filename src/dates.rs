@@ -0,0 +1,308 @@
+// src/dates.rs
+//
+// Due dates are "floating": the wall clock time the user typed, stored as if
+// it were UTC, so that a task due "2024-12-31" shows that date in any time
+// zone. Everything here works on naive local dates and times.
+use chrono::{DateTime, Datelike, Days, Duration, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("invalid date {input:?}: {reason}")]
+pub struct DateError {
+    pub input: String,
+    pub reason: String,
+}
+
+/// Shows a due date as `2024-12-31`, with the time only when it isn't midnight.
+pub fn format(date: &DateTime<Utc>) -> String {
+    if date.time() == NaiveTime::MIN {
+        date.format("%Y-%m-%d").to_string()
+    } else {
+        date.format("%Y-%m-%d %H:%M").to_string()
+    }
+}
+
+/// Whether a due date has passed. Due dates are floating, so this compares
+/// them with the local wall clock, not with the current time in UTC.
+pub fn is_overdue(due: &DateTime<Utc>) -> bool {
+    due.naive_utc() < Local::now().naive_local()
+}
+
+/// Parses a due date relative to the current local date. See `parse_at`.
+pub fn parse_due(input: &str) -> Result<DateTime<Utc>, DateError> {
+    parse_at(input, Local::now().naive_local()).map(|d| d.and_utc())
+}
+
+/// Parses a date the way a user would type it:
+///
+/// - ISO dates and times: `2024-12-31`, `2024-12-31T14:30`, `2024-12-31 14:30:00`,
+///   or RFC 3339 with an offset, which is converted to local time
+/// - `now`, `today`, `tomorrow`, `yesterday`
+/// - weekdays: `fri` is the next Friday after today, `next fri` the Friday
+///   of next week
+/// - `+3d`, `-1w`, `+2m`, `+1y`, and `in 2 weeks`, `in a month`, `next week`
+/// - `sow`/`eow`, `som`/`eom`, `soy`/`eoy`: start or end of this week, month, year
+///
+/// Anything but an explicit time of day may be followed by a time such as
+/// `14:30`, `9am` or `at 5pm`; otherwise dates are at midnight.
+pub fn parse_at(input: &str, now: NaiveDateTime) -> Result<NaiveDateTime, DateError> {
+    let error = |reason: &str| DateError {
+        input: input.to_string(),
+        reason: reason.to_string(),
+    };
+
+    let text = input.trim().to_lowercase();
+    if text.is_empty() {
+        return Err(error("empty date"));
+    }
+    if let Some(date_time) = parse_iso(&text) {
+        return Ok(date_time);
+    }
+    if text == "now" {
+        return Ok(now);
+    }
+
+    let mut words: Vec<&str> = text.split_whitespace().collect();
+    let mut time = None;
+    if words.len() > 1 {
+        if let Some(t) = words.last().and_then(|w| parse_time(w)) {
+            time = Some(t);
+            words.pop();
+            if words.last() == Some(&"at") {
+                words.pop();
+            }
+        }
+    }
+
+    let date = parse_relative(&words, now.date())
+        .ok_or_else(|| error("expected a date like 2024-12-31, tomorrow, fri, +3d or in 2 weeks"))?;
+    Ok(date.and_time(time.unwrap_or(NaiveTime::MIN)))
+}
+
+fn parse_iso(text: &str) -> Option<NaiveDateTime> {
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Some(date.and_time(NaiveTime::MIN));
+    }
+    for format in [
+        "%Y-%m-%dt%H:%M:%S",
+        "%Y-%m-%dt%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(date_time) = NaiveDateTime::parse_from_str(text, format) {
+            return Some(date_time);
+        }
+    }
+    DateTime::parse_from_rfc3339(&text.to_uppercase())
+        .ok()
+        .map(|d| d.with_timezone(&Local).naive_local())
+}
+
+/// `14:30`, `9am`, `5pm`, `12:15pm`.
+fn parse_time(word: &str) -> Option<NaiveTime> {
+    let (clock, offset) = if let Some(clock) = word.strip_suffix("am") {
+        (clock, 0)
+    } else if let Some(clock) = word.strip_suffix("pm") {
+        (clock, 12)
+    } else {
+        if !word.contains(':') {
+            return None;
+        }
+        (word, 0)
+    };
+
+    let (hour, minute) = match clock.split_once(':') {
+        Some((h, m)) => (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?),
+        None => (clock.parse::<u32>().ok()?, 0),
+    };
+    let hour = match (word.ends_with('m'), hour) {
+        (true, 1..=11) => hour + offset,
+        (true, 12) => offset,
+        (true, _) => return None,
+        (false, _) => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+fn parse_relative(words: &[&str], today: NaiveDate) -> Option<NaiveDate> {
+    match words {
+        ["today"] | ["tod"] => Some(today),
+        ["tomorrow"] | ["tom"] => today.checked_add_days(Days::new(1)),
+        ["yesterday"] => today.checked_sub_days(Days::new(1)),
+        ["next", "week"] => add(today, 1, Unit::Week),
+        ["next", "month"] => add(today, 1, Unit::Month),
+        ["next", "year"] => add(today, 1, Unit::Year),
+        [day] if weekday(day).is_some() => {
+            let target = weekday(day)?;
+            let ahead =
+                (7 + target.num_days_from_monday() as i64 - today.weekday().num_days_from_monday() as i64 - 1) % 7 + 1;
+            today.checked_add_signed(Duration::days(ahead))
+        }
+        ["next", day] if weekday(day).is_some() => {
+            let target = weekday(day)?.num_days_from_monday() as i64;
+            let ahead = 7 - today.weekday().num_days_from_monday() as i64 + target;
+            today.checked_add_signed(Duration::days(ahead))
+        }
+        ["sow"] => today.checked_sub_days(Days::new(today.weekday().num_days_from_monday() as u64)),
+        ["eow"] => today.checked_add_days(Days::new(6 - today.weekday().num_days_from_monday() as u64)),
+        ["som"] => today.with_day(1),
+        ["eom"] => today.with_day(1)?.checked_add_months(Months::new(1))?.pred_opt(),
+        ["soy"] => NaiveDate::from_ymd_opt(today.year(), 1, 1),
+        ["eoy"] => NaiveDate::from_ymd_opt(today.year(), 12, 31),
+        ["in", count, unit] => {
+            let count = match *count {
+                "a" | "an" | "one" => 1,
+                n => n.parse().ok()?,
+            };
+            add(today, count, Unit::parse(unit)?)
+        }
+        [offset] => {
+            let (sign, rest) = match offset.as_bytes().first()? {
+                b'+' => (1, &offset[1..]),
+                b'-' => (-1, &offset[1..]),
+                _ => return None,
+            };
+            let split = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            let count: i64 = rest[..split].parse().ok()?;
+            let unit = match &rest[split..] {
+                "" => Unit::Day,
+                unit => Unit::parse(unit)?,
+            };
+            add(today, sign * count, unit)
+        }
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Unit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Unit {
+    fn parse(text: &str) -> Option<Unit> {
+        match text {
+            "d" | "day" | "days" => Some(Unit::Day),
+            "w" | "wk" | "week" | "weeks" => Some(Unit::Week),
+            "m" | "mo" | "month" | "months" => Some(Unit::Month),
+            "y" | "yr" | "year" | "years" => Some(Unit::Year),
+            _ => None,
+        }
+    }
+}
+
+fn add(date: NaiveDate, count: i64, unit: Unit) -> Option<NaiveDate> {
+    match unit {
+        Unit::Day => date.checked_add_signed(Duration::try_days(count)?),
+        Unit::Week => date.checked_add_signed(Duration::try_weeks(count)?),
+        Unit::Month | Unit::Year => {
+            let months = if let Unit::Year = unit {
+                count.checked_mul(12)?
+            } else {
+                count
+            };
+            let months = Months::new(u32::try_from(months.unsigned_abs()).ok()?);
+            if count >= 0 {
+                date.checked_add_months(months)
+            } else {
+                date.checked_sub_months(months)
+            }
+        }
+    }
+}
+
+fn weekday(word: &str) -> Option<Weekday> {
+    let day = match word {
+        "mon" | "monday" => Weekday::Mon,
+        "tue" | "tues" | "tuesday" => Weekday::Tue,
+        "wed" | "wednesday" => Weekday::Wed,
+        "thu" | "thur" | "thurs" | "thursday" => Weekday::Thu,
+        "fri" | "friday" => Weekday::Fri,
+        "sat" | "saturday" => Weekday::Sat,
+        "sun" | "sunday" => Weekday::Sun,
+        _ => return None,
+    };
+    Some(day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wednesday 2026-10-14, 10:30.
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 14)
+            .unwrap()
+            .and_hms_opt(10, 30, 0)
+            .unwrap()
+    }
+
+    fn parse(input: &str) -> String {
+        match parse_at(input, now()) {
+            Ok(date) => format(&date.and_utc()),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn iso_dates_and_times() {
+        assert_eq!(parse("2024-12-31"), "2024-12-31");
+        assert_eq!(parse("2024-12-31T14:30"), "2024-12-31 14:30");
+        assert_eq!(parse("2024-12-31 14:30:15"), "2024-12-31 14:30");
+        assert_eq!(
+            parse("2024-02-30"),
+            "invalid date \"2024-02-30\": expected a date like 2024-12-31, tomorrow, fri, +3d or in 2 weeks"
+        );
+        assert_eq!(parse(" "), "invalid date \" \": empty date");
+    }
+
+    #[test]
+    fn relative_days() {
+        assert_eq!(parse("now"), "2026-10-14 10:30");
+        assert_eq!(parse("Today"), "2026-10-14");
+        assert_eq!(parse("tomorrow"), "2026-10-15");
+        assert_eq!(parse("yesterday"), "2026-10-13");
+        assert_eq!(parse("+3d"), "2026-10-17");
+        assert_eq!(parse("+3"), "2026-10-17");
+        assert_eq!(parse("-1w"), "2026-10-07");
+        assert_eq!(parse("+2m"), "2026-12-14");
+        assert_eq!(parse("in a month"), "2026-11-14");
+        assert_eq!(parse("in 2 weeks"), "2026-10-28");
+        assert_eq!(parse("next week"), "2026-10-21");
+        assert_eq!(parse("sow"), "2026-10-12");
+        assert_eq!(parse("eow"), "2026-10-18");
+        assert_eq!(parse("eom"), "2026-10-31");
+        assert_eq!(parse("eoy"), "2026-12-31");
+        assert!(parse("in many days").starts_with("invalid date"));
+    }
+
+    #[test]
+    fn weekdays() {
+        assert_eq!(parse("fri"), "2026-10-16");
+        assert_eq!(parse("friday"), "2026-10-16");
+        // Today's weekday is a week away.
+        assert_eq!(parse("wed"), "2026-10-21");
+        assert_eq!(parse("mon"), "2026-10-19");
+
+        assert_eq!(parse("next fri"), "2026-10-23");
+        assert_eq!(parse("next wed"), "2026-10-21");
+        assert_eq!(parse("next mon"), "2026-10-19");
+        let sunday = NaiveDate::from_ymd_opt(2026, 10, 18).unwrap().and_time(NaiveTime::MIN);
+        assert_eq!(
+            parse_at("next sun", sunday).unwrap().date(),
+            NaiveDate::from_ymd_opt(2026, 10, 25).unwrap()
+        );
+    }
+
+    #[test]
+    fn times_of_day() {
+        assert_eq!(parse("tomorrow 9am"), "2026-10-15 09:00");
+        assert_eq!(parse("fri at 17:30"), "2026-10-16 17:30");
+        assert_eq!(parse("next fri 12:15pm"), "2026-10-23 12:15");
+        assert_eq!(parse("today 12am"), "2026-10-14");
+        assert!(parse("tomorrow 13pm").starts_with("invalid date"));
+    }
+}
@@ -0,0 +1,134 @@
+// src/exchange.rs
+//
+// Moving tasks in and out of other todo apps' formats.
+pub mod ical;
+pub mod taskwarrior;
+pub mod todotxt;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::models::{renumber, Task};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// One task per line, see http://todotxt.org
+    Todotxt,
+    /// The JSON of `task export`
+    Taskwarrior,
+    /// iCalendar VTODOs, for calendar clients
+    Ics,
+}
+
+impl Format {
+    /// Guesses the format of a file from its extension: `.ics` for
+    /// iCalendar, `.json` for Taskwarrior and todo.txt for anything else.
+    pub fn detect(path: &Path) -> Format {
+        match path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase)
+            .as_deref()
+        {
+            Some("ics" | "ical") => Format::Ics,
+            Some("json") => Format::Taskwarrior,
+            _ => Format::Todotxt,
+        }
+    }
+}
+
+/// A file that can't be read at all.
+#[derive(Debug, Error)]
+#[error("not a {format} file: {reason}")]
+pub struct ExchangeError {
+    format: &'static str,
+    reason: String,
+}
+
+/// A line or entry of an import that was skipped.
+#[derive(Debug)]
+pub struct ImportError {
+    /// Where it is, e.g. `line 4` or `task 12`.
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// What a file held: the tasks that could be read, for the active list and
+/// the archive, and the parts that couldn't.
+#[derive(Debug, Default)]
+pub struct Parsed {
+    pub tasks: Vec<Task>,
+    pub archived: Vec<Task>,
+    pub errors: Vec<ImportError>,
+}
+
+/// The tasks an import added, numbered as they now are in their list.
+#[derive(Debug, Default)]
+pub struct Merged {
+    pub tasks: Vec<Task>,
+    pub archived: Vec<Task>,
+    pub duplicates: usize,
+}
+
+/// Writes the active tasks and then the archived ones. Formats without an
+/// archive of their own only get the archived tasks with `archived`.
+pub fn export(format: Format, tasks: &[Task], archived: &[Task]) -> Result<String, ExchangeError> {
+    match format {
+        Format::Todotxt => Ok(todotxt::export(tasks.iter().chain(archived))),
+        Format::Taskwarrior => taskwarrior::export(tasks, archived),
+        Format::Ics => Ok(ical::export(tasks, archived)),
+    }
+}
+
+pub fn parse(format: Format, text: &str) -> Result<Parsed, ExchangeError> {
+    match format {
+        Format::Todotxt => Ok(todotxt::parse(text)),
+        Format::Taskwarrior => taskwarrior::parse(text),
+        Format::Ics => Ok(ical::parse(text)),
+    }
+}
+
+/// Appends the imported tasks that aren't already in either list. A task is
+/// a duplicate when its uuid or iCalendar UID is known, or when a task with
+/// the same description and due date exists.
+pub fn merge(tasks: &mut Vec<Task>, archived: &mut Vec<Task>, parsed: Parsed, now: DateTime<Utc>) -> Merged {
+    let key = |task: &Task| {
+        let description = task.description.split_whitespace().collect::<Vec<_>>().join(" ");
+        (description.to_lowercase(), task.due_date)
+    };
+    let mut uuids: HashSet<Uuid> = tasks.iter().chain(archived.iter()).map(|t| t.uuid).collect();
+    let mut uids: HashSet<String> = tasks.iter().chain(archived.iter()).map(Task::ical_uid).collect();
+    let mut keys: HashSet<_> = tasks.iter().chain(archived.iter()).map(key).collect();
+
+    let mut merged = Merged::default();
+    let mut add = |list: &mut Vec<Task>, imported: Vec<Task>, duplicates: &mut usize| {
+        let start = list.len();
+        for mut task in imported {
+            if uuids.contains(&task.uuid) || uids.contains(&task.ical_uid()) || !keys.insert(key(&task)) {
+                *duplicates += 1;
+                continue;
+            }
+            uuids.insert(task.uuid);
+            uids.insert(task.ical_uid());
+            task.created.get_or_insert(now);
+            list.push(task);
+        }
+        renumber(list);
+        list[start..].to_vec()
+    };
+    merged.tasks = add(tasks, parsed.tasks, &mut merged.duplicates);
+    merged.archived = add(archived, parsed.archived, &mut merged.duplicates);
+    merged
+}
@@ -0,0 +1,423 @@
+// src/exchange/ical.rs
+//
+// iCalendar (RFC 5545) VTODO components, as calendar clients and CalDAV
+// servers exchange them.
+//
+// The UID is the task's uuid, or the UID it was imported with when that
+// wasn't a uuid, so a task keeps its UID however often it travels. Due
+// dates are written as floating local times (dates when at midnight), like
+// they are stored. Completed tasks stay in the active list; CANCELLED
+// tasks go to the archive without being done.
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use uuid::Uuid;
+
+use super::{ImportError, Parsed};
+use crate::models::{Priority, Task};
+use crate::recurrence::Recurrence;
+
+const PRODID: &str = "-//noteSage//todoSage//EN";
+
+/// A VCALENDAR with a VTODO for each task. The flag marks archived tasks.
+pub fn calendar<'a>(entries: impl IntoIterator<Item = (&'a Task, bool)>) -> String {
+    let now = Utc::now();
+    let mut out = String::new();
+    push(&mut out, "BEGIN:VCALENDAR");
+    push(&mut out, "VERSION:2.0");
+    push(&mut out, &format!("PRODID:{}", PRODID));
+    for (task, archived) in entries {
+        vtodo(&mut out, task, archived, now);
+    }
+    push(&mut out, "END:VCALENDAR");
+    out
+}
+
+pub fn export(tasks: &[Task], archived: &[Task]) -> String {
+    calendar(
+        tasks
+            .iter()
+            .map(|t| (t, false))
+            .chain(archived.iter().map(|t| (t, true))),
+    )
+}
+
+fn vtodo(out: &mut String, task: &Task, archived: bool, now: DateTime<Utc>) {
+    push(out, "BEGIN:VTODO");
+    push(out, &format!("UID:{}", escape(&task.ical_uid())));
+    push(out, &format!("DTSTAMP:{}", utc(now)));
+    if let Some(created) = task.created {
+        push(out, &format!("CREATED:{}", utc(created)));
+    }
+    push(out, &format!("SUMMARY:{}", escape(&task.description)));
+    if let Some(due) = task.due_date {
+        if due.time() == NaiveTime::MIN {
+            push(out, &format!("DUE;VALUE=DATE:{}", due.format("%Y%m%d")));
+        } else {
+            push(out, &format!("DUE:{}", due.format("%Y%m%dT%H%M%S")));
+        }
+    }
+    let priority = match task.priority {
+        Priority::High => 1,
+        Priority::Medium => 5,
+        Priority::Low => 9,
+    };
+    push(out, &format!("PRIORITY:{}", priority));
+    if !task.tags.is_empty() {
+        let tags: Vec<String> = task.tags.iter().map(|t| escape(t)).collect();
+        push(out, &format!("CATEGORIES:{}", tags.join(",")));
+    }
+    if task.completed {
+        push(out, "STATUS:COMPLETED");
+        push(out, "PERCENT-COMPLETE:100");
+        push(out, &format!("COMPLETED:{}", utc(task.completed_at.unwrap_or(now))));
+    } else if archived {
+        push(out, "STATUS:CANCELLED");
+    } else {
+        push(out, "STATUS:NEEDS-ACTION");
+    }
+    if let Some(recurrence) = &task.recurrence {
+        push(out, &format!("RRULE:{}", recurrence));
+    }
+    if let Some(parent) = task.parent {
+        push(out, &format!("RELATED-TO;RELTYPE=PARENT:{}", parent));
+    }
+    push(out, "END:VTODO");
+}
+
+pub fn parse(text: &str) -> Parsed {
+    let mut parsed = Parsed::default();
+    // The VTODO being read: where it started and its properties.
+    let mut current: Option<(usize, Vec<Property>)> = None;
+    // Depth of components nested in it, such as VALARM.
+    let mut nested = 0;
+
+    for (number, line) in unfold(text) {
+        let Some(property) = Property::parse(&line) else {
+            continue;
+        };
+        match (
+            property.name.as_str(),
+            property.value.to_uppercase().as_str(),
+            &mut current,
+        ) {
+            ("BEGIN", "VTODO", None) => current = Some((number, Vec::new())),
+            ("BEGIN", _, Some(_)) => nested += 1,
+            ("END", _, Some(_)) if nested > 0 => nested -= 1,
+            ("END", "VTODO", Some(_)) => {
+                let (start, properties) = current.take().expect("inside a VTODO");
+                match to_task(properties) {
+                    Ok((task, true)) => parsed.archived.push(task),
+                    Ok((task, false)) => parsed.tasks.push(task),
+                    Err(message) => parsed.errors.push(ImportError {
+                        location: format!("line {}", start),
+                        message,
+                    }),
+                }
+            }
+            (_, _, Some((_, properties))) if nested == 0 => properties.push(property),
+            _ => {}
+        }
+    }
+    if let Some((start, _)) = current {
+        parsed.errors.push(ImportError {
+            location: format!("line {}", start),
+            message: "the VTODO is never closed".into(),
+        });
+    }
+    parsed
+}
+
+/// The task and whether it belongs in the archive.
+fn to_task(properties: Vec<Property>) -> Result<(Task, bool), String> {
+    let mut task = Task {
+        uuid: Uuid::new_v4(),
+        ..Task::default()
+    };
+    let mut status = None;
+    let mut has_summary = false;
+
+    for property in properties {
+        let value = property.value.as_str();
+        match property.name.as_str() {
+            "UID" => match value.parse::<Uuid>() {
+                Ok(uuid) => task.uuid = uuid,
+                Err(_) => task.ical_uid = Some(unescape(value)),
+            },
+            "SUMMARY" => {
+                task.description = unescape(value);
+                has_summary = true;
+            }
+            "DUE" => {
+                let due = date_time(value, property.param("VALUE") == Some("DATE"))?;
+                task.due_date = Some(due.naive_local().and_utc());
+            }
+            "CREATED" => task.created = Some(date_time(value, false)?.with_timezone(&Utc)),
+            "COMPLETED" => task.completed_at = Some(date_time(value, false)?.with_timezone(&Utc)),
+            "PRIORITY" => {
+                task.priority = match value.trim().parse::<u8>() {
+                    Ok(1..=4) => Priority::High,
+                    Ok(5) => Priority::Medium,
+                    Ok(0 | 6..=9) => Priority::Low,
+                    _ => return Err(format!("invalid PRIORITY {:?}", value)),
+                }
+            }
+            "CATEGORIES" => {
+                for tag in split_unescaped(value, ',') {
+                    let tag = unescape(tag.trim());
+                    if !tag.is_empty() && !task.tags.contains(&tag) {
+                        task.tags.push(tag);
+                    }
+                }
+            }
+            "STATUS" => status = Some(value.to_uppercase()),
+            "RRULE" => {
+                let rule: Recurrence = value.parse().map_err(|e| format!("{}", e))?;
+                task.recurrence = Some(rule);
+            }
+            "RELATED-TO" if matches!(property.param("RELTYPE"), None | Some("PARENT")) => {
+                task.parent = value.parse().ok();
+            }
+            _ => {}
+        }
+    }
+
+    if !has_summary || task.description.trim().is_empty() {
+        return Err("the VTODO has no SUMMARY".into());
+    }
+    let archived = match status.as_deref() {
+        Some("COMPLETED") => {
+            task.completed = true;
+            false
+        }
+        Some("CANCELLED") => true,
+        Some("NEEDS-ACTION" | "IN-PROCESS") => false,
+        None => {
+            task.completed = task.completed_at.is_some();
+            false
+        }
+        Some(other) => return Err(format!("unknown STATUS {:?}", other)),
+    };
+    if !task.completed {
+        task.completed_at = None;
+    }
+    Ok((task, archived))
+}
+
+/// A content line: `NAME;PARAM=VALUE:value`.
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn parse(line: &str) -> Option<Property> {
+        // The value starts at the first colon outside a quoted parameter.
+        let mut quoted = false;
+        let colon = line.char_indices().find_map(|(i, c)| match c {
+            '"' => {
+                quoted = !quoted;
+                None
+            }
+            ':' if !quoted => Some(i),
+            _ => None,
+        })?;
+        let (head, value) = (&line[..colon], &line[colon + 1..]);
+        let mut parts = split_unescaped(head, ';').into_iter();
+        let name = parts.next()?.trim().to_uppercase();
+        let params = parts
+            .filter_map(|p| p.split_once('='))
+            .map(|(k, v)| (k.trim().to_uppercase(), v.trim_matches('"').to_uppercase()))
+            .collect();
+        Some(Property {
+            name,
+            params,
+            value: value.to_string(),
+        })
+    }
+
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Lines with their number, continuation lines joined back on.
+fn unfold(text: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some((_, last))) => last.push_str(rest),
+            _ => lines.push((index + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+/// Appends a content line, folded at 75 octets, with CRLF as RFC 5545 asks.
+fn push(out: &mut String, line: &str) {
+    let mut rest = line;
+    let mut limit = 75;
+    loop {
+        let mut end = rest.len().min(limit);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.push_str(&rest[..end]);
+        out.push_str("\r\n");
+        rest = &rest[end..];
+        if rest.is_empty() {
+            break;
+        }
+        out.push(' ');
+        limit = 74;
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// Splits on `separator` where it isn't escaped with a backslash.
+fn split_unescaped(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == separator => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+fn utc(instant: DateTime<Utc>) -> String {
+    instant.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// A DATE or DATE-TIME value as a local time. UTC times (`Z`) are converted;
+/// floating times and times with a TZID are taken as local wall clock time.
+fn date_time(value: &str, is_date: bool) -> Result<DateTime<Local>, String> {
+    let value = value.trim();
+    let invalid = || format!("invalid date {:?}", value);
+    let naive = if is_date || value.len() == 8 {
+        NaiveDate::parse_from_str(value, "%Y%m%d")
+            .map_err(|_| invalid())?
+            .and_time(NaiveTime::MIN)
+    } else if let Some(value) = value.strip_suffix('Z') {
+        let instant = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+        return Ok(instant.and_utc().with_timezone(&Local));
+    } else {
+        NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map_err(|_| invalid())?
+    };
+    Local.from_local_datetime(&naive).earliest().ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(description: &str) -> Task {
+        Task {
+            uuid: Uuid::new_v4(),
+            id: 1,
+            description: description.into(),
+            ..Task::default()
+        }
+    }
+
+    /// Exports the task and reads it back.
+    fn round_trip(task: &Task) -> (String, Task) {
+        let text = export(std::slice::from_ref(task), &[]);
+        let mut parsed = parse(&text);
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        assert_eq!(parsed.tasks.len(), 1);
+        (text, parsed.tasks.remove(0))
+    }
+
+    #[test]
+    fn the_uid_is_the_uuid() {
+        let task = Task {
+            tags: vec!["home".into()],
+            priority: Priority::High,
+            ..task("buy milk")
+        };
+        let (text, back) = round_trip(&task);
+        assert!(text.contains(&format!("\r\nUID:{}\r\n", task.uuid)));
+        assert_eq!(back.uuid, task.uuid);
+        assert_eq!(back.ical_uid, None);
+        assert_eq!((back.tags, back.priority), (task.tags, task.priority));
+    }
+
+    #[test]
+    fn a_foreign_uid_is_kept() {
+        let task = Task {
+            ical_uid: Some("20261014T090000Z-42@calendar.example.com".into()),
+            ..task("call mum")
+        };
+        let (text, back) = round_trip(&task);
+        assert!(text.contains("\r\nUID:20261014T090000Z-42@calendar.example.com\r\n"));
+        assert_eq!(back.ical_uid, task.ical_uid);
+        assert_eq!(back.ical_uid(), task.ical_uid());
+    }
+
+    #[test]
+    fn long_lines_are_folded() {
+        // Two-byte characters, so some folds fall inside one.
+        let description = "Grüße an die Straßenbahnfahrerin übermitteln, ".repeat(6);
+        let (text, back) = round_trip(&task(&description));
+        let lines: Vec<&str> = text.split("\r\n").collect();
+        assert!(lines.iter().all(|line| line.len() <= 75), "{:?}", lines);
+        let summary = lines.iter().position(|line| line.starts_with("SUMMARY:")).unwrap();
+        assert!(lines[summary + 1].starts_with(' '));
+        assert_eq!(back.description, description);
+    }
+
+    #[test]
+    fn special_characters_are_escaped() {
+        let task = Task {
+            tags: vec!["a,b".into(), "c;d".into(), "back\\slash".into()],
+            ..task("milk, eggs; bread \\ butter\nand jam")
+        };
+        let (text, back) = round_trip(&task);
+        let lines: Vec<&str> = text.split("\r\n").collect();
+        assert!(lines.contains(&r"SUMMARY:milk\, eggs\; bread \\ butter\nand jam"));
+        assert!(lines.contains(&r"CATEGORIES:a\,b,c\;d,back\\slash"));
+        assert_eq!(back.description, task.description);
+        assert_eq!(back.tags, task.tags);
+    }
+}
@@ -0,0 +1,395 @@
+// src/exchange/taskwarrior.rs
+//
+// The JSON of `task export` and `task import`: an array of tasks (or, from
+// older versions, one task per line) with Taskwarrior's compact UTC dates,
+// `20240131T120000Z`.
+//
+// Pending and waiting tasks go to the active list. Completed and deleted
+// tasks go to the archive, the deleted ones not marked done. The project
+// becomes a `project:NAME` tag, and back.
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use uuid::Uuid;
+
+use super::{ExchangeError, ImportError, Parsed};
+use crate::models::{Annotation, Priority, Task};
+
+const PROJECT_TAG: &str = "project:";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TwTask {
+    uuid: Uuid,
+    status: String,
+    description: String,
+    #[serde(default, with = "date", skip_serializing_if = "Option::is_none")]
+    entry: Option<DateTime<Utc>>,
+    #[serde(default, with = "date", skip_serializing_if = "Option::is_none")]
+    end: Option<DateTime<Utc>>,
+    #[serde(default, with = "date", skip_serializing_if = "Option::is_none")]
+    due: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    project: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    annotations: Vec<TwAnnotation>,
+    #[serde(default, deserialize_with = "depends", skip_serializing_if = "Vec::is_empty")]
+    depends: Vec<Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TwAnnotation {
+    #[serde(with = "date::required")]
+    entry: DateTime<Utc>,
+    description: String,
+}
+
+pub fn export(tasks: &[Task], archived: &[Task]) -> Result<String, ExchangeError> {
+    let now = Utc::now();
+    let entries: Vec<TwTask> = tasks
+        .iter()
+        .map(|task| (task, false))
+        .chain(archived.iter().map(|task| (task, true)))
+        .map(|(task, archived)| {
+            let status = match (task.completed, archived) {
+                (true, _) => "completed",
+                (false, true) => "deleted",
+                (false, false) => "pending",
+            };
+            let project = task.tags.iter().find_map(|t| t.strip_prefix(PROJECT_TAG));
+            TwTask {
+                uuid: task.uuid,
+                status: status.to_string(),
+                description: task.description.clone(),
+                entry: Some(task.created.unwrap_or(now)),
+                end: if status == "pending" {
+                    None
+                } else {
+                    Some(task.completed_at.unwrap_or(now))
+                },
+                due: task.due_date.map(|d| from_floating(d.naive_utc())),
+                priority: match task.priority {
+                    Priority::Low if task.no_priority => None,
+                    Priority::High => Some("H".into()),
+                    Priority::Medium => Some("M".into()),
+                    Priority::Low => Some("L".into()),
+                },
+                project: project.map(str::to_string),
+                tags: task
+                    .tags
+                    .iter()
+                    .filter(|t| !t.starts_with(PROJECT_TAG))
+                    .cloned()
+                    .collect(),
+                annotations: task
+                    .annotations
+                    .iter()
+                    .map(|a| TwAnnotation {
+                        entry: a.entry,
+                        description: a.description.clone(),
+                    })
+                    .collect(),
+                depends: task.depends_on.clone(),
+            }
+        })
+        .collect();
+
+    let mut out = serde_json::to_string_pretty(&entries).map_err(|e| error(e.to_string()))?;
+    out.push('\n');
+    Ok(out)
+}
+
+pub fn parse(text: &str) -> Result<Parsed, ExchangeError> {
+    // `task export` writes an array; `rc.json.array=off` one task per line.
+    let entries: Vec<(String, Result<serde_json::Value, String>)> = if text.trim_start().starts_with('[') {
+        let values: Vec<serde_json::Value> = serde_json::from_str(text).map_err(|e| error(e.to_string()))?;
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| (format!("task {}", i + 1), Ok(value)))
+            .collect()
+    } else {
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                (
+                    format!("line {}", i + 1),
+                    serde_json::from_str(line).map_err(|e| e.to_string()),
+                )
+            })
+            .collect()
+    };
+
+    let mut parsed = Parsed::default();
+    for (location, value) in entries {
+        let result = value.and_then(|value| {
+            let entry: TwTask = serde_json::from_value(value).map_err(|e| e.to_string())?;
+            to_task(entry)
+        });
+        match result {
+            Ok((task, true)) => parsed.archived.push(task),
+            Ok((task, false)) => parsed.tasks.push(task),
+            Err(message) => parsed.errors.push(ImportError { location, message }),
+        }
+    }
+    Ok(parsed)
+}
+
+/// The task and whether it belongs in the archive.
+fn to_task(entry: TwTask) -> Result<(Task, bool), String> {
+    let (completed, archived) = match entry.status.as_str() {
+        "pending" | "waiting" => (false, false),
+        "completed" => (true, true),
+        "deleted" => (false, true),
+        "recurring" => return Err("recurring templates are skipped; their pending instances are imported".into()),
+        status => return Err(format!("unknown status {:?}", status)),
+    };
+    let priority = match entry.priority.as_deref() {
+        Some("H") => Priority::High,
+        Some("M") => Priority::Medium,
+        Some("L") | None => Priority::Low,
+        Some(other) => return Err(format!("unknown priority {:?} (expected H, M or L)", other)),
+    };
+
+    let mut tags = Vec::new();
+    if let Some(project) = entry.project {
+        tags.push(format!("{}{}", PROJECT_TAG, project));
+    }
+    tags.extend(entry.tags);
+
+    let task = Task {
+        uuid: entry.uuid,
+        id: 0,
+        description: entry.description,
+        tags,
+        due_date: entry.due.map(|d| d.with_timezone(&Local).naive_local().and_utc()),
+        priority,
+        completed,
+        recurrence: None,
+        parent: None,
+        depends_on: entry.depends,
+        created: entry.entry,
+        completed_at: if completed { entry.end } else { None },
+        annotations: entry
+            .annotations
+            .into_iter()
+            .map(|a| Annotation {
+                entry: a.entry,
+                description: a.description,
+            })
+            .collect(),
+        ical_uid: None,
+        no_priority: entry.priority.is_none(),
+    };
+    Ok((task, archived))
+}
+
+/// The instant a floating due date falls on here.
+fn from_floating(due: NaiveDateTime) -> DateTime<Utc> {
+    Local
+        .from_local_datetime(&due)
+        .earliest()
+        .map(|d| d.with_timezone(&Utc))
+        .unwrap_or_else(|| due.and_utc())
+}
+
+/// A list of uuids, or a comma separated string of them as Taskwarrior
+/// before 2.6 wrote it.
+fn depends<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Uuid>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Depends {
+        List(Vec<Uuid>),
+        Text(String),
+    }
+    match Depends::deserialize(deserializer)? {
+        Depends::List(uuids) => Ok(uuids),
+        Depends::Text(text) => text
+            .split(',')
+            .filter(|u| !u.is_empty())
+            .map(|u| u.trim().parse().map_err(serde::de::Error::custom))
+            .collect(),
+    }
+}
+
+fn error(reason: String) -> ExchangeError {
+    ExchangeError {
+        format: "Taskwarrior",
+        reason,
+    }
+}
+
+/// Taskwarrior's `20240131T120000Z` dates.
+mod date {
+    use chrono::{DateTime, NaiveDateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    const FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+    pub fn serialize<S: Serializer>(date: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match date {
+            Some(date) => required::serialize(date, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|text| parse(&text).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+
+    fn parse(text: &str) -> Result<DateTime<Utc>, String> {
+        NaiveDateTime::parse_from_str(text, FORMAT)
+            .map(|d| d.and_utc())
+            .or_else(|_| DateTime::parse_from_rfc3339(text).map(|d| d.with_timezone(&Utc)))
+            .map_err(|_| format!("invalid date {:?}", text))
+    }
+
+    pub mod required {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(date: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&date.format(FORMAT).to_string())
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+            parse(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn entry(fields: Value) -> Value {
+        let mut entry = json!({
+            "uuid": Uuid::new_v4(),
+            "status": "pending",
+            "description": "buy milk",
+            "entry": "20261014T090000Z",
+        });
+        entry
+            .as_object_mut()
+            .unwrap()
+            .extend(fields.as_object().unwrap().clone());
+        entry
+    }
+
+    fn import(entries: &[Value]) -> Parsed {
+        parse(&Value::from(entries.to_vec()).to_string()).unwrap()
+    }
+
+    /// The exported entries, as JSON.
+    fn exported(parsed: &Parsed) -> Vec<Value> {
+        serde_json::from_str(&export(&parsed.tasks, &parsed.archived).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn statuses_pick_the_list() {
+        let parsed = import(&[
+            entry(json!({"status": "pending", "description": "pending"})),
+            entry(json!({"status": "waiting", "description": "waiting"})),
+            entry(json!({"status": "completed", "description": "completed", "end": "20261015T100000Z"})),
+            entry(json!({"status": "deleted", "description": "deleted", "end": "20261015T100000Z"})),
+            entry(json!({"status": "recurring", "description": "template"})),
+            entry(json!({"status": "sleeping", "description": "unknown"})),
+        ]);
+        let active: Vec<(&str, bool)> = parsed
+            .tasks
+            .iter()
+            .map(|t| (t.description.as_str(), t.completed))
+            .collect();
+        assert_eq!(active, [("pending", false), ("waiting", false)]);
+        let archived: Vec<(&str, bool)> = parsed
+            .archived
+            .iter()
+            .map(|t| (t.description.as_str(), t.completed))
+            .collect();
+        assert_eq!(archived, [("completed", true), ("deleted", false)]);
+        assert_eq!(
+            parsed.archived[0].completed_at,
+            Some(Utc.with_ymd_and_hms(2026, 10, 15, 10, 0, 0).unwrap())
+        );
+        assert_eq!(parsed.archived[1].completed_at, None);
+        let failed: Vec<&str> = parsed.errors.iter().map(|e| e.location.as_str()).collect();
+        assert_eq!(failed, ["task 5", "task 6"]);
+
+        let statuses: Vec<Value> = exported(&parsed).into_iter().map(|e| e["status"].clone()).collect();
+        assert_eq!(statuses, ["pending", "pending", "completed", "deleted"]);
+    }
+
+    #[test]
+    fn priorities_and_missing_ones_are_kept() {
+        let parsed = import(&[
+            entry(json!({"priority": "H"})),
+            entry(json!({"priority": "M"})),
+            entry(json!({"priority": "L"})),
+            entry(json!({})),
+        ]);
+        let priorities: Vec<&Priority> = parsed.tasks.iter().map(|t| &t.priority).collect();
+        assert_eq!(
+            priorities,
+            [&Priority::High, &Priority::Medium, &Priority::Low, &Priority::Low]
+        );
+        let exported: Vec<Option<Value>> = exported(&parsed)
+            .into_iter()
+            .map(|e| e.get("priority").cloned())
+            .collect();
+        assert_eq!(exported, [Some("H".into()), Some("M".into()), Some("L".into()), None]);
+
+        // Once it's set, it's written.
+        let mut task = parsed.tasks[3].clone();
+        task.priority = Priority::Medium;
+        let text = export(&[task], &[]).unwrap();
+        assert!(text.contains("\"priority\": \"M\""));
+
+        let parsed = import(&[entry(json!({"priority": "X"}))]);
+        assert!(parsed.errors[0].message.contains("unknown priority"));
+    }
+
+    #[test]
+    fn annotations_are_kept() {
+        let parsed = import(&[entry(json!({
+            "annotations": [
+                {"entry": "20261014T091500Z", "description": "oat milk if there's none"},
+                {"entry": "20261014T120000Z", "description": "the shop closes at 6"}
+            ]
+        }))]);
+        let annotations = &parsed.tasks[0].annotations;
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(
+            annotations[0].entry,
+            Utc.with_ymd_and_hms(2026, 10, 14, 9, 15, 0).unwrap()
+        );
+        assert_eq!(annotations[1].description, "the shop closes at 6");
+
+        assert_eq!(
+            exported(&parsed)[0]["annotations"],
+            json!([
+                {"entry": "20261014T091500Z", "description": "oat milk if there's none"},
+                {"entry": "20261014T120000Z", "description": "the shop closes at 6"}
+            ])
+        );
+    }
+
+    #[test]
+    fn depends_reads_lists_and_comma_separated_uuids() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let parsed = import(&[
+            entry(json!({"depends": format!("{},{}", a, b)})),
+            entry(json!({"depends": [a, b]})),
+            entry(json!({"depends": "not-a-uuid"})),
+        ]);
+        assert_eq!(parsed.tasks[0].depends_on, [a, b]);
+        assert_eq!(parsed.tasks[1].depends_on, [a, b]);
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!(exported(&parsed)[0]["depends"], json!([a, b]));
+    }
+}
@@ -0,0 +1,328 @@
+// src/exchange/todotxt.rs
+//
+// The todo.txt format (https://github.com/todotxt/todo.txt): one task per
+// line, `x` and a completion date for done tasks, `(A)` to `(C)` for
+// priorities, `+project` and `@context` tags and `key:value` extras.
+//
+// Tags are flat here, so projects become plain tags and contexts keep their
+// `@`, which lets them round-trip. `due:` is the usual due date extension,
+// `pri:` keeps the priority of done tasks (which lose their `(A)`),
+// `rec:` holds the recurrence and `uuid:` lets a re-import recognise tasks
+// it already has.
+//
+// todo.txt has no way to quote a word, so description words that would be
+// read back as a tag, an extra or a date get a `\` in front, which the
+// import takes off again.
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use uuid::Uuid;
+
+use super::{ImportError, Parsed};
+use crate::models::{Priority, Task};
+use crate::recurrence::{Frequency, Recurrence};
+
+pub fn export<'a>(tasks: impl IntoIterator<Item = &'a Task>) -> String {
+    let mut out = String::new();
+    for task in tasks {
+        out.push_str(&line(task));
+        out.push('\n');
+    }
+    out
+}
+
+fn line(task: &Task) -> String {
+    let mut parts = Vec::new();
+    let created = task.created.map(local_date);
+    if task.completed {
+        parts.push("x".to_string());
+        // A creation date alone would be read as the completion date.
+        if let Some(completed) = task.completed_at.map(local_date) {
+            parts.push(completed.to_string());
+            parts.extend(created.map(|d| d.to_string()));
+        }
+    } else {
+        parts.push(format!("({})", letter(&task.priority)));
+        parts.extend(created.map(|d| d.to_string()));
+    }
+
+    for (index, word) in task.description.split_whitespace().enumerate() {
+        // Right after the priority, a date would be taken for the creation date.
+        if is_special(word) || (index == 0 && created.is_none() && is_date(word)) {
+            parts.push(format!("\\{}", word));
+        } else {
+            parts.push(word.to_string());
+        }
+    }
+    for tag in &task.tags {
+        let tag: String = tag.split_whitespace().collect();
+        if tag.starts_with('@') || tag.starts_with('+') {
+            parts.push(tag);
+        } else {
+            parts.push(format!("+{}", tag));
+        }
+    }
+    if let Some(due) = task.due_date {
+        parts.push(format!("due:{}", due.date_naive()));
+    }
+    if let Some(recurrence) = &task.recurrence {
+        parts.push(format!("rec:{}", rec(recurrence)));
+    }
+    if task.completed {
+        parts.push(format!("pri:{}", letter(&task.priority)));
+    }
+    parts.push(format!("uuid:{}", task.uuid));
+    parts.join(" ")
+}
+
+pub fn parse(text: &str) -> Parsed {
+    let mut parsed = Parsed::default();
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_line(line) {
+            Ok(task) => parsed.tasks.push(task),
+            Err(message) => parsed.errors.push(ImportError {
+                location: format!("line {}", index + 1),
+                message,
+            }),
+        }
+    }
+    parsed
+}
+
+fn parse_line(line: &str) -> Result<Task, String> {
+    let mut words = line.split_whitespace().peekable();
+    let mut task = Task {
+        uuid: Uuid::new_v4(),
+        ..Task::default()
+    };
+
+    if words.next_if_eq(&"x").is_some() {
+        task.completed = true;
+        if let Some(completed) = words.next_if(|w| is_date(w)) {
+            task.completed_at = Some(utc_midnight(parse_date(completed)?));
+        }
+    } else if let Some(priority) = words.next_if(|w| w.len() == 3 && w.starts_with('(') && w.ends_with(')')) {
+        task.priority = from_letter(priority.as_bytes()[1])?;
+    }
+    if let Some(created) = words.next_if(|w| is_date(w)) {
+        task.created = Some(utc_midnight(parse_date(created)?));
+    }
+
+    let mut description = Vec::new();
+    for word in words {
+        if let Some(escaped) = word.strip_prefix('\\').filter(|w| !w.is_empty()) {
+            description.push(escaped);
+        } else if let Some(project) = word.strip_prefix('+').filter(|p| !p.is_empty()) {
+            push_tag(&mut task.tags, project);
+        } else if word.len() > 1 && word.starts_with('@') {
+            push_tag(&mut task.tags, word);
+        } else if let Some(due) = word.strip_prefix("due:") {
+            let date = parse_date(due)?;
+            task.due_date = Some(date.and_time(NaiveTime::MIN).and_utc());
+        } else if let Some(priority) = word.strip_prefix("pri:") {
+            match priority.as_bytes() {
+                [letter] => task.priority = from_letter(*letter)?,
+                _ => return Err(format!("invalid priority {:?}", priority)),
+            }
+        } else if let Some(recurrence) = word.strip_prefix("rec:") {
+            task.recurrence = Some(parse_rec(recurrence)?);
+        } else if let Some(uuid) = word.strip_prefix("uuid:") {
+            task.uuid = uuid.parse().map_err(|_| format!("invalid uuid {:?}", uuid))?;
+        } else {
+            description.push(word);
+        }
+    }
+
+    task.description = description.join(" ");
+    if task.description.is_empty() {
+        return Err("the task has no description".into());
+    }
+    Ok(task)
+}
+
+/// Whether todo.txt reads `word` as something other than description:
+/// a `+project`, an `@context` or a `key:value` extra (links are left
+/// alone, as todo.txt apps do), or whether it starts with the `\` that
+/// escapes these.
+fn is_special(word: &str) -> bool {
+    let extra = match word.split_once(':') {
+        Some((key, value)) => {
+            ["due", "pri", "rec", "uuid"].contains(&key)
+                || (!key.is_empty() && !value.is_empty() && !value.starts_with("//"))
+        }
+        None => false,
+    };
+    extra || (word.len() > 1 && (word.starts_with('+') || word.starts_with('@') || word.starts_with('\\')))
+}
+
+/// The usual `rec:` form, e.g. `2w` for every two weeks, or the RRULE for
+/// recurrences it can't express, such as those on given weekdays.
+fn rec(recurrence: &Recurrence) -> String {
+    if !recurrence.weekdays.is_empty() || recurrence.month_day.is_some() {
+        return recurrence.to_string();
+    }
+    let unit = match recurrence.frequency {
+        Frequency::Daily => 'd',
+        Frequency::Weekly => 'w',
+        Frequency::Monthly => 'm',
+        Frequency::Yearly => 'y',
+    };
+    format!("{}{}", recurrence.interval, unit)
+}
+
+/// Reads `rec:` values: `3d`, `w` or `+2m` (the `+`, repeating from the
+/// due date rather than the completion date, is how recurrences work here
+/// anyway), or an RRULE.
+fn parse_rec(text: &str) -> Result<Recurrence, String> {
+    let short = text.strip_prefix('+').unwrap_or(text);
+    let split = short.char_indices().last().map_or(0, |(i, _)| i);
+    let (count, unit) = (&short[..split], &short[split..]);
+    let recurrence: Result<Recurrence, _> = match unit {
+        "d" | "w" | "m" | "y" if count.chars().all(|c| c.is_ascii_digit()) => {
+            let unit = match unit {
+                "d" => "days",
+                "w" => "weeks",
+                "m" => "months",
+                _ => "years",
+            };
+            format!("every {} {}", if count.is_empty() { "1" } else { count }, unit).parse()
+        }
+        _ => text.parse(),
+    };
+    recurrence.map_err(|e| e.to_string())
+}
+
+fn push_tag(tags: &mut Vec<String>, tag: &str) {
+    if !tags.iter().any(|t| t == tag) {
+        tags.push(tag.to_string());
+    }
+}
+
+fn letter(priority: &Priority) -> char {
+    match priority {
+        Priority::High => 'A',
+        Priority::Medium => 'B',
+        Priority::Low => 'C',
+    }
+}
+
+/// `A` is high, `B` medium and `C` to `Z` low.
+fn from_letter(letter: u8) -> Result<Priority, String> {
+    match letter {
+        b'A' => Ok(Priority::High),
+        b'B' => Ok(Priority::Medium),
+        b'C'..=b'Z' => Ok(Priority::Low),
+        _ => Err(format!("invalid priority {:?}", letter as char)),
+    }
+}
+
+/// Whether `word` is shaped like `YYYY-MM-DD`, valid or not.
+fn is_date(word: &str) -> bool {
+    let bytes = word.as_bytes();
+    bytes.len() == 10
+        && bytes.iter().enumerate().all(|(i, b)| match i {
+            4 | 7 => *b == b'-',
+            _ => b.is_ascii_digit(),
+        })
+}
+
+fn parse_date(text: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(text, "%Y-%m-%d").map_err(|_| format!("invalid date {:?}", text))
+}
+
+/// The date of an instant in local time.
+fn local_date(instant: DateTime<Utc>) -> NaiveDate {
+    instant.with_timezone(&Local).date_naive()
+}
+
+/// The instant local midnight starts `date`.
+fn utc_midnight(date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_time(NaiveTime::MIN);
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|d| d.with_timezone(&Utc))
+        .unwrap_or_else(|| midnight.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(task: &Task) -> Task {
+        let parsed = parse(&export([task]));
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        assert_eq!(parsed.tasks.len(), 1);
+        parsed.tasks.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn tasks_round_trip() {
+        let task = Task {
+            uuid: Uuid::new_v4(),
+            description: "Water the plants".into(),
+            tags: vec!["home".into(), "@garden".into()],
+            due_date: Some(utc_midnight(NaiveDate::from_ymd_opt(2026, 5, 1).unwrap())),
+            priority: Priority::High,
+            created: Some(utc_midnight(NaiveDate::from_ymd_opt(2026, 4, 1).unwrap())),
+            recurrence: Some("every 2 weeks".parse().unwrap()),
+            ..Task::default()
+        };
+        let back = round_trip(&task);
+        assert_eq!(back.uuid, task.uuid);
+        assert_eq!(back.description, task.description);
+        assert_eq!(back.tags, task.tags);
+        assert_eq!(back.priority, task.priority);
+        assert_eq!(back.created, task.created);
+        assert_eq!(back.recurrence, task.recurrence);
+        assert_eq!(
+            back.due_date.map(|d| d.date_naive()),
+            Some(NaiveDate::from_ymd_opt(2026, 5, 1).unwrap())
+        );
+
+        let done = Task {
+            completed: true,
+            completed_at: Some(utc_midnight(NaiveDate::from_ymd_opt(2026, 4, 20).unwrap())),
+            priority: Priority::Low,
+            recurrence: Some("weekly on mon,thu".parse().unwrap()),
+            ..task
+        };
+        let back = round_trip(&done);
+        assert!(back.completed);
+        assert_eq!(back.completed_at, done.completed_at);
+        assert_eq!(back.priority, Priority::Low);
+        assert_eq!(back.recurrence, done.recurrence);
+    }
+
+    #[test]
+    fn special_words_in_descriptions_stay_description() {
+        let task = Task {
+            description: "2026-01-02 call +1 555 re: due:friday @home key:value C:\\x \\n https://example.com".into(),
+            ..Task::default()
+        };
+        let line = export([&task]);
+        assert!(line.contains(r"\+1 "), "{}", line);
+        assert!(line.contains(r"\@home "), "{}", line);
+        assert!(line.contains(" https://example.com "), "{}", line);
+
+        let back = round_trip(&task);
+        assert_eq!(back.description, task.description);
+        assert!(back.tags.is_empty());
+        assert_eq!(back.due_date, None);
+    }
+
+    #[test]
+    fn rec_reads_the_usual_forms() {
+        let parsed =
+            parse("(B) Pay rent rec:+1m\n(B) Stretch rec:d\n(B) Review rec:FREQ=WEEKLY;BYDAY=FR\n(B) Bad rec:2q");
+        let rules: Vec<String> = parsed
+            .tasks
+            .iter()
+            .map(|t| t.recurrence.as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(rules, ["FREQ=MONTHLY", "FREQ=DAILY", "FREQ=WEEKLY;BYDAY=FR"]);
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!(parsed.errors[0].location, "line 4");
+    }
+}
@@ -0,0 +1,227 @@
+// src/journal.rs
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::actions::{Action, ActionError};
+use crate::models::Task;
+use crate::store::{back_up_corrupt, write_atomic, StoreError};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub at: DateTime<Utc>,
+    pub action: Action,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Stacks {
+    undo: Vec<Entry>,
+    redo: Vec<Entry>,
+}
+
+/// The undo/redo history, saved next to the task store so that it survives
+/// between invocations. Only the most recent `limit` actions are kept.
+pub struct Journal {
+    path: PathBuf,
+    limit: usize,
+    stacks: Stacks,
+}
+
+impl Journal {
+    pub fn open(path: PathBuf, limit: usize) -> Result<Journal, StoreError> {
+        let stacks = match std::fs::read_to_string(&path) {
+            Ok(data) => serde_json::from_str(&data).map_err(|source| match back_up_corrupt(&path) {
+                Ok(backup) => StoreError::Corrupt {
+                    path: path.clone(),
+                    backup,
+                    source,
+                },
+                Err(e) => e,
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Stacks::default(),
+            Err(source) => return Err(StoreError::Read { path, source }),
+        };
+        Ok(Journal { path, limit, stacks })
+    }
+
+    /// Records a new action. Anything that was undone can no longer be
+    /// redone afterwards.
+    pub fn record(&mut self, action: Action) {
+        self.stacks.undo.push(Entry { at: Utc::now(), action });
+        self.stacks.redo.clear();
+        let excess = self.stacks.undo.len().saturating_sub(self.limit);
+        self.stacks.undo.drain(..excess);
+    }
+
+    /// Undoes the most recent action, returning it, or `None` when there is
+    /// nothing left to undo.
+    pub fn undo(&mut self, tasks: &mut Vec<Task>, archived: &mut Vec<Task>) -> Result<Option<Action>, ActionError> {
+        let Some(entry) = self.stacks.undo.pop() else {
+            return Ok(None);
+        };
+        if let Err(e) = entry.action.undo(tasks, archived) {
+            self.stacks.undo.push(entry);
+            return Err(e);
+        }
+        let action = entry.action.clone();
+        self.stacks.redo.push(entry);
+        Ok(Some(action))
+    }
+
+    /// Reapplies the most recently undone action.
+    pub fn redo(&mut self, tasks: &mut Vec<Task>, archived: &mut Vec<Task>) -> Result<Option<Action>, ActionError> {
+        let Some(entry) = self.stacks.redo.pop() else {
+            return Ok(None);
+        };
+        if let Err(e) = entry.action.redo(tasks, archived) {
+            self.stacks.redo.push(entry);
+            return Err(e);
+        }
+        let action = entry.action.clone();
+        self.stacks.undo.push(entry);
+        Ok(Some(action))
+    }
+
+    /// Actions that can be undone, most recent first.
+    pub fn undoable(&self) -> impl Iterator<Item = &Entry> {
+        self.stacks.undo.iter().rev()
+    }
+
+    /// Actions that can be redone, next one first.
+    pub fn redoable(&self) -> impl Iterator<Item = &Entry> {
+        self.stacks.redo.iter().rev()
+    }
+
+    pub fn save(&self) -> Result<(), StoreError> {
+        let json = serde_json::to_string_pretty(&self.stacks).map_err(|e| StoreError::Write {
+            path: self.path.clone(),
+            source: e.into(),
+        })?;
+        write_atomic(&self.path, json.as_bytes(), || Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::models::renumber;
+
+    fn journal() -> Journal {
+        let path = std::env::temp_dir().join(format!("todosage-journal-{}.json", Uuid::new_v4()));
+        Journal::open(path, 10).unwrap()
+    }
+
+    /// Adds a task the way commands do, recording it.
+    fn add(journal: &mut Journal, tasks: &mut Vec<Task>, description: &str) -> Task {
+        let task = Task {
+            uuid: Uuid::new_v4(),
+            description: description.into(),
+            ..Task::default()
+        };
+        tasks.push(task);
+        renumber(tasks);
+        let task = tasks.last().unwrap().clone();
+        journal.record(Action::Add(task.clone()));
+        task
+    }
+
+    fn descriptions(tasks: &[Task]) -> Vec<&str> {
+        tasks.iter().map(|t| t.description.as_str()).collect()
+    }
+
+    #[test]
+    fn undo_and_redo_several_steps() {
+        let mut journal = journal();
+        let (mut tasks, mut archived) = (Vec::new(), Vec::new());
+        for description in ["one", "two", "three"] {
+            add(&mut journal, &mut tasks, description);
+        }
+
+        for left in [["one", "two"].as_slice(), &["one"], &[]] {
+            assert!(journal.undo(&mut tasks, &mut archived).unwrap().is_some());
+            assert_eq!(descriptions(&tasks), left);
+        }
+        assert!(journal.undo(&mut tasks, &mut archived).unwrap().is_none());
+        assert_eq!(journal.redoable().count(), 3);
+
+        assert!(journal.redo(&mut tasks, &mut archived).unwrap().is_some());
+        assert!(journal.redo(&mut tasks, &mut archived).unwrap().is_some());
+        assert_eq!(descriptions(&tasks), ["one", "two"]);
+        assert_eq!(tasks.iter().map(|t| t.id).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(journal.undoable().count(), 2);
+        assert_eq!(journal.redoable().count(), 1);
+
+        // What's left survives a reopen.
+        journal.save().unwrap();
+        let mut reopened = Journal::open(journal.path.clone(), 10).unwrap();
+        std::fs::remove_file(&journal.path).unwrap();
+        assert!(reopened.redo(&mut tasks, &mut archived).unwrap().is_some());
+        assert_eq!(descriptions(&tasks), ["one", "two", "three"]);
+        assert!(reopened.redo(&mut tasks, &mut archived).unwrap().is_none());
+    }
+
+    #[test]
+    fn a_new_action_clears_redo() {
+        let mut journal = journal();
+        let (mut tasks, mut archived) = (Vec::new(), Vec::new());
+        add(&mut journal, &mut tasks, "one");
+        add(&mut journal, &mut tasks, "two");
+        journal.undo(&mut tasks, &mut archived).unwrap();
+        assert_eq!(journal.redoable().count(), 1);
+
+        add(&mut journal, &mut tasks, "three");
+        assert_eq!(journal.redoable().count(), 0);
+        assert!(journal.redo(&mut tasks, &mut archived).unwrap().is_none());
+        assert_eq!(descriptions(&tasks), ["one", "three"]);
+    }
+
+    #[test]
+    fn only_the_last_actions_are_kept() {
+        let mut journal = journal();
+        journal.limit = 2;
+        let (mut tasks, mut archived) = (Vec::new(), Vec::new());
+        for description in ["one", "two", "three"] {
+            add(&mut journal, &mut tasks, description);
+        }
+        while journal.undo(&mut tasks, &mut archived).unwrap().is_some() {}
+        assert_eq!(descriptions(&tasks), ["one"]);
+    }
+
+    #[test]
+    fn a_failing_step_leaves_the_lists_alone() {
+        let mut journal = journal();
+        let (mut tasks, mut archived) = (Vec::new(), Vec::new());
+        let kept = add(&mut journal, &mut tasks, "kept");
+        let added = Task {
+            uuid: Uuid::new_v4(),
+            description: "added".into(),
+            ..Task::default()
+        };
+        tasks.push(added.clone());
+        renumber(&mut tasks);
+        // Undone last to first: removing `added` works, then the task that
+        // was marked done is missing.
+        let missing = Uuid::new_v4();
+        journal.record(Action::Batch(vec![Action::Done(missing), Action::Add(added)]));
+
+        let before = tasks.clone();
+        let error = journal.undo(&mut tasks, &mut archived).unwrap_err();
+        assert!(matches!(error, ActionError::Missing(uuid) if uuid == missing));
+        assert_eq!(descriptions(&tasks), descriptions(&before));
+        assert_eq!(journal.undoable().count(), 2);
+        assert_eq!(journal.redoable().count(), 0);
+
+        // Once the task is back the batch undoes as a whole.
+        tasks.push(Task {
+            uuid: missing,
+            completed: true,
+            ..kept
+        });
+        journal.undo(&mut tasks, &mut archived).unwrap();
+        assert_eq!(descriptions(&tasks), ["kept", "kept"]);
+        assert!(!tasks[1].completed);
+    }
+}
@@ -1,28 +1,148 @@
-mod models;
+mod actions;
 mod cli;
+mod config;
+mod dates;
+mod exchange;
+mod journal;
+mod models;
+mod output;
+mod query;
+mod recurrence;
+mod sort;
+mod store;
+mod sync;
 mod tui;
-mod actions;
+mod urgency;
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, IsTerminal, Write};
+use std::process::ExitCode;
 
+use actions::Action;
+use chrono::Utc;
 use clap::Parser;
+use journal::Journal;
 use models::Task;
-use actions::Action;
-use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
-
-const MAX_DATETIME: DateTime<Utc> = DateTime::from_naive_utc_and_offset(NaiveDateTime::MAX, Utc);
+use uuid::Uuid;
 
 struct AppState {
     tasks: Vec<Task>,
     archived_tasks: Vec<Task>,
-    last_action: Option<Action>,
+    journal: Journal,
+}
+
+impl AppState {
+    fn save(&self, store: &store::Store) -> Result<(), store::StoreError> {
+        store.save(&self.tasks, &self.archived_tasks)?;
+        self.journal.save()
+    }
+}
+
+/// Other lists changed by undoing or redoing moves, by name, waiting to be
+/// saved with the current one.
+type Others = HashMap<String, (store::Store, AppState)>;
+
+/// Saves the current list and then the other lists its moves changed, so
+/// that a move is written to both lists at the same point.
+fn save_all(store: &store::Store, state: &AppState, others: &mut Others) -> Result<(), store::StoreError> {
+    state.save(store)?;
+    for (_, (other, there)) in others.drain() {
+        there.save(&other)?;
+    }
+    Ok(())
+}
+
+/// Errors exit with 2 (as clap's usage errors do), leaving 1 for list
+/// commands that found nothing.
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            if let Some(backup) = err.downcast_ref::<store::StoreError>().and_then(|e| e.backup()) {
+                eprintln!(
+                    "note: nothing was written; a copy of the unreadable store was saved to {}",
+                    backup.display()
+                );
+                eprintln!("note: fix or move the store file (or pick another with --store) and try again");
+            }
+            ExitCode::from(2)
+        }
+    }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn run() -> Result<ExitCode, Box<dyn std::error::Error>> {
     let cli = cli::Cli::parse();
-    let (active_tasks, archived_tasks) = models::load_tasks();
+    let config = config::Config::load()?;
+    let store = store::Store::open(cli.store, config.store.backend, config.store.backups)?;
+    store.offer_legacy_migration(cli.migrate)?;
+    let store = match cli.list.as_deref() {
+        Some(name) => store.list(name)?,
+        None => store,
+    };
+
+    // Restoring must work even when the current store can't be loaded.
+    if let Some(cli::Commands::Restore { backup }) = cli.command {
+        let (active, archived) = store.restore(backup)?;
+        println!(
+            "Restored backup {} ({} active, {} archived tasks)",
+            backup,
+            active.len(),
+            archived.len()
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(cli::Commands::Compact) = cli.command {
+        let compacted = store.compact()?;
+        println!(
+            "Compacted {} operations in {} files to {}",
+            compacted.before, compacted.files, compacted.after
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // Every list moves at once, since they all use the configured backend.
+    if let Some(cli::Commands::Migrate { to }) = cli.command {
+        for name in store.lists()? {
+            let list = store.list(&name)?;
+            let (active, archived) = list.migrate(to)?;
+            println!(
+                "Copied {} tasks and {} archived tasks to {}",
+                active,
+                archived,
+                list.location_of(to).display()
+            );
+        }
+        println!(
+            "Set store.backend = \"{}\" in the config file to use it; the {} store was left as it was",
+            to, config.store.backend
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(cli::Commands::Lists) = cli.command {
+        let names = store.lists()?;
+        let width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+        for name in names {
+            let (active, archived) = store.list(&name)?.load()?;
+            let current = if name == store.name() { "*" } else { " " };
+            println!(
+                "{} {:width$}  {} active, {} archived",
+                current,
+                name,
+                active.len(),
+                archived.len(),
+                width = width
+            );
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let (active_tasks, archived_tasks) = store.load()?;
     let mut state = AppState {
         tasks: active_tasks,
         archived_tasks,
-        last_action: None,
+        journal: Journal::open(store.journal_path(), config.store.undo_levels)?,
     };
 
     // Match on the Option<Commands>
@@ -32,198 +152,796 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             due_date,
             tags,
             priority,
+            recur,
+            parent,
+            depends,
         }) => {
-            let due_date_parsed = due_date.map(|d| {
-                NaiveDate::parse_from_str(&d, "%Y-%m-%d")
-                    .expect("Invalid date format. Use YYYY-MM-DD.")
-                    .and_hms_opt(0, 0, 0)
-                    .unwrap()
-                    .and_utc()
-            });
-
-            let priority_enum = match priority.expect("Priority must be specified").to_lowercase().as_str() {
-                "low" => models::Priority::Low,
-                "medium" => models::Priority::Medium,
-                "high" => models::Priority::High,
-                _ => models::Priority::Low,
+            let parent = match parent {
+                Some(parent) => Some(state.tasks[models::select(&state.tasks, &parent)?].uuid),
+                None => None,
             };
+            let uuid = Uuid::new_v4();
+            let mut depends_on = Vec::new();
+            for selector in depends {
+                let dependency = state.tasks[models::select(&state.tasks, &selector)?].uuid;
+                models::check_dependency(&state.tasks, uuid, dependency)?;
+                if !depends_on.contains(&dependency) {
+                    depends_on.push(dependency);
+                }
+            }
+            let due_date_parsed = due_date.as_deref().map(dates::parse_due).transpose()?;
 
             let new_task = Task {
+                uuid,
                 id: state.tasks.len() as u32 + 1,
                 description,
                 tags,
                 due_date: due_date_parsed,
-                priority: priority_enum,
+                priority: priority.unwrap_or_default(),
                 completed: false,
+                recurrence: recur,
+                parent,
+                depends_on,
+                created: Some(Utc::now()),
+                completed_at: None,
+                annotations: Vec::new(),
+                ical_uid: None,
+                no_priority: false,
             };
 
-            state.last_action = Some(Action::Add(new_task.clone()));
+            println!("Added task {} ({})", new_task.id, new_task.short_id());
+            state.journal.record(Action::Add(new_task.clone()));
             state.tasks.push(new_task);
-            models::save_tasks(&state.tasks, &state.archived_tasks)?;
+            state.save(&store)?;
         }
 
         Some(cli::Commands::List {
             sort_by_due_date,
             tags,
             sort_by_priority,
+            sort,
+            actionable,
+            query,
+            output,
         }) => {
-            let mut tasks = state.tasks.clone();
+            let query = query::Query::parse(&query.join(" "), chrono::Local::now().naive_local())?;
+            let sort = match (sort, sort_by_priority, sort_by_due_date) {
+                (Some(sort), _, _) => sort,
+                (None, true, true) => "priority-,due+".parse()?,
+                (None, true, false) => "priority-".parse()?,
+                (None, false, true) => "due+".parse()?,
+                (None, false, false) => config.sort.default.clone(),
+            };
 
-            if let Some(filter_tags) = &tags {
-                tasks.retain(|task| filter_tags.iter().all(|tag| task.tags.contains(tag)));
-            }
+            // The lists are already loaded, so the tags are checked here
+            // rather than asked of the store again.
+            let tags = store::Filter {
+                tags: tags.unwrap_or_default(),
+                ..Default::default()
+            };
 
-            if sort_by_due_date {
-                tasks.sort_by(|a, b| {
-                    a.due_date
-                        .unwrap_or(MAX_DATETIME)
-                        .cmp(&b.due_date.unwrap_or(MAX_DATETIME))
-                });
+            // Subtasks stay under their parent, sorted among their siblings.
+            let order = sort.order(&state.tasks);
+            let mut tasks: Vec<(&Task, usize)> = models::sorted_tree(&state.tasks, &order, &HashSet::new())
+                .into_iter()
+                .map(|row| (&state.tasks[row.index], row.depth))
+                .filter(|(task, _)| tags.matches(task))
+                .collect();
+
+            if actionable {
+                tasks.retain(|(task, _)| models::is_actionable(&state.tasks, task));
             }
 
-            if sort_by_priority {
-                tasks.sort_by(|a, b| {
-                    let a_pri = a.priority.clone() as u8;
-                    let b_pri = b.priority.clone() as u8;
-                    a_pri.cmp(&b_pri)
-                });
+            tasks.retain(|(task, _)| query.matches(task, &state.tasks));
+
+            if let Some(format) = output.format {
+                let tasks: Vec<&Task> = tasks.iter().map(|(task, _)| *task).collect();
+                print!("{}", output::render(&tasks, format, output.fields.as_deref())?);
+                return Ok(found(&tasks));
             }
 
             println!("Active tasks:");
-            for task in tasks {
+            for &(task, depth) in &tasks {
                 let status = if task.completed { "[✓]" } else { "[ ]" };
+                let progress = models::progress(&state.tasks, task.uuid)
+                    .map(|(done, total)| format!(" [{}/{}]", done, total))
+                    .unwrap_or_default();
                 let due_date = task
                     .due_date
-                    .map(|d| d.format("%Y-%m-%d").to_string())
+                    .map(|d| dates::format(&d))
                     .unwrap_or_else(|| "No due date".into());
-                let overdue = task.due_date.map(|d| d < Utc::now()).unwrap_or(false);
+                let overdue = task.due_date.is_some_and(|d| dates::is_overdue(&d));
+                let repeats = task
+                    .recurrence
+                    .as_ref()
+                    .map(|r| format!(", Repeats: {}", r.describe()))
+                    .unwrap_or_default();
+                let blockers = models::blockers(&state.tasks, task);
+                let blocked = if blockers.is_empty() || task.completed {
+                    String::new()
+                } else {
+                    let ids: Vec<String> = blockers.iter().map(|t| t.id.to_string()).collect();
+                    format!(", Blocked by: {}", ids.join(", "))
+                };
 
                 println!(
-                    "{} {}: {} (Due: {}{}, Tags: {:?}, Priority: {:?})",
+                    "{}{} {} ({}): {}{} (Due: {}{}, Tags: {:?}, Priority: {:?}{}{})",
+                    "    ".repeat(depth),
                     status,
                     task.id,
+                    task.short_id(),
                     task.description,
+                    progress,
                     due_date,
                     if overdue { " (OVERDUE!)" } else { "" },
                     task.tags,
+                    task.priority,
+                    repeats,
+                    blocked
+                );
+            }
+            return Ok(found(&tasks));
+        }
+
+        Some(cli::Commands::Next { count }) => {
+            let mut scored: Vec<(f64, &Task)> = state
+                .tasks
+                .iter()
+                .filter(|task| !task.completed)
+                .map(|task| (urgency::score(task, &state.tasks, &config.urgency), task))
+                .collect();
+            // Stable, so equally urgent tasks keep their list order.
+            scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+            println!("Most urgent tasks:");
+            for (score, task) in scored.into_iter().take(count) {
+                let due_date = task
+                    .due_date
+                    .map(|d| dates::format(&d))
+                    .unwrap_or_else(|| "No due date".into());
+                println!(
+                    "{:>6.2}  {} ({}): {} (Due: {}, Tags: {:?}, Priority: {:?})",
+                    score,
+                    task.id,
+                    task.short_id(),
+                    task.description,
+                    due_date,
+                    task.tags,
                     task.priority
                 );
             }
         }
 
-        Some(cli::Commands::Done { id }) => {
-            if let Some(task) = state.tasks.iter_mut().find(|t| t.id == id) {
-                task.completed = true;
-                state.last_action = Some(Action::Done(id));
-                println!("Marked task {} as done", id);
-            } else {
-                println!("Task with ID {} not found", id);
+        Some(cli::Commands::Done { id, recursive }) => {
+            let index = models::select(&state.tasks, &id)?;
+            let task = &state.tasks[index];
+            let (uuid, display) = (task.uuid, format!("{} ({})", task.id, task.short_id()));
+            let unfinished = actions::unfinished_subtasks(&state.tasks, uuid);
+            let subtasks = recursive
+                || (unfinished > 0
+                    && confirm(&format!(
+                        "Task {} has {} unfinished subtasks. Mark them as done too?",
+                        display, unfinished
+                    ))?);
+
+            if let Some(action) = actions::complete_tree(&mut state.tasks, index, subtasks) {
+                report_next_instances(&action, &state.tasks);
+                state.journal.record(action);
             }
-            models::save_tasks(&state.tasks, &state.archived_tasks)?;
+            println!("Marked task {} as done", display);
+            if subtasks && unfinished > 0 {
+                println!("Also marked {} subtasks as done", unfinished);
+            } else if unfinished > 0 {
+                eprintln!(
+                    "note: {} subtasks are still open; pass --recursive to finish them too",
+                    unfinished
+                );
+            }
+            state.save(&store)?;
         }
 
-        Some(cli::Commands::Delete { id }) => {
-            if let Some(task) = state.tasks.iter().find(|t| t.id == id).cloned() {
-                state.last_action = Some(Action::Delete(task.clone()));
-                state.tasks.retain(|t| t.id != id);
+        Some(cli::Commands::Move { id, to }) => {
+            let target = store.list(&to)?;
+            if target.name() == store.name() {
+                return Err(store::StoreError::SameList(to).into());
+            }
+            let (tasks, archived_tasks) = target.load()?;
+            let mut there = AppState {
+                tasks,
+                archived_tasks,
+                journal: Journal::open(target.journal_path(), config.store.undo_levels)?,
+            };
 
-                // Reassign IDs
-                for (idx, task) in state.tasks.iter_mut().enumerate() {
-                    task.id = (idx + 1) as u32;
-                }
+            let index = models::select(&state.tasks, &id)?;
+            let task = &state.tasks[index];
+            let (uuid, display) = (task.uuid, format!("{} ({})", task.id, task.short_id()));
+            let mut uuids = vec![uuid];
+            uuids.extend(models::descendants(&state.tasks, uuid));
+            let moved: Vec<Task> = state
+                .tasks
+                .iter()
+                .filter(|t| uuids.contains(&t.uuid))
+                .cloned()
+                .collect();
+            state.tasks.retain(|t| !uuids.contains(&t.uuid));
+            models::renumber(&mut state.tasks);
 
-                println!("Deleted task {}", id);
-            } else {
-                println!("Task with ID {} not found", id);
+            // The parent of a moved subtask stays behind, and dependencies
+            // can't reach into another list.
+            let mut added = Vec::with_capacity(moved.len());
+            let mut dropped = Vec::new();
+            for task in &moved {
+                let copy = Task {
+                    id: there.tasks.len() as u32 + 1,
+                    parent: task.parent.filter(|_| task.uuid != uuid),
+                    depends_on: task.depends_on.iter().copied().filter(|d| uuids.contains(d)).collect(),
+                    ..task.clone()
+                };
+                if copy.depends_on.len() != task.depends_on.len() {
+                    dropped.push(task.short_id());
+                }
+                there.tasks.push(copy.clone());
+                added.push(copy);
+            }
+            let mut actions = vec![Action::MoveOut {
+                tasks: moved,
+                to: target.name().into(),
+            }];
+            for task in state
+                .tasks
+                .iter_mut()
+                .filter(|t| t.depends_on.iter().any(|d| uuids.contains(d)))
+            {
+                let before = task.clone();
+                task.depends_on.retain(|d| !uuids.contains(d));
+                dropped.push(task.short_id());
+                actions.push(Action::Edit {
+                    before: Box::new(before),
+                    after: Box::new(task.clone()),
+                });
+            }
+            println!(
+                "Moved task {} to list {} as task {}",
+                display,
+                target.name(),
+                added[0].id
+            );
+            if added.len() > 1 {
+                println!("Also moved {} subtasks", added.len() - 1);
+            }
+            if !dropped.is_empty() {
+                eprintln!(
+                    "note: dependencies between the lists were removed from tasks {}",
+                    dropped.join(", ")
+                );
             }
-            models::save_tasks(&state.tasks, &state.archived_tasks)?;
+
+            state.journal.record(match actions.len() {
+                1 => actions.remove(0),
+                _ => Action::Batch(actions),
+            });
+            there.journal.record(Action::MoveIn {
+                tasks: added,
+                from: store.name().into(),
+            });
+            // Saved there first: if saving here then fails the tasks are in
+            // both lists rather than in neither.
+            there.save(&target)?;
+            state.save(&store)?;
         }
 
-        Some(cli::Commands::Archive) => {
-            let mut archived = Vec::new();
-            state.tasks.retain(|task| {
-                if task.completed {
-                    archived.push(task.clone());
-                    false
-                } else {
-                    true
+        Some(cli::Commands::Modify {
+            id,
+            description,
+            due,
+            clear_due,
+            priority,
+            add_tag,
+            remove_tag,
+            recur,
+            clear_recur,
+            depends,
+            remove_depends,
+        }) => {
+            let index = models::select(&state.tasks, &id)?;
+            let before = state.tasks[index].clone();
+            let mut after = before.clone();
+
+            if let Some(description) = description {
+                after.description = description;
+            }
+            if let Some(due) = due {
+                after.due_date = Some(dates::parse_due(&due)?);
+            }
+            if clear_due {
+                after.due_date = None;
+            }
+            if let Some(priority) = priority {
+                after.priority = priority;
+                after.no_priority = false;
+            }
+            if let Some(recur) = recur {
+                after.recurrence = Some(recur);
+            }
+            if clear_recur {
+                after.recurrence = None;
+            }
+            after.tags.retain(|tag| !remove_tag.contains(tag));
+            for tag in add_tag {
+                if !after.tags.contains(&tag) {
+                    after.tags.push(tag);
                 }
-            });
+            }
+            for selector in remove_depends {
+                let dependency = state.tasks[models::select(&state.tasks, &selector)?].uuid;
+                after.depends_on.retain(|uuid| *uuid != dependency);
+            }
+            for selector in depends {
+                let dependency = state.tasks[models::select(&state.tasks, &selector)?].uuid;
+                models::check_dependency(&state.tasks, after.uuid, dependency)?;
+                if !after.depends_on.contains(&dependency) {
+                    after.depends_on.push(dependency);
+                }
+            }
 
-            state.archived_tasks.extend(archived.clone());
-            state.last_action = Some(Action::Archive(archived.len()));
-            println!("Archived {} tasks", archived.len());
+            let action = Action::for_edit(&before, &after);
 
-            for (index, task) in state.tasks.iter_mut().enumerate() {
-                task.id = (index + 1) as u32;
+            match action {
+                Some(action) => {
+                    println!(
+                        "Modified task {} ({}): {}",
+                        after.id,
+                        after.short_id(),
+                        action.describe()
+                    );
+                    state.tasks[index] = after;
+                    state.journal.record(action);
+                    state.save(&store)?;
+                }
+                None => println!("Nothing to change for task {} ({})", before.id, before.short_id()),
             }
+        }
 
-            for (index, task) in state.archived_tasks.iter_mut().enumerate() {
-                task.id = (index + 1) as u32;
+        Some(cli::Commands::Deps { id }) => {
+            let task = &state.tasks[models::select(&state.tasks, &id)?];
+            println!("{}", dependency_line(task, &state.tasks));
+            if task.depends_on.is_empty() {
+                println!("  depends on nothing");
+            } else {
+                println!("  depends on:");
+                print_dependencies(task, &state, 2, &mut vec![task.uuid]);
             }
+            let dependents: Vec<&Task> = models::dependents(&state.tasks, task.uuid).collect();
+            if !dependents.is_empty() {
+                println!("  needed by:");
+                for dependent in dependents {
+                    println!("    {}", dependency_line(dependent, &state.tasks));
+                }
+            }
+        }
 
-            models::save_tasks(&state.tasks, &state.archived_tasks)?;
+        Some(cli::Commands::Delete { id }) => {
+            let task = state.tasks.remove(models::select(&state.tasks, &id)?);
+            println!("Deleted task {} ({})", task.id, task.short_id());
+            state.journal.record(Action::Delete(task));
+            models::renumber(&mut state.tasks);
+            state.save(&store)?;
         }
 
-        Some(cli::Commands::ListArchived) => {
+        Some(cli::Commands::Archive) => match actions::archive(&mut state.tasks, &mut state.archived_tasks) {
+            Some(action) => {
+                if let Action::Archive(moved) = &action {
+                    println!("Archived {} tasks", moved.len());
+                }
+                state.journal.record(action);
+                state.save(&store)?;
+            }
+            None => println!("Archived 0 tasks"),
+        },
+
+        Some(cli::Commands::ListArchived { output }) => {
+            let archived = store.query(&store::Filter {
+                archived: true,
+                ..Default::default()
+            })?;
+            if let Some(format) = output.format {
+                let tasks: Vec<&Task> = archived.iter().collect();
+                print!("{}", output::render(&tasks, format, output.fields.as_deref())?);
+                return Ok(found(&tasks));
+            }
+
             println!("Archived tasks:");
-            for task in &state.archived_tasks {
+            for task in &archived {
                 let due_date = task
                     .due_date
-                    .map(|d| d.format("%Y-%m-%d").to_string())
+                    .map(|d| dates::format(&d))
                     .unwrap_or_else(|| "No due date".into());
+                // Tasks deleted in Taskwarrior are archived without being done.
+                let status = if task.completed { "[✓]" } else { "[-]" };
 
                 println!(
-                    "[✓] {}: {} (Due: {}, Tags: {:?})",
-                    task.id, task.description, due_date, task.tags
+                    "{} {}: {} (Due: {}, Tags: {:?})",
+                    status, task.id, task.description, due_date, task.tags
                 );
             }
+            return Ok(found(&archived));
         }
 
-        Some(cli::Commands::Undo) => {
-            if let Some(action) = state.last_action.take() {
-                match action {
-                    Action::Add(task) => {
-                        state.tasks.retain(|t| t.id != task.id);
-                        println!("Undone: Removed added task '{}'", task.description);
-                    }
-                    Action::Delete(task) => {
-                        state.tasks.push(task);
-                        println!("Undone: Restored deleted task");
+        Some(cli::Commands::Undo { count }) => {
+            let mut others = Others::new();
+            let mut undone = 0;
+            while undone < count {
+                match state.journal.undo(&mut state.tasks, &mut state.archived_tasks)? {
+                    Some(action) => {
+                        other_half(&store, &config, &action, true, &mut others)?;
+                        println!("Undone: {}", action.describe());
                     }
-                    Action::Done(id) => {
-                        if let Some(task) = state.tasks.iter_mut().find(|t| t.id == id) {
-                            task.completed = false;
-                            println!("Undone: Marked task {} as incomplete", id);
-                        }
-                    }
-                    Action::Archive(count) => {
-                        let mut restored = Vec::new();
-                        state.archived_tasks.retain(|task| {
-                            if task.completed {
-                                restored.push(task.clone());
-                                false
-                            } else {
-                                true
-                            }
-                        });
-                        state.tasks.extend(restored);
-                        println!("Undone: Restored {} archived tasks", count);
+                    None => break,
+                }
+                undone += 1;
+            }
+            if undone == 0 {
+                println!("Nothing to undo!");
+            } else {
+                save_all(&store, &state, &mut others)?;
+            }
+        }
+
+        Some(cli::Commands::Redo { count }) => {
+            let mut others = Others::new();
+            let mut redone = 0;
+            while redone < count {
+                match state.journal.redo(&mut state.tasks, &mut state.archived_tasks)? {
+                    Some(action) => {
+                        other_half(&store, &config, &action, false, &mut others)?;
+                        println!("Redone: {}", action.describe());
                     }
+                    None => break,
                 }
-                models::save_tasks(&state.tasks, &state.archived_tasks)?;
+                redone += 1;
+            }
+            if redone == 0 {
+                println!("Nothing to redo!");
             } else {
-                println!("Nothing to undo!");
+                save_all(&store, &state, &mut others)?;
+            }
+        }
+
+        Some(cli::Commands::History) => {
+            let redoable: Vec<_> = state.journal.redoable().collect();
+            if !redoable.is_empty() {
+                println!("Can be redone:");
+                for entry in redoable.iter().rev() {
+                    println!(
+                        "      {}  {}",
+                        entry.at.format("%Y-%m-%d %H:%M"),
+                        entry.action.describe()
+                    );
+                }
+            }
+            println!("Can be undone (most recent first):");
+            for (n, entry) in state.journal.undoable().enumerate() {
+                println!(
+                    "  {:>3} {}  {}",
+                    n + 1,
+                    entry.at.format("%Y-%m-%d %H:%M"),
+                    entry.action.describe()
+                );
+            }
+        }
+
+        Some(cli::Commands::Export {
+            format,
+            output,
+            archived,
+        }) => {
+            let archived_tasks: &[Task] = if archived { &state.archived_tasks } else { &[] };
+            let text = exchange::export(format, &state.tasks, archived_tasks)?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, text).map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+                    eprintln!(
+                        "Exported {} tasks to {}",
+                        state.tasks.len() + archived_tasks.len(),
+                        path.display()
+                    );
+                }
+                None => print!("{}", text),
+            }
+        }
+
+        Some(cli::Commands::Import { file, from }) => {
+            let text = if file.as_os_str() == "-" {
+                std::io::read_to_string(std::io::stdin())?
+            } else {
+                std::fs::read_to_string(&file).map_err(|e| format!("could not read {}: {}", file.display(), e))?
+            };
+            let from = from.unwrap_or_else(|| exchange::Format::detect(&file));
+            let parsed = exchange::parse(from, &text)?;
+            for error in &parsed.errors {
+                eprintln!("skipped {}", error);
             }
+            let unreadable = parsed.errors.len();
+
+            let merged = exchange::merge(&mut state.tasks, &mut state.archived_tasks, parsed, Utc::now());
+            println!(
+                "Imported {} tasks and {} archived tasks ({} already here, {} unreadable)",
+                merged.tasks.len(),
+                merged.archived.len(),
+                merged.duplicates,
+                unreadable
+            );
+            if !merged.tasks.is_empty() || !merged.archived.is_empty() {
+                state.journal.record(Action::Import {
+                    tasks: merged.tasks,
+                    archived: merged.archived,
+                });
+            }
+            state.save(&store)?;
         }
 
+        Some(cli::Commands::Sync {
+            target: cli::SyncTarget::Caldav { prefer, dry_run },
+        }) => {
+            let caldav = match &config.sync.caldav {
+                Some(caldav) => Some(caldav.for_list(store.name())?),
+                None => None,
+            };
+            let (report, action) = sync::caldav::sync(
+                caldav.as_ref(),
+                store.sync_state_path("caldav"),
+                &mut state.tasks,
+                &state.archived_tasks,
+                prefer,
+                dry_run,
+            )?;
+            let would = if dry_run { "would be " } else { "" };
+            println!(
+                "Server: {} {}created, {} {}updated, {} {}deleted",
+                report.created, would, report.pushed, would, report.deleted_remote, would
+            );
+            println!(
+                "Here: {} {}added, {} {}updated, {} {}deleted",
+                report.added, would, report.pulled, would, report.deleted_local, would
+            );
+            for skipped in &report.skipped {
+                eprintln!("skipped {}", skipped);
+            }
+            if !report.conflicts.is_empty() {
+                eprintln!("{} conflicts, left as they are on both sides:", report.conflicts.len());
+                for conflict in &report.conflicts {
+                    eprintln!("    {}", conflict);
+                }
+                eprintln!("note: run again with --prefer local or --prefer remote to settle them");
+            }
+            if let Some(action) = action {
+                state.journal.record(action);
+            }
+            if !dry_run {
+                state.save(&store)?;
+            }
+            if !report.conflicts.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+
+        Some(cli::Commands::Sync {
+            target: cli::SyncTarget::Git { prefer, dry_run },
+        }) => {
+            let git = config.sync.git.as_ref().map(|git| git.for_list(store.name()));
+            let (report, action) = sync::git::sync(
+                git.as_ref(),
+                store.sync_state_path("git"),
+                &mut state.tasks,
+                &mut state.archived_tasks,
+                prefer,
+                dry_run,
+            )?;
+            let would = if dry_run { "would be " } else { "" };
+            println!(
+                "Here: {} {}added, {} {}updated, {} {}deleted",
+                report.added, would, report.updated, would, report.deleted, would
+            );
+            match &report.pushed {
+                Some(commit) => println!("Pushed {}", commit),
+                None if !dry_run => println!("Nothing to push"),
+                None => {}
+            }
+            if !report.conflicts.is_empty() {
+                eprintln!("{} conflicts:", report.conflicts.len());
+                for conflict in &report.conflicts {
+                    eprintln!("    {}", conflict);
+                }
+                eprintln!("note: the versions not kept are still in the repository's history");
+            }
+            if let Some(action) = action {
+                state.journal.record(action);
+                state.save(&store)?;
+            }
+            if !report.conflicts.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+
+        // Handled before loading the store
+        Some(
+            cli::Commands::Restore { .. }
+            | cli::Commands::Compact
+            | cli::Commands::Migrate { .. }
+            | cli::Commands::Lists,
+        ) => unreachable!(),
+
         // Handle TUI mode (either via --tui or default)
         Some(cli::Commands::Tui) | None => {
-            tui::run_tui(&mut state.tasks, &mut state.archived_tasks)?;
-            // Save any changes made in the TUI
-            models::save_tasks(&state.tasks, &state.archived_tasks)?;
+            let mut store = store;
+            let mut others = Others::new();
+            loop {
+                let mut lists = store.lists()?;
+                if !lists.iter().any(|name| name == store.name()) {
+                    lists.push(store.name().into());
+                }
+                let next = tui::run_tui(
+                    &mut state.tasks,
+                    &mut state.archived_tasks,
+                    &mut state.journal,
+                    &config,
+                    &lists,
+                    store.name(),
+                    &mut |action, undo| other_half(&store, &config, action, undo, &mut others),
+                )?;
+                // Save any changes made in the TUI
+                save_all(&store, &state, &mut others)?;
+
+                let Some(name) = next else { break };
+                store = store.list(&name)?;
+                let (tasks, archived_tasks) = store.load()?;
+                state = AppState {
+                    tasks,
+                    archived_tasks,
+                    journal: Journal::open(store.journal_path(), config.store.undo_levels)?,
+                };
+            }
         }
     }
 
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Undoes or redoes the other list's half of a move that was just undone or
+/// redone here, so a move is only ever undone as a whole. That half must be
+/// the next thing to undo or redo there. The other list is loaded into
+/// `others` and changed there, to be saved by `save_all`; an error leaves
+/// it as it was.
+fn other_half(
+    store: &store::Store,
+    config: &config::Config,
+    action: &Action,
+    undo: bool,
+    others: &mut Others,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some((tasks, list, out)) = action.list_move() else {
+        return Ok(());
+    };
+    if !others.contains_key(list) {
+        let other = store.list(list)?;
+        let (active, archived_tasks) = other.load()?;
+        let journal = Journal::open(other.journal_path(), config.store.undo_levels)?;
+        let there = AppState {
+            tasks: active,
+            archived_tasks,
+            journal,
+        };
+        others.insert(list.into(), (other, there));
+    }
+    let (_, there) = others.get_mut(list).expect("loaded above");
+
+    let uuids = |tasks: &[Task]| tasks.iter().map(|t| t.uuid).collect::<HashSet<_>>();
+    let next = match undo {
+        true => there.journal.undoable().next(),
+        false => there.journal.redoable().next(),
+    };
+    let matches =
+        next.and_then(|entry| entry.action.list_move())
+            .is_some_and(|(their_tasks, their_list, their_out)| {
+                their_list == store.name() && their_out != out && uuids(their_tasks) == uuids(tasks)
+            });
+    if !matches {
+        return Err(actions::ActionError::OtherList {
+            list: list.into(),
+            verb: if undo { "undo" } else { "redo" },
+        }
+        .into());
+    }
+    if undo {
+        there.journal.undo(&mut there.tasks, &mut there.archived_tasks)?;
+    } else {
+        there.journal.redo(&mut there.tasks, &mut there.archived_tasks)?;
+    }
     Ok(())
 }
+
+/// Lists exit with 1 when nothing matched, like grep, so scripts can branch
+/// on it.
+fn found<T>(tasks: &[T]) -> ExitCode {
+    if tasks.is_empty() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// `[ ] 3 (1f0c9a2b): description`, marked blocked when it is waiting.
+fn dependency_line(task: &Task, tasks: &[Task]) -> String {
+    let status = if task.completed {
+        "[✓]"
+    } else if models::is_blocked(tasks, task) {
+        "[⊘]"
+    } else {
+        "[ ]"
+    };
+    format!("{} {} ({}): {}", status, task.id, task.short_id(), task.description)
+}
+
+/// Prints what `task` depends on as an indented tree. `path` holds the tasks
+/// above, so a cycle in a hand-edited store is shown instead of followed.
+fn print_dependencies(task: &Task, state: &AppState, depth: usize, path: &mut Vec<Uuid>) {
+    for uuid in &task.depends_on {
+        let indent = "  ".repeat(depth);
+        let found = models::position(&state.tasks, *uuid)
+            .map(|i| &state.tasks[i])
+            .or_else(|| models::position(&state.archived_tasks, *uuid).map(|i| &state.archived_tasks[i]));
+        match found {
+            Some(dependency) if path.contains(uuid) => {
+                println!("{}{} (cycle!)", indent, dependency_line(dependency, &state.tasks));
+            }
+            Some(dependency) if models::position(&state.tasks, *uuid).is_none() => {
+                println!(
+                    "{}[✓] {}: {} (archived)",
+                    indent,
+                    dependency.short_id(),
+                    dependency.description
+                );
+            }
+            Some(dependency) => {
+                println!("{}{}", indent, dependency_line(dependency, &state.tasks));
+                path.push(*uuid);
+                print_dependencies(dependency, state, depth + 1, path);
+                path.pop();
+            }
+            None => println!("{}{} (deleted)", indent, &uuid.simple().to_string()[..8]),
+        }
+    }
+}
+
+/// Asks a yes/no question on the terminal. Without one the answer is no.
+fn confirm(question: &str) -> std::io::Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(false);
+    }
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Tells the user about the tasks that completing recurring ones added.
+fn report_next_instances(action: &Action, tasks: &[Task]) {
+    match action {
+        Action::Recur { next, .. } => {
+            let id = models::position(tasks, next.uuid).map_or(next.id, |i| tasks[i].id);
+            println!(
+                "Added the next instance as task {} ({}), due {}",
+                id,
+                next.short_id(),
+                next.due_date.map(|d| dates::format(&d)).unwrap_or_default()
+            );
+        }
+        Action::Batch(actions) => {
+            for action in actions {
+                report_next_instances(action, tasks);
+            }
+        }
+        _ => {}
+    }
+}
@@ -1,52 +1,457 @@
 // models.rs
-use serde::{Serialize, Deserialize};
+use std::collections::HashSet;
+use std::str::FromStr;
+
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+use crate::recurrence::Recurrence;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
 pub enum Priority {
+    #[default]
     Low,
     Medium,
     High,
 }
 
-impl Default for Priority {
-    fn default() -> Self {
-        Priority::Low
+impl Priority {
+    /// 0 for low up to 2 for high.
+    pub fn rank(&self) -> u8 {
+        match self {
+            Priority::Low => 0,
+            Priority::Medium => 1,
+            Priority::High => 2,
+        }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Default, Clone)] // Added `Clone`
+#[derive(Debug, Error)]
+#[error("invalid priority {0:?} (expected low, medium or high)")]
+pub struct ParsePriorityError(String);
+
+impl FromStr for Priority {
+    type Err = ParsePriorityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "low" | "l" => Ok(Priority::Low),
+            "medium" | "m" => Ok(Priority::Medium),
+            "high" | "h" => Ok(Priority::High),
+            _ => Err(ParsePriorityError(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)] // Added `Clone`
 pub struct Task {
+    /// Permanent identifier, never reused. Stores written before it existed
+    /// get one assigned on load.
+    #[serde(default = "Uuid::new_v4")]
+    pub uuid: Uuid,
+    /// Position in its list, starting at 1. Changes whenever a task before
+    /// it is removed; use `uuid` to refer to a task in scripts.
     pub id: u32,
     pub description: String,
     pub tags: Vec<String>,
     pub due_date: Option<DateTime<Utc>>,
     pub priority: Priority,
     pub completed: bool,
+    /// Completing a task with a rule adds its next instance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<Recurrence>,
+    /// The task this one is a step of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<Uuid>,
+    /// Tasks that must be done before this one can start.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<Uuid>,
+    /// When the task was added. Unknown for tasks from older stores.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<DateTime<Utc>>,
+    /// When the task was marked done.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Timestamped notes, as Taskwarrior keeps them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<Annotation>,
+    /// The iCalendar UID of a task imported from a calendar whose UIDs
+    /// aren't uuids. Others use their uuid as UID.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ical_uid: Option<String>,
+    /// Imported from Taskwarrior without a priority. It reads as low, and
+    /// is exported without one again as long as it stays low and isn't
+    /// set.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_priority: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    pub entry: DateTime<Utc>,
+    pub description: String,
+}
+
+/// Reassigns the display ids so they run from 1 in list order.
+pub fn renumber(tasks: &mut [Task]) {
+    for (index, task) in tasks.iter_mut().enumerate() {
+        task.id = (index + 1) as u32;
+    }
+}
+
+impl Task {
+    /// First 8 hex digits of the uuid, enough to tell tasks apart in
+    /// practice. Any unique prefix can be used to select a task.
+    pub fn short_id(&self) -> String {
+        self.uuid.simple().to_string()[..8].to_string()
+    }
+
+    /// The UID the task has in iCalendar files and CalDAV collections.
+    pub fn ical_uid(&self) -> String {
+        self.ical_uid.clone().unwrap_or_else(|| self.uuid.to_string())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DependencyError {
+    #[error("a task can't depend on itself")]
+    OnItself,
+    #[error("that would create a cycle: {0}")]
+    Cycle(String),
+}
+
+#[derive(Debug, Error)]
+pub enum SelectError {
+    #[error("no task matches {0:?}")]
+    NotFound(String),
+    #[error("{0:?} matches {1} tasks; use a longer id prefix")]
+    Ambiguous(String, usize),
+}
+
+/// Finds a task by display index (`3`) or by a unique prefix of its uuid
+/// (`1f0c9a`, dashes optional). A number is tried as a display index first.
+pub fn select(tasks: &[Task], selector: &str) -> Result<usize, SelectError> {
+    let selector = selector.trim();
+    if let Ok(id) = selector.parse::<u32>() {
+        if let Some(index) = tasks.iter().position(|t| t.id == id) {
+            return Ok(index);
+        }
+    }
+
+    let prefix = selector.replace('-', "").to_lowercase();
+    if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(SelectError::NotFound(selector.to_string()));
+    }
+
+    let mut matches = tasks
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| t.uuid.simple().to_string().starts_with(&prefix))
+        .map(|(index, _)| index);
+    match (matches.next(), matches.count()) {
+        (Some(index), 0) => Ok(index),
+        (Some(_), others) => Err(SelectError::Ambiguous(selector.to_string(), others + 1)),
+        (None, _) => Err(SelectError::NotFound(selector.to_string())),
+    }
+}
+
+pub fn position(tasks: &[Task], uuid: Uuid) -> Option<usize> {
+    tasks.iter().position(|t| t.uuid == uuid)
+}
+
+/// Direct children of the task `uuid`, in list order.
+pub fn children(tasks: &[Task], uuid: Uuid) -> impl Iterator<Item = &Task> {
+    tasks.iter().filter(move |t| t.parent == Some(uuid))
 }
 
-pub fn save_tasks(active: &[Task], archived: &[Task]) -> std::io::Result<()> {
-    let data = serde_json::json!({
-        "active": active,
-        "archived": archived
-    });
-    std::fs::write("tasks.json", serde_json::to_string_pretty(&data)?)
-}
-
-pub fn load_tasks() -> (Vec<Task>, Vec<Task>) {
-    match std::fs::read_to_string("tasks.json") {
-        Ok(data) => {
-            let parsed: serde_json::Value = serde_json::from_str(&data).unwrap_or_default();
-            let active = parsed["active"]
-                .as_array()
-                .map(|t| serde_json::from_value(serde_json::Value::Array(t.clone())).unwrap())
-                .unwrap_or_default();
-            let archived = parsed["archived"]
-                .as_array()
-                .map(|t| serde_json::from_value(serde_json::Value::Array(t.clone())).unwrap())
-                .unwrap_or_default();
-            (active, archived)
-        }
-        Err(_) => (Vec::new(), Vec::new()),
+/// Uuids of every task below `uuid`, parents before their children.
+pub fn descendants(tasks: &[Task], uuid: Uuid) -> Vec<Uuid> {
+    let mut found = Vec::new();
+    let mut pending = vec![uuid];
+    while let Some(parent) = pending.pop() {
+        for child in children(tasks, parent) {
+            // A hand-edited store could contain a cycle.
+            if child.uuid != uuid && !found.contains(&child.uuid) {
+                found.push(child.uuid);
+                pending.push(child.uuid);
+            }
+        }
+    }
+    found
+}
+
+/// Completed and total direct children, or `None` for a task without any.
+pub fn progress(tasks: &[Task], uuid: Uuid) -> Option<(usize, usize)> {
+    let (done, total) = children(tasks, uuid).fold((0, 0), |(done, total), t| (done + t.completed as usize, total + 1));
+    (total > 0).then_some((done, total))
+}
+
+/// One line of a task list shown as a tree.
+#[derive(Debug, Clone, Copy)]
+pub struct TreeRow {
+    /// Index into the task list.
+    pub index: usize,
+    pub depth: usize,
+}
+
+/// The tasks in tree order: each task followed by its children, skipping
+/// the children of tasks in `collapsed`. Tasks whose parent isn't in the
+/// list are shown at the top level.
+pub fn tree(tasks: &[Task], collapsed: &HashSet<Uuid>) -> Vec<TreeRow> {
+    let order: Vec<usize> = (0..tasks.len()).collect();
+    sorted_tree(tasks, &order, collapsed)
+}
+
+/// Like `tree`, with siblings in the order they appear in `order`, a
+/// permutation of the indices of `tasks`.
+pub fn sorted_tree(tasks: &[Task], order: &[usize], collapsed: &HashSet<Uuid>) -> Vec<TreeRow> {
+    let present: HashSet<Uuid> = tasks.iter().map(|t| t.uuid).collect();
+    let mut seen = HashSet::new();
+    let mut rows = Vec::new();
+    let mut pending: Vec<TreeRow> = order
+        .iter()
+        .filter(|&&i| !tasks[i].parent.is_some_and(|p| present.contains(&p)))
+        .map(|&index| TreeRow { index, depth: 0 })
+        .rev()
+        .collect();
+
+    loop {
+        while let Some(row) = pending.pop() {
+            let task = &tasks[row.index];
+            if !seen.insert(task.uuid) {
+                continue;
+            }
+            rows.push(row);
+            if collapsed.contains(&task.uuid) {
+                seen.extend(descendants(tasks, task.uuid));
+                continue;
+            }
+            pending.extend(
+                order
+                    .iter()
+                    .filter(|&&i| tasks[i].parent == Some(task.uuid))
+                    .map(|&index| TreeRow {
+                        index,
+                        depth: row.depth + 1,
+                    })
+                    .rev(),
+            );
+        }
+        // Anything left over is part of a parent cycle; show it at the top.
+        match order.iter().find(|&&i| !seen.contains(&tasks[i].uuid)) {
+            Some(&index) => pending.push(TreeRow { index, depth: 0 }),
+            None => break,
+        }
+    }
+    rows
+}
+
+/// The unfinished tasks in `tasks` that `task` is waiting for. Dependencies
+/// that were archived or deleted no longer block anything.
+pub fn blockers<'a>(tasks: &'a [Task], task: &Task) -> Vec<&'a Task> {
+    task.depends_on
+        .iter()
+        .filter_map(|uuid| position(tasks, *uuid))
+        .map(|index| &tasks[index])
+        .filter(|t| !t.completed)
+        .collect()
+}
+
+pub fn is_blocked(tasks: &[Task], task: &Task) -> bool {
+    !blockers(tasks, task).is_empty()
+}
+
+/// Not done and not waiting for anything.
+pub fn is_actionable(tasks: &[Task], task: &Task) -> bool {
+    !task.completed && !is_blocked(tasks, task)
+}
+
+/// Tasks that list `uuid` as a dependency.
+pub fn dependents(tasks: &[Task], uuid: Uuid) -> impl Iterator<Item = &Task> {
+    tasks.iter().filter(move |t| t.depends_on.contains(&uuid))
+}
+
+/// Checks that making `task` depend on `dependency` keeps the graph acyclic,
+/// i.e. that `dependency` doesn't already (indirectly) depend on `task`.
+pub fn check_dependency(tasks: &[Task], task: Uuid, dependency: Uuid) -> Result<(), DependencyError> {
+    if task == dependency {
+        return Err(DependencyError::OnItself);
+    }
+
+    // Depth first search from `dependency`, remembering how each task was reached.
+    let mut came_from = std::collections::HashMap::new();
+    let mut pending = vec![dependency];
+    while let Some(current) = pending.pop() {
+        if current == task {
+            let mut path = vec![task];
+            while let Some(previous) = came_from.get(path.last().unwrap()) {
+                path.push(*previous);
+            }
+            path.reverse();
+            path.insert(0, task);
+            let names: Vec<String> = path.iter().map(|uuid| describe(tasks, *uuid)).collect();
+            return Err(DependencyError::Cycle(names.join(" -> ")));
+        }
+        let Some(index) = position(tasks, current) else {
+            continue;
+        };
+        for next in &tasks[index].depends_on {
+            if *next != dependency && !came_from.contains_key(next) {
+                came_from.insert(*next, current);
+                pending.push(*next);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `3 (1f0c9a2b)`, or just the short uuid for a task that isn't in `tasks`.
+fn describe(tasks: &[Task], uuid: Uuid) -> String {
+    match position(tasks, uuid) {
+        Some(index) => format!("{} ({})", tasks[index].id, tasks[index].short_id()),
+        None => uuid.simple().to_string()[..8].to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tasks 1, 2, 3, ... with uuids `00000000-...-000000000001` and up.
+    fn tasks(count: u128) -> Vec<Task> {
+        let mut tasks: Vec<Task> = (1..=count)
+            .map(|n| Task {
+                uuid: Uuid::from_u128(n),
+                description: format!("task {}", n),
+                ..Task::default()
+            })
+            .collect();
+        renumber(&mut tasks);
+        tasks
+    }
+
+    fn uuid(n: u128) -> Uuid {
+        Uuid::from_u128(n)
+    }
+
+    /// Tasks 1 to 3 with uuids that share prefixes.
+    fn to_select() -> Vec<Task> {
+        let mut tasks = tasks(3);
+        for (task, uuid) in tasks.iter_mut().zip([
+            "1f0c9a2b-0000-4000-8000-000000000001",
+            "1f0d0000-0000-4000-8000-000000000002",
+            "12ab0000-0000-4000-8000-000000000003",
+        ]) {
+            task.uuid = uuid.parse().unwrap();
+        }
+        tasks
+    }
+
+    #[test]
+    fn select_by_display_id() {
+        let tasks = to_select();
+        assert_eq!(select(&tasks, "1").unwrap(), 0);
+        assert_eq!(select(&tasks, " 3 ").unwrap(), 2);
+    }
+
+    #[test]
+    fn select_by_unique_prefix() {
+        let tasks = to_select();
+        assert_eq!(select(&tasks, "1f0c").unwrap(), 0);
+        assert_eq!(select(&tasks, "1F0D").unwrap(), 1);
+        assert_eq!(select(&tasks, "1f0c9a2b-0000").unwrap(), 0);
+        // No task has id 12, so it's read as a prefix.
+        assert_eq!(select(&tasks, "12").unwrap(), 2);
+    }
+
+    #[test]
+    fn select_refuses_ambiguous_prefixes() {
+        let tasks = to_select();
+        assert!(matches!(select(&tasks, "1f0"), Err(SelectError::Ambiguous(_, 2))));
+        // All three uuids start with 1, but the display id comes first.
+        assert!(matches!(select(&tasks, "1"), Ok(0)));
+        assert_eq!(
+            select(&tasks, "1f").unwrap_err().to_string(),
+            "\"1f\" matches 2 tasks; use a longer id prefix"
+        );
+    }
+
+    #[test]
+    fn select_reports_no_match() {
+        let tasks = to_select();
+        for selector in ["4", "ffff", "1f0e", "", "next", "1f0c9a2g"] {
+            assert!(
+                matches!(select(&tasks, selector), Err(SelectError::NotFound(_))),
+                "{:?}",
+                selector
+            );
+        }
+        assert_eq!(select(&[], "1").unwrap_err().to_string(), "no task matches \"1\"");
+    }
+
+    #[test]
+    fn a_task_cannot_depend_on_itself() {
+        let tasks = tasks(1);
+        assert!(matches!(
+            check_dependency(&tasks, uuid(1), uuid(1)),
+            Err(DependencyError::OnItself)
+        ));
+    }
+
+    #[test]
+    fn direct_cycles_are_refused() {
+        let mut tasks = tasks(2);
+        tasks[0].depends_on.push(uuid(2));
+        assert!(check_dependency(&tasks, uuid(1), uuid(2)).is_ok());
+        assert!(matches!(
+            check_dependency(&tasks, uuid(2), uuid(1)),
+            Err(DependencyError::Cycle(_))
+        ));
+    }
+
+    #[test]
+    fn indirect_cycles_report_the_path() {
+        let mut tasks = tasks(4);
+        tasks[0].depends_on.push(uuid(2));
+        tasks[1].depends_on.push(uuid(3));
+        // Depending on an unrelated task, or one further down, is fine.
+        assert!(check_dependency(&tasks, uuid(3), uuid(4)).is_ok());
+        assert!(check_dependency(&tasks, uuid(1), uuid(3)).is_ok());
+
+        let error = check_dependency(&tasks, uuid(3), uuid(1)).unwrap_err();
+        let short = |n: usize| tasks[n - 1].short_id();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "that would create a cycle: 3 ({}) -> 1 ({}) -> 2 ({}) -> 3 ({})",
+                short(3),
+                short(1),
+                short(2),
+                short(3)
+            )
+        );
+    }
+
+    #[test]
+    fn tasks_become_actionable_once_their_blockers_are_done() {
+        let mut tasks = tasks(3);
+        tasks[0].depends_on = vec![uuid(2), uuid(3)];
+        assert!(!is_actionable(&tasks, &tasks[0]));
+        assert_eq!(blockers(&tasks, &tasks[0]).len(), 2);
+
+        tasks[1].completed = true;
+        assert!(!is_actionable(&tasks, &tasks[0]));
+        tasks[2].completed = true;
+        assert!(is_actionable(&tasks, &tasks[0]));
+
+        // A blocker that was archived or deleted no longer counts.
+        tasks[2].completed = false;
+        tasks.remove(2);
+        assert!(is_actionable(&tasks, &tasks[0]));
+
+        tasks[0].completed = true;
+        assert!(!is_actionable(&tasks, &tasks[0]));
     }
 }
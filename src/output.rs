@@ -0,0 +1,225 @@
+// src/output.rs
+//
+// Machine-readable renderings of a task list for `list --format`.
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use thiserror::Error;
+
+use crate::dates;
+use crate::models::Task;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// The serialized task schema, one array of objects
+    Json,
+    Csv,
+    Tsv,
+    /// Aligned columns with a header
+    Table,
+}
+
+#[derive(Debug, Error)]
+#[error("unknown field {0:?} (expected one of: {names})", names = Field::ALL.map(Field::name).join(", "))]
+pub struct FieldError(String);
+
+/// A column, named after the `Task` field it shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Id,
+    Uuid,
+    Description,
+    Tags,
+    DueDate,
+    Priority,
+    Completed,
+    Recurrence,
+    Parent,
+    DependsOn,
+    Created,
+    CompletedAt,
+}
+
+impl Field {
+    const ALL: [Field; 12] = [
+        Field::Id,
+        Field::Uuid,
+        Field::Description,
+        Field::Tags,
+        Field::DueDate,
+        Field::Priority,
+        Field::Completed,
+        Field::Recurrence,
+        Field::Parent,
+        Field::DependsOn,
+        Field::Created,
+        Field::CompletedAt,
+    ];
+
+    /// Columns shown when `--fields` isn't given.
+    pub const DEFAULT: [Field; 7] = [
+        Field::Id,
+        Field::Uuid,
+        Field::Description,
+        Field::Tags,
+        Field::DueDate,
+        Field::Priority,
+        Field::Completed,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Field::Id => "id",
+            Field::Uuid => "uuid",
+            Field::Description => "description",
+            Field::Tags => "tags",
+            Field::DueDate => "due_date",
+            Field::Priority => "priority",
+            Field::Completed => "completed",
+            Field::Recurrence => "recurrence",
+            Field::Parent => "parent",
+            Field::DependsOn => "depends_on",
+            Field::Created => "created",
+            Field::CompletedAt => "completed_at",
+        }
+    }
+
+    /// The value as text. Lists are comma separated, missing values empty.
+    fn text(self, task: &Task) -> String {
+        match self {
+            Field::Id => task.id.to_string(),
+            Field::Uuid => task.uuid.to_string(),
+            Field::Description => task.description.clone(),
+            Field::Tags => task.tags.join(","),
+            Field::DueDate => task.due_date.map(|d| dates::format(&d)).unwrap_or_default(),
+            Field::Priority => format!("{:?}", task.priority).to_lowercase(),
+            Field::Completed => task.completed.to_string(),
+            Field::Recurrence => task.recurrence.as_ref().map(|r| r.to_string()).unwrap_or_default(),
+            Field::Parent => task.parent.map(|p| p.to_string()).unwrap_or_default(),
+            Field::DependsOn => {
+                let uuids: Vec<String> = task.depends_on.iter().map(|u| u.to_string()).collect();
+                uuids.join(",")
+            }
+            Field::Created => task.created.map(|d| d.to_rfc3339()).unwrap_or_default(),
+            Field::CompletedAt => task.completed_at.map(|d| d.to_rfc3339()).unwrap_or_default(),
+        }
+    }
+}
+
+impl FromStr for Field {
+    type Err = FieldError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase().replace('-', "_");
+        match name.as_str() {
+            "desc" => Ok(Field::Description),
+            "due" => Ok(Field::DueDate),
+            "depends" => Ok(Field::DependsOn),
+            _ => Field::ALL
+                .into_iter()
+                .find(|f| f.name() == name)
+                .ok_or(FieldError(s.to_string())),
+        }
+    }
+}
+
+/// Renders `tasks` in `format`. `fields` picks the columns (or JSON keys);
+/// without it JSON has every field and the others `Field::DEFAULT`.
+pub fn render(tasks: &[&Task], format: Format, fields: Option<&[Field]>) -> Result<String, serde_json::Error> {
+    let columns = fields.unwrap_or(&Field::DEFAULT);
+    let rows = || tasks.iter().map(|task| columns.iter().map(|f| f.text(task)));
+    let header = || columns.iter().map(|f| f.name().to_string());
+
+    let mut out = String::new();
+    match format {
+        Format::Json => {
+            out = match fields {
+                None => serde_json::to_string_pretty(tasks)?,
+                Some(fields) => {
+                    let mut objects = Vec::new();
+                    for task in tasks {
+                        let serde_json::Value::Object(mut all) = serde_json::to_value(task)? else {
+                            unreachable!("tasks serialize to objects")
+                        };
+                        // Empty fields are left out of the schema; spell them out.
+                        let picked: Vec<(&str, serde_json::Value)> = fields
+                            .iter()
+                            .map(|f| {
+                                let value = all.remove(f.name()).unwrap_or(match f {
+                                    Field::DependsOn => serde_json::Value::Array(Vec::new()),
+                                    _ => serde_json::Value::Null,
+                                });
+                                (f.name(), value)
+                            })
+                            .collect();
+                        objects.push(Picked(picked));
+                    }
+                    serde_json::to_string_pretty(&objects)?
+                }
+            };
+            out.push('\n');
+        }
+        Format::Csv | Format::Tsv => {
+            let (separator, escape): (&str, fn(String) -> String) = match format {
+                Format::Csv => (",", csv_escape),
+                _ => ("\t", tsv_escape),
+            };
+            for row in std::iter::once(header().collect::<Vec<_>>()).chain(rows().map(|r| r.collect())) {
+                let cells: Vec<String> = row.into_iter().map(escape).collect();
+                out.push_str(&cells.join(separator));
+                out.push('\n');
+            }
+        }
+        Format::Table => {
+            let header: Vec<String> = header().map(|h| h.to_uppercase()).collect();
+            let body: Vec<Vec<String>> = rows().map(|r| r.map(tsv_escape).collect()).collect();
+            let widths: Vec<usize> = (0..columns.len())
+                .map(|i| {
+                    std::iter::once(&header)
+                        .chain(&body)
+                        .map(|row| row[i].chars().count())
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect();
+            for row in std::iter::once(&header).chain(&body) {
+                let cells: Vec<String> = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{:<width$}", cell, width = *width))
+                    .collect();
+                out.push_str(cells.join("  ").trim_end());
+                out.push('\n');
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Some fields of a task, serialized in the order they were asked for.
+struct Picked<'a>(Vec<(&'a str, serde_json::Value)>);
+
+impl Serialize for Picked<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in &self.0 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Quotes a cell when it contains a separator, quote or line break (RFC 4180).
+fn csv_escape(cell: String) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell
+    }
+}
+
+/// TSV has no quoting, so tabs and line breaks become spaces.
+fn tsv_escape(cell: String) -> String {
+    cell.replace(['\t', '\n', '\r'], " ")
+}
@@ -0,0 +1,592 @@
+// src/query.rs
+//
+// Filter expressions for `todo list` and the TUI '/' prompt, e.g.
+//
+//     tag:work and (priority>=medium or due<+3d) and not done
+//
+// Terms next to each other are joined with `and`. A bare word or a "quoted
+// phrase" searches the description.
+use std::fmt;
+
+use chrono::{DateTime, Days, Duration, Local, NaiveDateTime, NaiveTime, Utc};
+
+use crate::dates;
+use crate::models::{self, Priority, Task};
+
+/// A filter that failed to parse, pointing at where it went wrong.
+#[derive(Debug)]
+pub struct QueryError {
+    query: String,
+    /// Position of the problem, in characters.
+    position: usize,
+    message: String,
+}
+
+impl QueryError {
+    /// The message and column on one line, for the TUI status line.
+    pub fn summary(&self) -> String {
+        format!("{} at column {}", self.message, self.position + 1)
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "invalid filter: {}", self.summary())?;
+        writeln!(f, "  {}", self.query)?;
+        write!(f, "  {}^", " ".repeat(self.position))
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    /// `:` or `=`
+    Is,
+    IsNot,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Op {
+    /// Longest first, so `>=` isn't read as `>`.
+    const ALL: [(&'static str, Op); 7] = [
+        (">=", Op::GreaterOrEqual),
+        ("<=", Op::LessOrEqual),
+        ("!=", Op::IsNot),
+        (">", Op::Greater),
+        ("<", Op::Less),
+        (":", Op::Is),
+        ("=", Op::Is),
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateField {
+    Due,
+    Created,
+    Completed,
+}
+
+/// A span of time `[start, end)`: a whole day for a plain date, a single
+/// minute for a date with a time.
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum DateValue {
+    None,
+    Any,
+    Span(Span),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Done,
+    Pending,
+    Overdue,
+    Blocked,
+    Actionable,
+    Recurring,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Text(String),
+    Tag(Op, String),
+    Priority(Op, Priority),
+    Date(DateField, Op, DateValue),
+    State(State),
+}
+
+/// A parsed filter. The empty filter matches everything.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    expr: Option<Expr>,
+}
+
+impl Query {
+    /// Parses `input`, resolving relative dates like `+3d` against `now`.
+    pub fn parse(input: &str, now: NaiveDateTime) -> Result<Query, QueryError> {
+        let tokens = tokenize(input).map_err(|(position, message)| QueryError {
+            query: input.to_string(),
+            position,
+            message,
+        })?;
+        let mut parser = Parser {
+            tokens,
+            next: 0,
+            now,
+            end: input.chars().count(),
+        };
+        let result = if parser.tokens.is_empty() {
+            Ok(None)
+        } else {
+            parser.or().and_then(|expr| match parser.tokens.get(parser.next) {
+                None => Ok(Some(expr)),
+                Some(token) if token.text == ")" => Err((token.position, "unmatched ')'".into())),
+                Some(token) => Err((token.position, format!("unexpected {:?}", token.text))),
+            })
+        };
+        result
+            .map(|expr| Query { expr })
+            .map_err(|(position, message)| QueryError {
+                query: input.to_string(),
+                position,
+                message,
+            })
+    }
+
+    /// Whether `task` passes the filter. `tasks` is the list it is in, used
+    /// to tell whether it is blocked.
+    pub fn matches(&self, task: &Task, tasks: &[Task]) -> bool {
+        match &self.expr {
+            Some(expr) => expr.matches(task, tasks),
+            None => true,
+        }
+    }
+}
+
+impl Expr {
+    fn matches(&self, task: &Task, tasks: &[Task]) -> bool {
+        match self {
+            Expr::And(a, b) => a.matches(task, tasks) && b.matches(task, tasks),
+            Expr::Or(a, b) => a.matches(task, tasks) || b.matches(task, tasks),
+            Expr::Not(a) => !a.matches(task, tasks),
+            Expr::Text(text) => task.description.to_lowercase().contains(text),
+            Expr::Tag(op, tag) => {
+                let has = task.tags.iter().any(|t| t.eq_ignore_ascii_case(tag));
+                if *op == Op::IsNot {
+                    !has
+                } else {
+                    has
+                }
+            }
+            Expr::Priority(op, priority) => compare(*op, task.priority.rank(), priority.rank()),
+            Expr::Date(field, op, value) => {
+                let date = match field {
+                    // Due dates are floating wall clock times.
+                    DateField::Due => task.due_date.map(|d| d.naive_utc()),
+                    DateField::Created => task.created.map(local),
+                    DateField::Completed => task.completed_at.map(local),
+                };
+                match (value, date) {
+                    (DateValue::None, date) => date.is_none() == (*op != Op::IsNot),
+                    (DateValue::Any, date) => date.is_some() == (*op != Op::IsNot),
+                    (DateValue::Span(_), None) => false,
+                    (DateValue::Span(span), Some(date)) => match op {
+                        Op::Is => span.start <= date && date < span.end,
+                        Op::IsNot => date < span.start || span.end <= date,
+                        Op::Less => date < span.start,
+                        Op::LessOrEqual => date < span.end,
+                        Op::Greater => date >= span.end,
+                        Op::GreaterOrEqual => date >= span.start,
+                    },
+                }
+            }
+            Expr::State(state) => match state {
+                State::Done => task.completed,
+                State::Pending => !task.completed,
+                State::Overdue => !task.completed && task.due_date.is_some_and(|d| dates::is_overdue(&d)),
+                State::Blocked => !task.completed && models::is_blocked(tasks, task),
+                State::Actionable => models::is_actionable(tasks, task),
+                State::Recurring => task.recurrence.is_some(),
+            },
+        }
+    }
+}
+
+fn local(date: DateTime<Utc>) -> NaiveDateTime {
+    date.with_timezone(&Local).naive_local()
+}
+
+fn compare(op: Op, value: u8, wanted: u8) -> bool {
+    match op {
+        Op::Is => value == wanted,
+        Op::IsNot => value != wanted,
+        Op::Less => value < wanted,
+        Op::LessOrEqual => value <= wanted,
+        Op::Greater => value > wanted,
+        Op::GreaterOrEqual => value >= wanted,
+    }
+}
+
+#[derive(Debug)]
+struct Token {
+    /// With any quotes still in place.
+    text: String,
+    position: usize,
+}
+
+/// Splits on whitespace and parentheses. Double quotes group a phrase
+/// (`"call mom"`, `due<"fri 5pm"`).
+fn tokenize(input: &str) -> Result<Vec<Token>, (usize, String)> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().enumerate().peekable();
+    while let Some(&(position, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == '(' || c == ')' {
+            chars.next();
+            tokens.push(Token {
+                text: c.to_string(),
+                position,
+            });
+            continue;
+        }
+
+        let mut text = String::new();
+        let mut quote_start = None;
+        while let Some(&(i, c)) = chars.peek() {
+            if quote_start.is_none() && (c.is_whitespace() || c == '(' || c == ')') {
+                break;
+            }
+            if c == '"' {
+                quote_start = match quote_start {
+                    Some(_) => None,
+                    None => Some(i),
+                };
+            }
+            text.push(c);
+            chars.next();
+        }
+        if let Some(start) = quote_start {
+            return Err((start, "unterminated quote".into()));
+        }
+        tokens.push(Token { text, position });
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+    now: NaiveDateTime,
+    /// Length of the input, for errors at the very end.
+    end: usize,
+}
+
+type ParseResult = Result<Expr, (usize, String)>;
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.next).map(|t| t.text.as_str())
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        self.peek().is_some_and(|t| t.eq_ignore_ascii_case(keyword))
+    }
+
+    fn or(&mut self) -> ParseResult {
+        let mut expr = self.and()?;
+        while self.peek_keyword("or") {
+            self.next += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> ParseResult {
+        let mut expr = self.not()?;
+        loop {
+            if self.peek_keyword("and") {
+                self.next += 1;
+            } else if self.peek().is_none() || self.peek_keyword("or") || self.peek() == Some(")") {
+                return Ok(expr);
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+    }
+
+    fn not(&mut self) -> ParseResult {
+        if self.peek_keyword("not") {
+            self.next += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> ParseResult {
+        let Some(token) = self.tokens.get(self.next) else {
+            return Err((self.end, "expected a filter term".into()));
+        };
+        let position = token.position;
+        match token.text.as_str() {
+            "(" => {
+                self.next += 1;
+                let expr = self.or()?;
+                if self.peek() != Some(")") {
+                    return Err((position, "'(' is never closed".into()));
+                }
+                self.next += 1;
+                Ok(expr)
+            }
+            ")" => Err((position, "expected a filter term before ')'".into())),
+            keyword if ["and", "or"].contains(&keyword.to_lowercase().as_str()) => {
+                Err((position, format!("expected a filter term before {:?}", keyword)))
+            }
+            _ => {
+                self.next += 1;
+                let text = self.tokens[self.next - 1].text.clone();
+                self.term(&text, position)
+            }
+        }
+    }
+
+    fn term(&self, text: &str, position: usize) -> ParseResult {
+        // A quoted phrase is always a text search.
+        if text.starts_with('"') {
+            return Ok(Expr::Text(unquote(text).to_lowercase()));
+        }
+
+        let split = text.char_indices().find_map(|(i, _)| {
+            Op::ALL
+                .iter()
+                .find(|(s, _)| text[i..].starts_with(s))
+                .map(|(s, op)| (i, *s, *op))
+        });
+        let Some((at, symbol, op)) = split else {
+            return Ok(match text.to_lowercase().as_str() {
+                "done" | "completed" => Expr::State(State::Done),
+                "pending" => Expr::State(State::Pending),
+                "overdue" => Expr::State(State::Overdue),
+                "blocked" => Expr::State(State::Blocked),
+                "actionable" => Expr::State(State::Actionable),
+                "recurring" => Expr::State(State::Recurring),
+                word => Expr::Text(word.to_string()),
+            });
+        };
+
+        let field = text[..at].to_lowercase();
+        let value = unquote(&text[at + symbol.len()..]);
+        let value_position = position + text[..at + symbol.len()].chars().count();
+        if value.is_empty() {
+            return Err((value_position, format!("expected a value after {}{}", field, symbol)));
+        }
+        let ordered = !matches!(op, Op::Is | Op::IsNot);
+
+        match field.as_str() {
+            "tag" | "tags" if !ordered => Ok(Expr::Tag(op, value.to_string())),
+            "priority" | "pri" => value
+                .parse::<Priority>()
+                .map(|p| Expr::Priority(op, p))
+                .map_err(|e| (value_position, e.to_string())),
+            "due" => self.date(DateField::Due, op, value, value_position),
+            "created" | "entry" => self.date(DateField::Created, op, value, value_position),
+            "completed" | "end" => self.date(DateField::Completed, op, value, value_position),
+            "description" | "desc" if !ordered => match op {
+                Op::IsNot => Ok(Expr::Not(Box::new(Expr::Text(value.to_lowercase())))),
+                _ => Ok(Expr::Text(value.to_lowercase())),
+            },
+            "tag" | "tags" | "description" | "desc" => {
+                Err((position + at, format!("{} can only be compared with : or !=", field)))
+            }
+            "" => Err((position, format!("expected a field name before {}", symbol))),
+            _ => Err((
+                position,
+                format!(
+                    "unknown field {:?} (expected tag, priority, due, created, completed or description)",
+                    field
+                ),
+            )),
+        }
+    }
+
+    /// `none`, `any`, a date in any format `add` accepts, or a range
+    /// `from..to` (only with `:`).
+    fn date(&self, field: DateField, op: Op, value: &str, position: usize) -> ParseResult {
+        let value = match value.to_lowercase().as_str() {
+            "none" if matches!(op, Op::Is | Op::IsNot) => DateValue::None,
+            "any" if matches!(op, Op::Is | Op::IsNot) => DateValue::Any,
+            _ => match value.split_once("..") {
+                Some((from, to)) => {
+                    if op != Op::Is {
+                        return Err((position, "a date range can only be used with :".into()));
+                    }
+                    let to_position = position + from.chars().count() + 2;
+                    let start = self.span(from, position)?.start;
+                    let end = self.span(to, to_position)?.end;
+                    DateValue::Span(Span { start, end })
+                }
+                None => DateValue::Span(self.span(value, position)?),
+            },
+        };
+        Ok(Expr::Date(field, op, value))
+    }
+
+    fn span(&self, value: &str, position: usize) -> Result<Span, (usize, String)> {
+        let start = dates::parse_at(value, self.now).map_err(|e| (position, e.to_string()))?;
+        let end = if start.time() == NaiveTime::MIN {
+            start
+                .date()
+                .checked_add_days(Days::new(1))
+                .map(|d| d.and_time(NaiveTime::MIN))
+        } else {
+            start.checked_add_signed(Duration::minutes(1))
+        };
+        let end = end.ok_or_else(|| (position, "date out of range".to_string()))?;
+        Ok(Span { start, end })
+    }
+}
+
+fn unquote(text: &str) -> &str {
+    let text = text.strip_prefix('"').unwrap_or(text);
+    text.strip_suffix('"').unwrap_or(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use uuid::Uuid;
+
+    use super::*;
+
+    /// Wednesday 2100-10-13, 10:30. `overdue` goes by the clock, so the
+    /// tasks are due long after today, but for one long before.
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2100, 10, 13)
+            .unwrap()
+            .and_hms_opt(10, 30, 0)
+            .unwrap()
+    }
+
+    fn tasks() -> Vec<Task> {
+        let due = |y, m, d, h| {
+            Some(
+                NaiveDate::from_ymd_opt(y, m, d)
+                    .unwrap()
+                    .and_hms_opt(h, 0, 0)
+                    .unwrap()
+                    .and_utc(),
+            )
+        };
+        let task = |id, description: &str, tags: &[&str], priority, due_date| Task {
+            uuid: Uuid::new_v4(),
+            id,
+            description: description.into(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            priority,
+            due_date,
+            ..Task::default()
+        };
+        let mut tasks = vec![
+            task(1, "Call mom", &["home"], Priority::High, due(2100, 10, 13, 0)),
+            task(
+                2,
+                "Write the report",
+                &["work"],
+                Priority::Medium,
+                due(2100, 10, 15, 17),
+            ),
+            task(3, "Review the budget", &["Work", "money"], Priority::Low, None),
+            task(4, "Renew the passport", &[], Priority::High, due(2000, 1, 1, 0)),
+            task(5, "Pay the rent", &[], Priority::Medium, None),
+        ];
+        tasks[1].depends_on = vec![tasks[2].uuid];
+        tasks[4].completed = true;
+        tasks
+    }
+
+    fn ids(query: &str) -> Vec<u32> {
+        let query = Query::parse(query, now()).unwrap_or_else(|e| panic!("{}", e));
+        let tasks = tasks();
+        tasks
+            .iter()
+            .filter(|t| query.matches(t, &tasks))
+            .map(|t| t.id)
+            .collect()
+    }
+
+    fn error(query: &str) -> (usize, String) {
+        let error = Query::parse(query, now()).expect_err("an invalid filter");
+        (error.position, error.message)
+    }
+
+    #[test]
+    fn terms() {
+        assert_eq!(ids(""), [1, 2, 3, 4, 5]);
+        assert_eq!(ids("the"), [2, 3, 4, 5]);
+        assert_eq!(ids("\"call MOM\""), [1]);
+        assert_eq!(ids("desc!=the"), [1]);
+        assert_eq!(ids("tag:work"), [2, 3]);
+        assert_eq!(ids("tag!=work"), [1, 4, 5]);
+        assert_eq!(ids("priority>=medium"), [1, 2, 4, 5]);
+        assert_eq!(ids("pri=low"), [3]);
+        assert_eq!(ids("done"), [5]);
+        assert_eq!(ids("pending"), [1, 2, 3, 4]);
+        assert_eq!(ids("overdue"), [4]);
+        assert_eq!(ids("blocked"), [2]);
+        assert_eq!(ids("actionable"), [1, 3, 4]);
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(ids("due:today"), [1]);
+        assert_eq!(ids("due:fri"), [2]);
+        assert_eq!(ids("due:\"fri 5pm\""), [2]);
+        assert_eq!(ids("due<\"fri 5pm\""), [1, 4]);
+        assert_eq!(ids("due<=fri"), [1, 2, 4]);
+        assert_eq!(ids("due>today"), [2]);
+        assert_eq!(ids("due:none"), [3, 5]);
+        assert_eq!(ids("due!=none"), [1, 2, 4]);
+        assert_eq!(ids("due:today..eow"), [1, 2]);
+        assert_eq!(ids("due<+3d"), [1, 2, 4]);
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(ids("tag:work pri:low"), [3]);
+        assert_eq!(ids("tag:work and pri:low"), [3]);
+        assert_eq!(ids("tag:home or tag:work and pri:low"), [1, 3]);
+        assert_eq!(ids("(tag:home or tag:work) and pri:high"), [1]);
+        assert_eq!(ids("not done and not tag:work"), [1, 4]);
+        assert_eq!(ids("NOT (due:none OR overdue)"), [1, 2]);
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        assert_eq!(error("tag:work and"), (12, "expected a filter term".into()));
+        assert_eq!(error("(tag:work or done"), (0, "'(' is never closed".into()));
+        assert_eq!(error("done)"), (4, "unmatched ')'".into()));
+        assert_eq!(
+            error("done or or pending"),
+            (8, "expected a filter term before \"or\"".into())
+        );
+        assert_eq!(error("\"call mom"), (0, "unterminated quote".into()));
+        assert_eq!(error("due:"), (4, "expected a value after due:".into()));
+        assert_eq!(error("tag<work"), (3, "tag can only be compared with : or !=".into()));
+        assert_eq!(error("due>tomorow").0, 4);
+        assert_eq!(error("due:fri..never").0, 9);
+        assert_eq!(
+            error("due<today..fri"),
+            (4, "a date range can only be used with :".into())
+        );
+        assert_eq!(error(":work"), (0, "expected a field name before :".into()));
+        // Columns count characters, not bytes.
+        assert_eq!(error("\"café\" colour:red").0, 7);
+        assert!(error("colour:red").1.starts_with("unknown field \"colour\""));
+    }
+
+    #[test]
+    fn error_message_has_a_caret() {
+        let error = Query::parse("tag:work and pri>urgent", now()).unwrap_err();
+        assert_eq!(
+            error.summary(),
+            "invalid priority \"urgent\" (expected low, medium or high) at column 18"
+        );
+        assert_eq!(
+            error.to_string(),
+            "invalid filter: invalid priority \"urgent\" (expected low, medium or high) at column 18\n  tag:work and pri>urgent\n                   ^"
+        );
+    }
+}
@@ -0,0 +1,430 @@
+// src/recurrence.rs
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("invalid recurrence {input:?}: {reason}")]
+pub struct RecurrenceError {
+    input: String,
+    reason: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// How a task repeats. This is the subset of an iCalendar RRULE that makes
+/// sense for chores: FREQ, INTERVAL, BYDAY (weekly only) and BYMONTHDAY
+/// (monthly only). It is stored as the RRULE text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    pub interval: u32,
+    pub weekdays: Vec<Weekday>,
+    pub month_day: Option<u32>,
+}
+
+impl Recurrence {
+    fn new(frequency: Frequency) -> Self {
+        Recurrence {
+            frequency,
+            interval: 1,
+            weekdays: Vec::new(),
+            month_day: None,
+        }
+    }
+
+    /// The first occurrence strictly after `after`.
+    pub fn next_after(&self, after: NaiveDate) -> Option<NaiveDate> {
+        let interval = self.interval.max(1);
+        match self.frequency {
+            Frequency::Daily => after.checked_add_days(Days::new(interval as u64)),
+            Frequency::Weekly if self.weekdays.is_empty() => after.checked_add_days(Days::new(7 * interval as u64)),
+            Frequency::Weekly => {
+                // Weeks start on Monday, and the week of `after` is one of
+                // the weeks the rule is on.
+                let weekday = after.weekday().num_days_from_monday();
+                let days = self.weekdays.iter().map(|d| d.num_days_from_monday());
+                match days.clone().filter(|d| *d > weekday).min() {
+                    Some(day) => after.checked_add_days(Days::new((day - weekday) as u64)),
+                    None => {
+                        let first = days.min()?;
+                        let monday = after.checked_sub_days(Days::new(weekday as u64))?;
+                        monday.checked_add_days(Days::new(7 * interval as u64 + first as u64))
+                    }
+                }
+            }
+            Frequency::Monthly => match self.month_day {
+                None => after.checked_add_months(Months::new(self.step_months()?)),
+                Some(day) => {
+                    let first = after.with_day(1)?;
+                    (0..=2u32)
+                        .filter_map(|n| first.checked_add_months(Months::new(n.checked_mul(interval)?)))
+                        .map(|month| clamp_day(month, day))
+                        .find(|d| *d > after)
+                }
+            },
+            Frequency::Yearly => after.checked_add_months(Months::new(self.step_months()?)),
+        }
+    }
+
+    /// The next due date after a task due on `due` is done. Occurrences that
+    /// are already in the past are skipped so that catching up on an old
+    /// chore doesn't leave a backlog of copies.
+    pub fn next_due(&self, due: NaiveDate, today: NaiveDate) -> Option<NaiveDate> {
+        let mut next = self.next_after(due)?;
+        let mut count: u32 = 1;
+        while next < today {
+            next = match self.step_months() {
+                // Counted from `due`, so that the 31st isn't the 28th for
+                // good after a February.
+                Some(months) => {
+                    count += 1;
+                    due.checked_add_months(Months::new(months.checked_mul(count)?))?
+                }
+                None => self.next_after(next)?,
+            };
+        }
+        Some(next)
+    }
+
+    /// The rule for the task after one due on `due` and next due on `next`:
+    /// a plain monthly rule that had to fall back to the end of a shorter
+    /// month keeps the day of `due`, so that it comes back to it afterwards.
+    pub fn anchored(&self, due: NaiveDate, next: NaiveDate) -> Recurrence {
+        match self.frequency {
+            Frequency::Monthly if self.month_day.is_none() && next.day() < due.day() => Recurrence {
+                month_day: Some(due.day()),
+                ..self.clone()
+            },
+            _ => self.clone(),
+        }
+    }
+
+    /// How many months apart occurrences are, for rules that repeat on the
+    /// same day of the month.
+    fn step_months(&self) -> Option<u32> {
+        match self.frequency {
+            Frequency::Monthly if self.month_day.is_none() => Some(self.interval.max(1)),
+            Frequency::Yearly => self.interval.max(1).checked_mul(12),
+            _ => None,
+        }
+    }
+
+    /// Human readable form, e.g. "every 2 weeks on Mon, Thu".
+    pub fn describe(&self) -> String {
+        let unit = match self.frequency {
+            Frequency::Daily => "day",
+            Frequency::Weekly => "week",
+            Frequency::Monthly => "month",
+            Frequency::Yearly => "year",
+        };
+        let mut text = match self.interval {
+            1 => format!("every {}", unit),
+            n => format!("every {} {}s", n, unit),
+        };
+        if !self.weekdays.is_empty() {
+            let days: Vec<String> = self.weekdays.iter().map(|d| d.to_string()).collect();
+            text.push_str(&format!(" on {}", days.join(", ")));
+        }
+        if let Some(day) = self.month_day {
+            text.push_str(&format!(" on day {}", day));
+        }
+        text
+    }
+
+    fn parse_rrule(rule: &str) -> Result<Recurrence, String> {
+        let mut frequency = None;
+        let mut recurrence = Recurrence::new(Frequency::Daily);
+        for part in rule.split(';').filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected KEY=VALUE, found {:?}", part))?;
+            match key {
+                "FREQ" => {
+                    frequency = Some(match value {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(format!("unsupported FREQ {}", value)),
+                    })
+                }
+                "INTERVAL" => recurrence.interval = parse_interval(value)?,
+                "BYDAY" => recurrence.weekdays = parse_weekdays(value)?,
+                "BYMONTHDAY" => recurrence.month_day = Some(parse_month_day(value)?),
+                "WKST" => {}
+                _ => return Err(format!("unsupported RRULE part {}", key)),
+            }
+        }
+        recurrence.frequency = frequency.ok_or("missing FREQ")?;
+        recurrence.check()?;
+        Ok(recurrence)
+    }
+
+    /// Parses the short forms: `daily`, `weekly`, `weekdays`,
+    /// `weekly on mon,thu`, `monthly on 15`, `every 3 days`, `every 2 weeks`.
+    fn parse_short(text: &str) -> Result<Recurrence, String> {
+        let text = text.to_lowercase().replace(':', " on ");
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (base, on) = match words.iter().position(|w| *w == "on") {
+            Some(i) => (&words[..i], Some(words[i + 1..].join(""))),
+            None => (&words[..], None),
+        };
+
+        let mut recurrence = match base {
+            ["daily"] => Recurrence::new(Frequency::Daily),
+            ["weekly"] => Recurrence::new(Frequency::Weekly),
+            ["monthly"] => Recurrence::new(Frequency::Monthly),
+            ["yearly"] | ["annually"] => Recurrence::new(Frequency::Yearly),
+            ["weekdays"] => Recurrence {
+                weekdays: vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+                ..Recurrence::new(Frequency::Weekly)
+            },
+            ["every", unit] | ["every", "1", unit] => Recurrence::new(parse_unit(unit)?),
+            ["every", n, unit] => Recurrence {
+                interval: parse_interval(n)?,
+                ..Recurrence::new(parse_unit(unit)?)
+            },
+            _ => {
+                return Err(
+                    "expected daily, weekly, monthly, yearly, weekdays, every N days/weeks/months or an RRULE".into(),
+                )
+            }
+        };
+
+        if let Some(on) = on {
+            match recurrence.frequency {
+                Frequency::Weekly => recurrence.weekdays = parse_weekdays(&on)?,
+                Frequency::Monthly => recurrence.month_day = Some(parse_month_day(&on)?),
+                _ => return Err("\"on\" only works with weekly (weekdays) or monthly (a day of the month)".into()),
+            }
+        }
+        recurrence.check()?;
+        Ok(recurrence)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if !self.weekdays.is_empty() && self.frequency != Frequency::Weekly {
+            return Err("weekdays can only be given for weekly recurrences".into());
+        }
+        if self.month_day.is_some() && self.frequency != Frequency::Monthly {
+            return Err("a day of the month can only be given for monthly recurrences".into());
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = RecurrenceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let upper = trimmed.to_uppercase();
+        let result = if upper.starts_with("FREQ=") || upper.starts_with("RRULE:") {
+            Recurrence::parse_rrule(upper.trim_start_matches("RRULE:"))
+        } else {
+            Recurrence::parse_short(trimmed)
+        };
+        result.map_err(|reason| RecurrenceError {
+            input: s.to_string(),
+            reason,
+        })
+    }
+}
+
+/// Formats as an RRULE value, e.g. `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH`.
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        };
+        write!(f, "FREQ={}", frequency)?;
+        if self.interval > 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.weekdays.is_empty() {
+            let days: Vec<&str> = self.weekdays.iter().map(|d| rrule_day(*d)).collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if let Some(day) = self.month_day {
+            write!(f, ";BYMONTHDAY={}", day)?;
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for Recurrence {
+    type Error = RecurrenceError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Recurrence> for String {
+    fn from(value: Recurrence) -> Self {
+        value.to_string()
+    }
+}
+
+fn parse_unit(unit: &str) -> Result<Frequency, String> {
+    match unit.trim_end_matches('s') {
+        "day" | "d" => Ok(Frequency::Daily),
+        "week" | "w" => Ok(Frequency::Weekly),
+        "month" | "m" => Ok(Frequency::Monthly),
+        "year" | "y" => Ok(Frequency::Yearly),
+        _ => Err(format!("unknown unit {:?}", unit)),
+    }
+}
+
+/// The longest interval a rule can have, which keeps month arithmetic
+/// well away from overflowing.
+const MAX_INTERVAL: u32 = 1000;
+
+fn parse_interval(text: &str) -> Result<u32, String> {
+    match text.parse::<u32>() {
+        Ok(n @ 1..=MAX_INTERVAL) => Ok(n),
+        _ => Err(format!("invalid interval {:?}, expected 1 to {}", text, MAX_INTERVAL)),
+    }
+}
+
+fn parse_month_day(text: &str) -> Result<u32, String> {
+    match text.parse::<u32>() {
+        Ok(n @ 1..=31) => Ok(n),
+        _ => Err(format!("invalid day of the month {:?}", text)),
+    }
+}
+
+fn parse_weekdays(text: &str) -> Result<Vec<Weekday>, String> {
+    let mut days = Vec::new();
+    for day in text.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+        let weekday = match day.to_lowercase().as_str() {
+            "mo" | "mon" | "monday" => Weekday::Mon,
+            "tu" | "tue" | "tuesday" => Weekday::Tue,
+            "we" | "wed" | "wednesday" => Weekday::Wed,
+            "th" | "thu" | "thursday" => Weekday::Thu,
+            "fr" | "fri" | "friday" => Weekday::Fri,
+            "sa" | "sat" | "saturday" => Weekday::Sat,
+            "su" | "sun" | "sunday" => Weekday::Sun,
+            _ => return Err(format!("unknown weekday {:?}", day)),
+        };
+        if !days.contains(&weekday) {
+            days.push(weekday);
+        }
+    }
+    if days.is_empty() {
+        return Err("expected at least one weekday".into());
+    }
+    days.sort_by_key(|d| d.num_days_from_monday());
+    Ok(days)
+}
+
+fn rrule_day(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+/// `day` in the month of `month`, or its last day for short months.
+fn clamp_day(month: NaiveDate, day: u32) -> NaiveDate {
+    (day.min(31)..=day)
+        .chain((28..day.min(31)).rev())
+        .find_map(|d| month.with_day(d))
+        .unwrap_or(month)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn rule(text: &str) -> Recurrence {
+        text.parse().expect("valid rule")
+    }
+
+    #[test]
+    fn intervals_are_capped() {
+        assert!("FREQ=MONTHLY;INTERVAL=1000".parse::<Recurrence>().is_ok());
+        assert!("FREQ=MONTHLY;INTERVAL=1001".parse::<Recurrence>().is_err());
+        assert!("FREQ=YEARLY;INTERVAL=4294967295".parse::<Recurrence>().is_err());
+        assert!("every 0 days".parse::<Recurrence>().is_err());
+
+        let huge = Recurrence {
+            interval: u32::MAX,
+            ..rule("yearly")
+        };
+        assert_eq!(huge.next_after(date(2026, 1, 1)), None);
+        let huge = Recurrence {
+            interval: u32::MAX,
+            ..rule("monthly on 15")
+        };
+        assert_eq!(huge.next_after(date(2026, 1, 20)), None);
+    }
+
+    #[test]
+    fn weekly_on_days() {
+        let every_other = rule("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH");
+        // 2026-10-13 is a Tuesday.
+        assert_eq!(every_other.next_after(date(2026, 10, 13)), Some(date(2026, 10, 15)));
+        assert_eq!(every_other.next_after(date(2026, 10, 15)), Some(date(2026, 10, 26)));
+        assert_eq!(
+            rule("weekly on sun").next_after(date(2026, 10, 18)),
+            Some(date(2026, 10, 25))
+        );
+
+        let rare = rule("FREQ=WEEKLY;INTERVAL=1000;BYDAY=MO");
+        assert_eq!(
+            rare.next_after(date(2026, 10, 13)),
+            Some(date(2026, 10, 12) + Days::new(7000))
+        );
+    }
+
+    #[test]
+    fn monthly_keeps_the_day() {
+        let monthly = rule("monthly");
+        let next = monthly.next_due(date(2026, 1, 31), date(2026, 1, 1)).unwrap();
+        assert_eq!(next, date(2026, 2, 28));
+        let monthly = monthly.anchored(date(2026, 1, 31), next);
+        assert_eq!(monthly.next_due(next, date(2026, 1, 1)), Some(date(2026, 3, 31)));
+        assert_eq!(
+            rule("monthly").anchored(date(2026, 1, 15), date(2026, 2, 15)),
+            rule("monthly")
+        );
+
+        // Catching up skips to the 31st, not the 28th.
+        assert_eq!(
+            rule("monthly").next_due(date(2026, 1, 31), date(2026, 3, 2)),
+            Some(date(2026, 3, 31))
+        );
+        assert_eq!(
+            rule("yearly").next_due(date(2024, 2, 29), date(2027, 6, 1)),
+            Some(date(2028, 2, 29))
+        );
+        assert_eq!(
+            rule("monthly on 31").next_after(date(2026, 4, 30)),
+            Some(date(2026, 5, 31))
+        );
+    }
+}
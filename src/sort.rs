@@ -0,0 +1,271 @@
+// src/sort.rs
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::models::Task;
+
+#[derive(Debug, Error)]
+#[error("invalid sort {input:?}: {reason}")]
+pub struct SortError {
+    input: String,
+    reason: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortField {
+    /// Position in the list, i.e. the order tasks were added in.
+    Id,
+    Priority,
+    Due,
+    Created,
+    Completed,
+    Description,
+}
+
+impl SortField {
+    const ALL: [SortField; 6] = [
+        SortField::Id,
+        SortField::Priority,
+        SortField::Due,
+        SortField::Created,
+        SortField::Completed,
+        SortField::Description,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            SortField::Id => "id",
+            SortField::Priority => "priority",
+            SortField::Due => "due",
+            SortField::Created => "created",
+            SortField::Completed => "completed",
+            SortField::Description => "description",
+        }
+    }
+
+    fn parse(name: &str) -> Option<SortField> {
+        match name {
+            "pri" => Some(SortField::Priority),
+            "desc" => Some(SortField::Description),
+            _ => SortField::ALL.into_iter().find(|f| f.name() == name),
+        }
+    }
+
+    /// Compares two tasks in ascending order. `None` when either task has
+    /// no value for the field.
+    fn compare(self, a: &Task, b: &Task) -> Option<Ordering> {
+        fn both<T: Ord>(a: Option<T>, b: Option<T>) -> Option<Ordering> {
+            Some(a?.cmp(&b?))
+        }
+        match self {
+            SortField::Id => Some(a.id.cmp(&b.id)),
+            SortField::Priority => Some(a.priority.rank().cmp(&b.priority.rank())),
+            SortField::Due => both(a.due_date, b.due_date),
+            SortField::Created => both(a.created, b.created),
+            SortField::Completed => both(a.completed_at, b.completed_at),
+            SortField::Description => Some(a.description.to_lowercase().cmp(&b.description.to_lowercase())),
+        }
+    }
+
+    fn has_value(self, task: &Task) -> bool {
+        match self {
+            SortField::Due => task.due_date.is_some(),
+            SortField::Created => task.created.is_some(),
+            SortField::Completed => task.completed_at.is_some(),
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+/// A list of sort keys, each breaking ties left by the ones before it:
+/// `priority-,due+,created`. `+` (the default) is ascending, `-` descending.
+/// Tasks without a value for a key go last either way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortSpec {
+    keys: Vec<SortKey>,
+}
+
+impl Default for SortSpec {
+    /// The order tasks were added in.
+    fn default() -> Self {
+        SortSpec {
+            keys: vec![SortKey {
+                field: SortField::Id,
+                descending: false,
+            }],
+        }
+    }
+}
+
+impl SortSpec {
+    /// Sort modes the TUI cycles through when the config doesn't list any.
+    pub fn default_modes() -> Vec<SortSpec> {
+        ["id", "priority-,due+", "due+,priority-", "created-", "description"]
+            .into_iter()
+            .map(|s| s.parse().expect("built-in sort modes are valid"))
+            .collect()
+    }
+
+    pub fn compare(&self, a: &Task, b: &Task) -> Ordering {
+        for key in &self.keys {
+            let ordering = match key.field.compare(a, b) {
+                Some(ordering) if key.descending => ordering.reverse(),
+                Some(ordering) => ordering,
+                // Missing values last, whatever the direction.
+                None => key.field.has_value(b).cmp(&key.field.has_value(a)),
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+
+    /// Indices of `tasks` in sorted order. The sort is stable, so ties keep
+    /// their list order.
+    pub fn order(&self, tasks: &[Task]) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..tasks.len()).collect();
+        order.sort_by(|&a, &b| self.compare(&tasks[a], &tasks[b]));
+        order
+    }
+}
+
+impl FromStr for SortSpec {
+    type Err = SortError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |reason: String| SortError {
+            input: s.to_string(),
+            reason,
+        };
+
+        let mut keys = Vec::new();
+        for part in s.split(',').map(str::trim) {
+            let (name, descending) = if let Some(name) = part.strip_suffix('-') {
+                (name, true)
+            } else {
+                (part.strip_suffix('+').unwrap_or(part), false)
+            };
+            let field = SortField::parse(&name.to_lowercase()).ok_or_else(|| {
+                let names: Vec<&str> = SortField::ALL.iter().map(|f| f.name()).collect();
+                error(format!("unknown key {:?} (expected {})", name, names.join(", ")))
+            })?;
+            if keys.iter().any(|k: &SortKey| k.field == field) {
+                return Err(error(format!("{} is given twice", field.name())));
+            }
+            keys.push(SortKey { field, descending });
+        }
+        Ok(SortSpec { keys })
+    }
+}
+
+impl fmt::Display for SortSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys: Vec<String> = self
+            .keys
+            .iter()
+            .map(|k| format!("{}{}", k.field.name(), if k.descending { "-" } else { "+" }))
+            .collect();
+        write!(f, "{}", keys.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+    use crate::models::{renumber, Priority};
+
+    fn key(field: SortField, descending: bool) -> SortKey {
+        SortKey { field, descending }
+    }
+
+    /// Tasks 1 to 6: priority, due day and created day.
+    fn tasks() -> Vec<Task> {
+        let day = |day: Option<u32>| day.map(|day| Utc.with_ymd_and_hms(2026, 10, day, 9, 0, 0).unwrap());
+        let mut tasks: Vec<Task> = [
+            (Priority::Low, Some(20), Some(1)),
+            (Priority::High, None, Some(2)),
+            (Priority::High, Some(25), Some(3)),
+            (Priority::High, Some(15), None),
+            (Priority::Low, None, Some(5)),
+            (Priority::High, Some(15), Some(6)),
+        ]
+        .into_iter()
+        .map(|(priority, due, created)| Task {
+            priority,
+            due_date: day(due),
+            created: day(created),
+            ..Task::default()
+        })
+        .collect();
+        renumber(&mut tasks);
+        tasks
+    }
+
+    fn sorted(spec: &str) -> Vec<u32> {
+        let tasks = tasks();
+        let spec: SortSpec = spec.parse().unwrap();
+        spec.order(&tasks).into_iter().map(|i| tasks[i].id).collect()
+    }
+
+    #[test]
+    fn parses_keys_and_directions() {
+        let spec: SortSpec = "priority-,due+,created".parse().unwrap();
+        assert_eq!(
+            spec.keys,
+            [
+                key(SortField::Priority, true),
+                key(SortField::Due, false),
+                key(SortField::Created, false)
+            ]
+        );
+        assert_eq!(spec.to_string(), "priority-,due+,created+");
+
+        let spec: SortSpec = " Pri- , DESC ".parse().unwrap();
+        assert_eq!(
+            spec.keys,
+            [key(SortField::Priority, true), key(SortField::Description, false)]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_and_repeated_keys() {
+        let error = "priority-,urgency".parse::<SortSpec>().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid sort \"priority-,urgency\": unknown key \"urgency\" \
+             (expected id, priority, due, created, completed, description)"
+        );
+        assert!("".parse::<SortSpec>().is_err());
+        assert!("due*".parse::<SortSpec>().is_err());
+        let error = "due+,priority,due-".parse::<SortSpec>().unwrap_err();
+        assert!(error.to_string().ends_with("due is given twice"));
+    }
+
+    #[test]
+    fn later_keys_break_ties() {
+        // High before low; among those, earlier due dates first with the
+        // undated ones last; then by creation, the unknown one last.
+        assert_eq!(sorted("priority-,due+,created"), [6, 4, 3, 2, 1, 5]);
+        assert_eq!(sorted("priority+,due-,created-"), [1, 5, 3, 6, 4, 2]);
+    }
+
+    #[test]
+    fn missing_values_go_last_both_ways() {
+        assert_eq!(sorted("due+"), [4, 6, 1, 3, 2, 5]);
+        assert_eq!(sorted("due-"), [3, 1, 4, 6, 2, 5]);
+        assert_eq!(sorted("created-"), [6, 5, 3, 2, 1, 4]);
+        assert_eq!(sorted("created+"), [1, 2, 3, 5, 6, 4]);
+    }
+}
@@ -0,0 +1,684 @@
+// src/store.rs
+pub mod json;
+pub mod oplog;
+pub mod sqlite;
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use directories::BaseDirs;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::config::APP_DIR;
+use crate::models::Task;
+use json::JsonFile;
+use oplog::{Compacted, Log};
+use sqlite::Database;
+
+/// Environment variable that overrides the store location, like `--store`.
+pub const STORE_ENV: &str = "TODOSAGE_STORE";
+
+/// Environment variable that picks the task list, like `--list`.
+pub const LIST_ENV: &str = "TODOSAGE_LIST";
+
+/// The list kept in the store file itself. Named lists are stores of their
+/// own in `<store>.lists/`, e.g. `tasks.lists/work.json`.
+pub const DEFAULT_LIST: &str = "default";
+
+/// Where versions before the per-user store kept their tasks.
+const LEGACY_STORE: &str = "tasks.json";
+
+/// `~/.local/share/noteSage/tasks.json` on Linux, or the platform equivalent.
+pub fn default_path() -> Option<PathBuf> {
+    BaseDirs::new().map(|dirs| dirs.data_dir().join(APP_DIR).join("tasks.json"))
+}
+
+/// How the tasks are kept on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Backend {
+    /// One JSON file, rewritten on every save
+    #[default]
+    Json,
+    /// An SQLite database, written a task at a time
+    Sqlite,
+    /// An operation log per device, for stores in a synced folder
+    Oplog,
+}
+
+impl Backend {
+    /// Where this backend keeps the store whose JSON file is `path`:
+    /// `tasks.json`, `tasks.db` or `tasks.oplog/`.
+    pub fn location(self, path: &Path) -> PathBuf {
+        match self {
+            Backend::Json => path.to_path_buf(),
+            Backend::Sqlite => path.with_extension("db"),
+            Backend::Oplog => path.with_extension("oplog"),
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Backend::Json => "json",
+            Backend::Sqlite => "sqlite",
+            Backend::Oplog => "oplog",
+        })
+    }
+}
+
+/// Both lists as one sequence, each task with whether it's archived.
+fn entries<'a>(active: &'a [Task], archived: &'a [Task]) -> impl Iterator<Item = (&'a Task, bool)> {
+    active
+        .iter()
+        .map(|t| (t, false))
+        .chain(archived.iter().map(|t| (t, true)))
+}
+
+#[derive(Debug, Error)]
+pub enum StoreError {
+    #[error("could not find a home directory; set --store or {}", STORE_ENV)]
+    NoHome,
+    #[error("could not read {path}: {source}")]
+    Read { path: PathBuf, source: std::io::Error },
+    #[error("could not write {path}: {source}")]
+    Write { path: PathBuf, source: std::io::Error },
+    #[error("there is no backup {path}")]
+    MissingBackup { path: PathBuf },
+    #[error("{path} is not valid JSON: {source}")]
+    Corrupt {
+        path: PathBuf,
+        backup: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("{path}: {reason}")]
+    Malformed {
+        path: PathBuf,
+        backup: PathBuf,
+        reason: String,
+    },
+    #[error("{path}: {list} task #{index}{} is invalid: {source}", .id.map(|id| format!(" (id {})", id)).unwrap_or_default())]
+    InvalidTask {
+        path: PathBuf,
+        backup: PathBuf,
+        list: &'static str,
+        index: usize,
+        id: Option<u64>,
+        source: serde_json::Error,
+    },
+    #[error(
+        "{path} was written by a newer version of todoSage (store version {version}, this one reads up to {}); upgrade to use it",
+        json::VERSION
+    )]
+    TooNew { path: PathBuf, version: u64 },
+    #[error("{path}: task {uuid} is invalid: {source}")]
+    InvalidRecord {
+        path: PathBuf,
+        uuid: Uuid,
+        source: serde_json::Error,
+    },
+    #[error("{path}: {source}")]
+    Database { path: PathBuf, source: rusqlite::Error },
+    #[error("task {0} is not in the store")]
+    MissingTask(Uuid),
+    #[error("the {0} backend keeps no backups; only the json one does")]
+    NoBackups(Backend),
+    #[error("only the operation log can be compacted; set store.backend = \"oplog\" in the config file")]
+    NotOplog,
+    #[error("the store already uses the {0} backend")]
+    SameBackend(Backend),
+    #[error("{path} already holds tasks; move it away first")]
+    NotEmpty { path: PathBuf },
+    #[error("the task is already in list {0}")]
+    SameList(String),
+    #[error("invalid list name {0:?}: use letters, digits, '-' and '_'")]
+    InvalidList(String),
+    #[error(
+        "invalid {} {0:?}: it names a file, so it can't be empty or hold '/' or '..'",
+        oplog::DEVICE_ENV
+    )]
+    InvalidDevice(String),
+    #[error("could not read the lists in {path}: {source}")]
+    Lists { path: PathBuf, source: std::io::Error },
+}
+
+impl StoreError {
+    /// Where the unreadable store was copied to, if it was.
+    pub fn backup(&self) -> Option<&Path> {
+        match self {
+            StoreError::Corrupt { backup, .. }
+            | StoreError::Malformed { backup, .. }
+            | StoreError::InvalidTask { backup, .. } => Some(backup),
+            _ => None,
+        }
+    }
+}
+
+/// Where the tasks are kept. The app works on both lists in memory, and
+/// [`Store::save`] hands a backend only what changed since they were
+/// loaded, as inserts, updates, deletes and moves between the lists,
+/// followed by a commit.
+pub trait TaskStore {
+    /// Both lists, each in the order of its ids.
+    fn load(&mut self) -> Result<(Vec<Task>, Vec<Task>), StoreError>;
+    fn insert(&mut self, task: &Task, archived: bool) -> Result<(), StoreError>;
+    /// Replaces the task with the same uuid, in whichever list it is.
+    fn update(&mut self, task: &Task) -> Result<(), StoreError>;
+    fn delete(&mut self, uuid: Uuid) -> Result<(), StoreError>;
+    /// Moves a task to the archive, or with `archived` false back out of it.
+    fn archive(&mut self, uuid: Uuid, archived: bool) -> Result<(), StoreError>;
+    /// The tasks of one list that match `filter`, in the order of their ids.
+    fn query(&mut self, filter: &Filter) -> Result<Vec<Task>, StoreError>;
+    /// Makes the changes since the last commit durable, all of them or none.
+    fn commit(&mut self) -> Result<(), StoreError>;
+
+    fn compact(&mut self) -> Result<Compacted, StoreError> {
+        Err(StoreError::NotOplog)
+    }
+}
+
+/// What [`TaskStore::query`] asks for.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// The archive rather than the active list.
+    pub archived: bool,
+    /// Only tasks with all of these tags.
+    pub tags: Vec<String>,
+    pub completed: Option<bool>,
+    /// Only tasks due before this.
+    pub due_before: Option<DateTime<Utc>>,
+}
+
+impl Filter {
+    pub fn matches(&self, task: &Task) -> bool {
+        self.tags.iter().all(|tag| task.tags.contains(tag))
+            && self.completed.is_none_or(|completed| task.completed == completed)
+            && self
+                .due_before
+                .is_none_or(|before| task.due_date.is_some_and(|due| due < before))
+    }
+}
+
+/// List names become file names, so they're kept to characters that are
+/// safe in one everywhere.
+fn valid_list_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Opens the `backend` store for `path`. The file at `path` is the JSON
+/// store; the others live next to it, see [`Backend::location`].
+pub fn open_backend(path: &Path, backend: Backend, backups: usize) -> Result<Box<dyn TaskStore>, StoreError> {
+    let location = backend.location(path);
+    Ok(match backend {
+        Backend::Json => Box::new(JsonFile::new(&location, backups)),
+        Backend::Sqlite => Box::new(Database::open(&location)?),
+        Backend::Oplog => Box::new(Log::open(location)?),
+    })
+}
+
+pub struct Store {
+    /// The default list's store, which named lists are kept next to.
+    base: PathBuf,
+    /// This list's store, the same as `base` for the default list.
+    path: PathBuf,
+    list: String,
+    backend: Backend,
+    backups: usize,
+    tasks: RefCell<Box<dyn TaskStore>>,
+    /// Every task as last loaded or saved, and whether it's archived.
+    loaded: RefCell<HashMap<Uuid, (Task, bool)>>,
+}
+
+impl Store {
+    /// Opens the store at `path`, or at the per-user default when no
+    /// `--store`/`TODOSAGE_STORE` override was given. The JSON backend keeps
+    /// the previous `backups` versions of the file on each save.
+    pub fn open(path: Option<PathBuf>, backend: Backend, backups: usize) -> Result<Store, StoreError> {
+        let path = match path {
+            Some(path) => path,
+            None => default_path().ok_or(StoreError::NoHome)?,
+        };
+        Store::open_list(path.clone(), path, DEFAULT_LIST.into(), backend, backups)
+    }
+
+    fn open_list(
+        base: PathBuf,
+        path: PathBuf,
+        list: String,
+        backend: Backend,
+        backups: usize,
+    ) -> Result<Store, StoreError> {
+        Ok(Store {
+            tasks: RefCell::new(open_backend(&path, backend, backups)?),
+            base,
+            path,
+            list,
+            backend,
+            backups,
+            loaded: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// The store of the list called `name`, next to this one. A list that
+    /// doesn't exist yet is empty and is created on the first save.
+    pub fn list(&self, name: &str) -> Result<Store, StoreError> {
+        if name == DEFAULT_LIST {
+            return Store::open(Some(self.base.clone()), self.backend, self.backups);
+        }
+        if !valid_list_name(name) {
+            return Err(StoreError::InvalidList(name.into()));
+        }
+        let path = self.lists_dir().join(format!("{}.json", name));
+        Store::open_list(self.base.clone(), path, name.into(), self.backend, self.backups)
+    }
+
+    /// This store's list name.
+    pub fn name(&self) -> &str {
+        &self.list
+    }
+
+    fn lists_dir(&self) -> PathBuf {
+        self.base.with_extension("lists")
+    }
+
+    /// The names of the lists the backend has stores for, the default list
+    /// first and the others in order.
+    pub fn lists(&self) -> Result<Vec<String>, StoreError> {
+        let dir = self.lists_dir();
+        let extension = self.backend.location(Path::new("x.json"));
+        let extension = extension.extension().unwrap_or_default();
+        let mut names = Vec::new();
+        match std::fs::read_dir(&dir) {
+            Ok(entries) => {
+                for entry in entries {
+                    let entry = entry.map_err(|source| StoreError::Lists {
+                        path: dir.clone(),
+                        source,
+                    })?;
+                    // Journals and sync states, e.g. `work.journal.json`,
+                    // have a second dot and are skipped.
+                    let path = entry.path();
+                    let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+                    if path.extension() == Some(extension) && valid_list_name(name) {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(source) => return Err(StoreError::Lists { path: dir, source }),
+        }
+        names.sort();
+        names.insert(0, DEFAULT_LIST.into());
+        Ok(names)
+    }
+
+    /// Reads both task lists. Anything that can't be read back exactly is
+    /// an error, so that the caller never overwrites tasks it failed to load.
+    ///
+    /// An operation log that doesn't exist yet starts with the tasks of the
+    /// JSON store, so switching to it keeps them.
+    pub fn load(&self) -> Result<(Vec<Task>, Vec<Task>), StoreError> {
+        let log = self.backend.location(&self.path);
+        let (active, archived) = if self.backend == Backend::Oplog && !log.exists() && self.path.exists() {
+            let (active, archived) = JsonFile::new(&self.path, 0).load()?;
+            let mut tasks = self.tasks.borrow_mut();
+            for (task, archived) in entries(&active, &archived) {
+                tasks.insert(task, archived)?;
+            }
+            tasks.commit()?;
+            eprintln!(
+                "note: copied the tasks in {} to the operation log in {}",
+                self.path.display(),
+                log.display()
+            );
+            (active, archived)
+        } else {
+            self.tasks.borrow_mut().load()?
+        };
+        self.remember(&active, &archived);
+        Ok((active, archived))
+    }
+
+    fn remember(&self, active: &[Task], archived: &[Task]) {
+        *self.loaded.borrow_mut() = entries(active, archived)
+            .map(|(task, archived)| (task.uuid, (task.clone(), archived)))
+            .collect();
+    }
+
+    /// Writes what changed since the lists were loaded or last saved.
+    pub fn save(&self, active: &[Task], archived: &[Task]) -> Result<(), StoreError> {
+        let mut tasks = self.tasks.borrow_mut();
+        let mut changed = false;
+        {
+            let loaded = self.loaded.borrow();
+            let mut seen = HashSet::new();
+            for (task, archived) in entries(active, archived) {
+                seen.insert(task.uuid);
+                match loaded.get(&task.uuid) {
+                    None => tasks.insert(task, archived)?,
+                    Some((old, was_archived)) if old == task && *was_archived == archived => continue,
+                    Some((old, was_archived)) => {
+                        if *was_archived != archived {
+                            tasks.archive(task.uuid, archived)?;
+                        }
+                        if old != task {
+                            tasks.update(task)?;
+                        }
+                    }
+                }
+                changed = true;
+            }
+            for uuid in loaded.keys().filter(|uuid| !seen.contains(uuid)) {
+                tasks.delete(*uuid)?;
+                changed = true;
+            }
+        }
+        if changed {
+            tasks.commit()?;
+        }
+        self.remember(active, archived);
+        Ok(())
+    }
+
+    /// The tasks of one list that match `filter`.
+    pub fn query(&self, filter: &Filter) -> Result<Vec<Task>, StoreError> {
+        self.tasks.borrow_mut().query(filter)
+    }
+
+    /// Squashes the operation log, see [`Log::compact`].
+    pub fn compact(&self) -> Result<Compacted, StoreError> {
+        self.tasks.borrow_mut().compact()
+    }
+
+    /// Copies every task into an empty `to` store next to this one, which
+    /// is left as it is. Returns how many tasks and archived tasks moved.
+    pub fn migrate(&self, to: Backend) -> Result<(usize, usize), StoreError> {
+        if to == self.backend {
+            return Err(StoreError::SameBackend(to));
+        }
+        let (active, archived) = self.load()?;
+        let mut target = open_backend(&self.path, to, self.backups)?;
+        let (existing, existing_archived) = target.load()?;
+        if !existing.is_empty() || !existing_archived.is_empty() {
+            return Err(StoreError::NotEmpty {
+                path: to.location(&self.path),
+            });
+        }
+        for (task, archived) in entries(&active, &archived) {
+            target.insert(task, archived)?;
+        }
+        target.commit()?;
+        Ok((active.len(), archived.len()))
+    }
+
+    /// Where the `backend` store for this list is, e.g. `tasks.db`.
+    pub fn location_of(&self, backend: Backend) -> PathBuf {
+        backend.location(&self.path)
+    }
+
+    /// The undo journal lives next to the store: `tasks.journal.json`.
+    pub fn journal_path(&self) -> PathBuf {
+        self.path.with_extension("journal.json")
+    }
+
+    /// What a sync backend remembers about the last sync lives next to the
+    /// store too, e.g. `tasks.caldav.json`.
+    pub fn sync_state_path(&self, backend: &str) -> PathBuf {
+        self.path.with_extension(format!("{}.json", backend))
+    }
+
+    /// Makes backup `n` of the JSON store the current one. The replaced
+    /// store becomes backup 1 like on any other save, so a restore can
+    /// itself be undone with `restore --backup 1`.
+    pub fn restore(&self, n: usize) -> Result<(Vec<Task>, Vec<Task>), StoreError> {
+        if self.backend != Backend::Json {
+            return Err(StoreError::NoBackups(self.backend));
+        }
+        let path = json::backup_path(&self.path, n);
+        if !path.exists() {
+            return Err(StoreError::MissingBackup { path });
+        }
+
+        let (active, archived) = JsonFile::new(&path, 0).load()?;
+        JsonFile::new(&self.path, self.backups).write(&active, &archived)?;
+        Ok((active, archived))
+    }
+
+    /// Legacy files the user chose not to copy, one path per line:
+    /// `tasks.legacy-declined`.
+    fn declined_path(&self) -> PathBuf {
+        self.base.with_extension("legacy-declined")
+    }
+
+    /// On the first run, offers to copy a `tasks.json` left in the current
+    /// directory by older versions into the store. The legacy file is kept.
+    /// A "no" is remembered for that file, which is only offered again when
+    /// `ask_again` (`--migrate`) is set.
+    pub fn offer_legacy_migration(&self, ask_again: bool) -> std::io::Result<()> {
+        let legacy = Path::new(LEGACY_STORE);
+        if !legacy.is_file() {
+            return Ok(());
+        }
+        if self.path.exists() {
+            if ask_again {
+                eprintln!(
+                    "note: not copying {} over {}, which already exists",
+                    LEGACY_STORE,
+                    self.path.display()
+                );
+            }
+            return Ok(());
+        }
+        let declined_path = self.declined_path();
+        let declined = match std::fs::read_to_string(&declined_path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let legacy_path = legacy.canonicalize()?;
+        if !ask_again && declined.lines().any(|line| Path::new(line) == legacy_path) {
+            return Ok(());
+        }
+
+        if !std::io::stdin().is_terminal() {
+            eprintln!(
+                "note: found {} in the current directory; run interactively to move it to {}",
+                LEGACY_STORE,
+                self.path.display()
+            );
+            return Ok(());
+        }
+
+        print!(
+            "Found {} in the current directory. Copy it to {}? [y/N] ",
+            LEGACY_STORE,
+            self.path.display()
+        );
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().lock().read_line(&mut answer)?;
+        if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            if !declined.lines().any(|line| Path::new(line) == legacy_path) {
+                if let Some(dir) = declined_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+                    std::fs::create_dir_all(dir)?;
+                }
+                let mut file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&declined_path)?;
+                writeln!(file, "{}", legacy_path.display())?;
+            }
+            println!("Not copied; run with --migrate to be asked again.");
+            return Ok(());
+        }
+
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::copy(legacy, &self.path)?;
+        println!(
+            "Copied {} to {}. You can delete the old file.",
+            LEGACY_STORE,
+            self.path.display()
+        );
+        Ok(())
+    }
+}
+
+/// Replaces `path` with `contents` through a flushed temporary file and a
+/// rename, calling `before_rename` once the new contents are safely on disk.
+pub fn write_atomic(
+    path: &Path,
+    contents: &[u8],
+    before_rename: impl FnOnce() -> Result<(), StoreError>,
+) -> Result<(), StoreError> {
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty());
+    if let Some(dir) = dir {
+        std::fs::create_dir_all(dir).map_err(|source| StoreError::Write {
+            path: dir.to_path_buf(),
+            source,
+        })?;
+    }
+
+    let tmp = with_suffix(path, ".tmp");
+    let write_tmp = || -> std::io::Result<()> {
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()
+    };
+    if let Err(source) = write_tmp() {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(StoreError::Write { path: tmp, source });
+    }
+
+    before_rename()?;
+
+    std::fs::rename(&tmp, path).map_err(|source| StoreError::Write {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    // Make the rename itself durable. Not every platform lets a
+    // directory be opened for this, so failures are ignored.
+    if let Ok(dir) = File::open(dir.unwrap_or(Path::new("."))) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Copies an unreadable file to `<file>.corrupt`, or `.corrupt.2`, `.corrupt.3`
+/// and so on so that an earlier copy is never overwritten.
+pub fn back_up_corrupt(path: &Path) -> Result<PathBuf, StoreError> {
+    let mut backup = with_suffix(path, ".corrupt");
+    let mut n = 2;
+    while backup.exists() {
+        backup = with_suffix(path, &format!(".corrupt.{}", n));
+        n += 1;
+    }
+
+    std::fs::copy(path, &backup).map_err(|source| StoreError::Write {
+        path: backup.clone(),
+        source,
+    })?;
+    Ok(backup)
+}
+
+/// `path` with `suffix` appended to its file name, e.g. `tasks.json.1`.
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("todosage-store-{}", Uuid::new_v4()))
+            .join("tasks.json")
+    }
+
+    fn task(description: &str) -> Task {
+        Task {
+            uuid: Uuid::new_v4(),
+            id: 1,
+            description: description.into(),
+            ..Task::default()
+        }
+    }
+
+    /// Saves the store once per description, each time with that one task.
+    fn save_each(store: &Store, descriptions: &[&str]) {
+        store.load().unwrap();
+        for description in descriptions {
+            store.save(&[task(description)], &[]).unwrap();
+        }
+    }
+
+    fn description(path: &Path) -> String {
+        let (active, _) = JsonFile::new(path, 0).load().unwrap();
+        active[0].description.clone()
+    }
+
+    #[test]
+    fn backups_rotate_up_to_the_configured_count() {
+        let path = path();
+        let store = Store::open(Some(path.clone()), Backend::Json, 3).unwrap();
+        save_each(&store, &["one", "two", "three", "four", "five"]);
+
+        assert_eq!(description(&path), "five");
+        for (n, expected) in [(1, "four"), (2, "three"), (3, "two")] {
+            assert_eq!(description(&json::backup_path(&path, n)), expected);
+        }
+        assert!(!json::backup_path(&path, 4).exists());
+    }
+
+    #[test]
+    fn restore_brings_back_a_backup() {
+        let path = path();
+        let store = Store::open(Some(path.clone()), Backend::Json, 3).unwrap();
+        save_each(&store, &["one", "two", "three"]);
+
+        let (active, _) = store.restore(2).unwrap();
+        assert_eq!(active[0].description, "one");
+        assert_eq!(description(&path), "one");
+        // The replaced version is a backup like any other, so the restore
+        // can be undone.
+        assert_eq!(description(&json::backup_path(&path, 1)), "three");
+
+        assert!(matches!(store.restore(4), Err(StoreError::MissingBackup { .. })));
+        let sqlite = Store::open(Some(path), Backend::Sqlite, 3).unwrap();
+        assert!(matches!(sqlite.restore(1), Err(StoreError::NoBackups(Backend::Sqlite))));
+    }
+
+    #[test]
+    fn a_corrupt_store_is_copied_aside() {
+        let path = path();
+        write_atomic(&path, b"{\"active\": [", || Ok(())).unwrap();
+        let store = Store::open(Some(path.clone()), Backend::Json, 3).unwrap();
+
+        let error = store.load().unwrap_err();
+        assert!(matches!(error, StoreError::Corrupt { .. }));
+        let first = error.backup().unwrap().to_path_buf();
+        assert_eq!(first, with_suffix(&path, ".corrupt"));
+        assert_eq!(std::fs::read(&first).unwrap(), b"{\"active\": [");
+        assert_eq!(std::fs::read(&path).unwrap(), b"{\"active\": [");
+
+        // A second failure doesn't overwrite the first copy.
+        std::fs::write(&path, b"[]").unwrap();
+        let error = store.load().unwrap_err();
+        assert!(matches!(error, StoreError::Malformed { .. }));
+        assert_eq!(error.backup(), Some(with_suffix(&path, ".corrupt.2").as_path()));
+        assert_eq!(std::fs::read(&first).unwrap(), b"{\"active\": [");
+        assert_eq!(std::fs::read(with_suffix(&path, ".corrupt.2")).unwrap(), b"[]");
+    }
+}
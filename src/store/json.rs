@@ -0,0 +1,362 @@
+// src/store/json.rs
+//
+// The tasks as one JSON file, `{"version": N, "active": [...], "archived":
+// [...]}`. The lists are kept in memory and the whole file is rewritten on
+// commit.
+//
+// `version` goes up whenever the layout changes in a way older versions of
+// the app can't read back intact. Older files are upgraded on load by the
+// steps in `MIGRATIONS`; newer ones are refused rather than rewritten
+// without the fields this version doesn't know about.
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use super::{back_up_corrupt, with_suffix, write_atomic, Filter, StoreError, TaskStore};
+use crate::models::Task;
+
+/// The version of the file this build writes.
+pub const VERSION: u64 = 1;
+
+/// `MIGRATIONS[n]` upgrades a version `n` file to version `n + 1`. Files
+/// from before versioning are version 0.
+const MIGRATIONS: [fn(&mut Map<String, Value>); VERSION as usize] = [add_uuids];
+
+/// Version 1 gave every task a permanent uuid.
+fn add_uuids(root: &mut Map<String, Value>) {
+    for list in ["active", "archived"] {
+        let Some(Value::Array(items)) = root.get_mut(list) else {
+            continue;
+        };
+        for item in items.iter_mut().filter_map(Value::as_object_mut) {
+            item.entry("uuid")
+                .or_insert_with(|| Value::String(Uuid::new_v4().to_string()));
+        }
+    }
+}
+
+pub struct JsonFile {
+    path: PathBuf,
+    backups: usize,
+    /// The lists as loaded and changed since.
+    lists: Option<(Vec<Task>, Vec<Task>)>,
+}
+
+impl JsonFile {
+    /// The file at `path`. Each commit keeps the previous `backups` versions
+    /// of it.
+    pub fn new(path: &Path, backups: usize) -> JsonFile {
+        JsonFile {
+            path: path.to_path_buf(),
+            backups,
+            lists: None,
+        }
+    }
+
+    fn lists(&mut self) -> Result<&mut (Vec<Task>, Vec<Task>), StoreError> {
+        if self.lists.is_none() {
+            self.lists = Some(self.read()?);
+        }
+        Ok(self.lists.as_mut().expect("just loaded"))
+    }
+
+    /// Where a task is: which list, and where in it.
+    fn find(&mut self, uuid: Uuid) -> Result<(&mut Vec<Task>, usize), StoreError> {
+        let (active, archived) = self.lists()?;
+        if let Some(index) = active.iter().position(|t| t.uuid == uuid) {
+            return Ok((active, index));
+        }
+        match archived.iter().position(|t| t.uuid == uuid) {
+            Some(index) => Ok((archived, index)),
+            None => Err(StoreError::MissingTask(uuid)),
+        }
+    }
+
+    /// Replaces the file without ever leaving a partly written one behind:
+    /// the new contents go to `<store>.tmp`, are flushed to disk and then
+    /// renamed over the store. The version being replaced becomes
+    /// `<store>.1`, older backups shift up and the oldest is dropped.
+    ///
+    /// A file that was never read is checked first, so that one written by
+    /// a newer version is never replaced.
+    pub fn write(&self, active: &[Task], archived: &[Task]) -> Result<(), StoreError> {
+        if self.lists.is_none() {
+            self.check_version()?;
+        }
+        let data = serde_json::json!({
+            "version": VERSION,
+            "active": active,
+            "archived": archived
+        });
+        let json = serde_json::to_string_pretty(&data).map_err(|e| StoreError::Write {
+            path: self.path.clone(),
+            source: e.into(),
+        })?;
+        write_atomic(&self.path, json.as_bytes(), || self.rotate_backups())
+    }
+
+    fn rotate_backups(&self) -> Result<(), StoreError> {
+        if self.backups == 0 || !self.path.exists() {
+            return Ok(());
+        }
+
+        let rotate = || -> std::io::Result<()> {
+            for n in (1..self.backups).rev() {
+                let from = backup_path(&self.path, n);
+                if from.exists() {
+                    std::fs::rename(&from, backup_path(&self.path, n + 1))?;
+                }
+            }
+            // Copy rather than rename so the store itself never disappears.
+            std::fs::copy(&self.path, backup_path(&self.path, 1))?;
+            Ok(())
+        };
+        rotate().map_err(|source| StoreError::Write {
+            path: backup_path(&self.path, 1),
+            source,
+        })
+    }
+
+    /// Errors if the file was written by a newer version. One that can't be
+    /// parsed is left for `read` to report, or to be replaced.
+    fn check_version(&self) -> Result<(), StoreError> {
+        let Ok(data) = std::fs::read_to_string(&self.path) else {
+            return Ok(());
+        };
+        match serde_json::from_str::<Value>(&data) {
+            Ok(Value::Object(root)) => self.version(&root).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The file's version, which this build must be able to read.
+    fn version(&self, root: &Map<String, Value>) -> Result<u64, StoreError> {
+        let version = match root.get("version") {
+            None => 0,
+            Some(value) => match value.as_u64() {
+                Some(version) => version,
+                None => {
+                    return Err(StoreError::Malformed {
+                        path: self.path.clone(),
+                        backup: back_up_corrupt(&self.path)?,
+                        reason: format!("\"version\" is not a number: {}", value),
+                    })
+                }
+            },
+        };
+        if version > VERSION {
+            return Err(StoreError::TooNew {
+                path: self.path.clone(),
+                version,
+            });
+        }
+        Ok(version)
+    }
+
+    /// Reads both task lists, upgrading an older file first. A missing file
+    /// is an empty store; anything that can't be read back exactly is an
+    /// error, so that the caller never overwrites tasks it failed to load.
+    /// The unreadable file is copied aside to `<store>.corrupt` first.
+    fn read(&self) -> Result<(Vec<Task>, Vec<Task>), StoreError> {
+        let data = match std::fs::read_to_string(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), Vec::new())),
+            Err(source) => {
+                return Err(StoreError::Read {
+                    path: self.path.clone(),
+                    source,
+                })
+            }
+        };
+
+        let parsed: Value = match serde_json::from_str(&data) {
+            Ok(parsed) => parsed,
+            Err(source) => {
+                return Err(StoreError::Corrupt {
+                    path: self.path.clone(),
+                    backup: back_up_corrupt(&self.path)?,
+                    source,
+                })
+            }
+        };
+
+        let Value::Object(mut root) = parsed else {
+            return Err(StoreError::Malformed {
+                path: self.path.clone(),
+                backup: back_up_corrupt(&self.path)?,
+                reason: "expected an object with \"active\" and \"archived\" lists".into(),
+            });
+        };
+
+        let version = self.version(&root)?;
+        for migrate in &MIGRATIONS[version as usize..] {
+            migrate(&mut root);
+        }
+
+        let active = self.parse_list(root.remove("active"), "active")?;
+        let archived = self.parse_list(root.remove("archived"), "archived")?;
+
+        // Write an upgraded file back, keeping the old one as a backup, so
+        // that what the migrations made up (such as new uuids) stays the
+        // same on the next run.
+        if version < VERSION {
+            self.write(&active, &archived)?;
+        }
+        Ok((active, archived))
+    }
+
+    fn parse_list(&self, value: Option<Value>, list: &'static str) -> Result<Vec<Task>, StoreError> {
+        let items = match value {
+            None | Some(Value::Null) => return Ok(Vec::new()),
+            Some(Value::Array(items)) => items,
+            Some(_) => {
+                return Err(StoreError::Malformed {
+                    path: self.path.clone(),
+                    backup: back_up_corrupt(&self.path)?,
+                    reason: format!("\"{}\" is not a list", list),
+                })
+            }
+        };
+
+        let mut tasks = Vec::with_capacity(items.len());
+        for (index, item) in items.into_iter().enumerate() {
+            let id = item.get("id").and_then(Value::as_u64);
+            match serde_json::from_value(item) {
+                Ok(task) => tasks.push(task),
+                Err(source) => {
+                    return Err(StoreError::InvalidTask {
+                        path: self.path.clone(),
+                        backup: back_up_corrupt(&self.path)?,
+                        list,
+                        index: index + 1,
+                        id,
+                        source,
+                    })
+                }
+            }
+        }
+        Ok(tasks)
+    }
+}
+
+impl TaskStore for JsonFile {
+    fn load(&mut self) -> Result<(Vec<Task>, Vec<Task>), StoreError> {
+        self.lists = Some(self.read()?);
+        Ok(self.lists.clone().expect("just loaded"))
+    }
+
+    fn insert(&mut self, task: &Task, archived: bool) -> Result<(), StoreError> {
+        let (active, archive) = self.lists()?;
+        if archived { archive } else { active }.push(task.clone());
+        Ok(())
+    }
+
+    fn update(&mut self, task: &Task) -> Result<(), StoreError> {
+        let (list, index) = self.find(task.uuid)?;
+        list[index] = task.clone();
+        Ok(())
+    }
+
+    fn delete(&mut self, uuid: Uuid) -> Result<(), StoreError> {
+        let (list, index) = self.find(uuid)?;
+        list.remove(index);
+        Ok(())
+    }
+
+    fn archive(&mut self, uuid: Uuid, archived: bool) -> Result<(), StoreError> {
+        let (list, index) = self.find(uuid)?;
+        let task = list.remove(index);
+        self.insert(&task, archived)
+    }
+
+    fn query(&mut self, filter: &Filter) -> Result<Vec<Task>, StoreError> {
+        let (active, archived) = self.lists()?;
+        let list = if filter.archived { archived } else { active };
+        Ok(list.iter().filter(|task| filter.matches(task)).cloned().collect())
+    }
+
+    /// Writes the file, each list in the order of its ids.
+    fn commit(&mut self) -> Result<(), StoreError> {
+        if let Some((active, archived)) = &mut self.lists {
+            active.sort_by_key(|t| t.id);
+            archived.sort_by_key(|t| t.id);
+        }
+        match &self.lists {
+            Some((active, archived)) => self.write(active, archived),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `<store>.N`, the Nth most recent previous version of the store.
+pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+    with_suffix(path, &format!(".{}", n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("todosage-json-{}", Uuid::new_v4()))
+            .join("tasks.json")
+    }
+
+    fn task(id: u32, description: &str) -> Value {
+        serde_json::json!({
+            "id": id,
+            "description": description,
+            "tags": [],
+            "due_date": null,
+            "priority": "Low",
+            "completed": false
+        })
+    }
+
+    #[test]
+    fn version_0_files_get_uuids_and_are_written_back() {
+        let path = path();
+        let old = serde_json::json!({
+            "active": [task(1, "buy milk"), task(2, "file taxes")],
+            "archived": [task(1, "old")]
+        })
+        .to_string();
+        write_atomic(&path, old.as_bytes(), || Ok(())).unwrap();
+
+        let (active, archived) = JsonFile::new(&path, 3).load().unwrap();
+        let uuids: Vec<Uuid> = active.iter().chain(&archived).map(|t| t.uuid).collect();
+        assert_eq!(uuids.len(), 3);
+        assert!(uuids.iter().all(|uuid| !uuid.is_nil()));
+        assert!(uuids[0] != uuids[1] && uuids[1] != uuids[2] && uuids[0] != uuids[2]);
+
+        let written: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written["version"], VERSION);
+        assert_eq!(std::fs::read_to_string(backup_path(&path, 1)).unwrap(), old);
+
+        // The uuids made up are the ones kept.
+        let (again, _) = JsonFile::new(&path, 3).load().unwrap();
+        assert_eq!(again, active);
+        assert!(!backup_path(&path, 2).exists());
+    }
+
+    #[test]
+    fn newer_files_are_refused_and_left_alone() {
+        let path = path();
+        let newer = serde_json::json!({
+            "version": VERSION + 1,
+            "active": [task(1, "buy milk")],
+            "archived": [],
+            "something_new": true
+        })
+        .to_string();
+        write_atomic(&path, newer.as_bytes(), || Ok(())).unwrap();
+
+        let mut store = JsonFile::new(&path, 3);
+        assert!(matches!(store.load(), Err(StoreError::TooNew { version, .. }) if version == VERSION + 1));
+        assert!(matches!(store.write(&[], &[]), Err(StoreError::TooNew { .. })));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), newer);
+        assert!(!backup_path(&path, 1).exists());
+    }
+}
@@ -0,0 +1,660 @@
+// src/store/oplog.rs
+//
+// The tasks as an append-only log of operations, for stores kept in a
+// folder that a file-sync tool (Syncthing, Dropbox, Google Drive, ...)
+// copies between devices.
+//
+// Each device appends to its own file, `tasks.oplog/<device>.jsonl`, so two
+// devices never write the same file. An operation sets fields of one task,
+// stamped with the time and the device; reading all the files and keeping,
+// for every field, the value with the latest stamp gives the same tasks in
+// whatever order the files arrive. The archive is an `archived` field and
+// deletion a `deleted` one, so they merge like any other change. Conflicted
+// copies made by a sync tool are read too: operations are idempotent.
+//
+// Saving appends only the fields that changed since the tasks were loaded,
+// so a change another device made meanwhile is not overwritten with the
+// stale value. Display ids aren't logged: tasks are numbered in the order
+// their first operation was written, so removing one doesn't rewrite the
+// ids of all the tasks after it.
+use std::collections::{BTreeMap, HashMap};
+use std::fs::OpenOptions;
+use std::io::{Read as _, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::{DateTime, Duration, Utc};
+use directories::BaseDirs;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use super::{back_up_corrupt, entries, write_atomic, Filter, StoreError, TaskStore};
+use crate::config::APP_DIR;
+use crate::models::{renumber, Task};
+
+/// Environment variable that overrides this device's id.
+pub const DEVICE_ENV: &str = "TODOSAGE_DEVICE";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Op {
+    at: DateTime<Utc>,
+    device: String,
+    task: Uuid,
+    set: Map<String, Value>,
+}
+
+/// Which of two writes wins: the later one, and on a tie the device that
+/// sorts last.
+type Stamp = (DateTime<Utc>, String);
+
+#[derive(Debug, Default)]
+struct Record {
+    fields: BTreeMap<String, (Value, Stamp)>,
+    /// The stamp of the task's first operation, and its line in the file,
+    /// which orders tasks added by the same write.
+    first: Option<(Stamp, usize)>,
+}
+
+/// Every task's fields as they are known here.
+type Fields = HashMap<Uuid, Map<String, Value>>;
+
+/// What `compact` did.
+#[derive(Debug)]
+pub struct Compacted {
+    pub files: usize,
+    pub before: usize,
+    pub after: usize,
+}
+
+pub struct Log {
+    dir: PathBuf,
+    device: String,
+    /// The fields as last loaded and changed since, which an update is
+    /// compared with.
+    loaded: Fields,
+    /// The fields set since the last commit, by task.
+    pending: Vec<(Uuid, Map<String, Value>)>,
+}
+
+/// The log files read, with what they held.
+struct Read {
+    records: HashMap<Uuid, Record>,
+    ops: usize,
+    latest: Option<DateTime<Utc>>,
+    /// Each file with its length and modification time when it was read.
+    files: Vec<(PathBuf, u64, Option<SystemTime>)>,
+}
+
+impl Log {
+    pub fn open(dir: PathBuf) -> Result<Log, StoreError> {
+        Ok(Log {
+            dir,
+            device: device_id()?,
+            loaded: Fields::new(),
+            pending: Vec::new(),
+        })
+    }
+
+    fn own_file(&self) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", self.device))
+    }
+
+    fn write_error(&self, source: std::io::Error) -> StoreError {
+        StoreError::Write {
+            path: self.own_file(),
+            source,
+        }
+    }
+
+    fn append(&self, lines: &[u8]) -> Result<(), StoreError> {
+        std::fs::create_dir_all(&self.dir).map_err(|source| StoreError::Write {
+            path: self.dir.clone(),
+            source,
+        })?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .read(true)
+            .open(self.own_file())
+            .map_err(|e| self.write_error(e))?;
+        // A save cut short leaves half a line; drop it rather than append to it.
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).map_err(|e| self.write_error(e))?;
+        if data.last().is_some_and(|&b| b != b'\n') {
+            let end = data.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+            file.set_len(end as u64).map_err(|e| self.write_error(e))?;
+        }
+        file.write_all(lines)
+            .and_then(|_| file.sync_all())
+            .map_err(|e| self.write_error(e))
+    }
+
+    /// Squashes the log into one operation per task and stamp in this
+    /// device's file, and removes the other devices' files. Every field
+    /// keeps its stamp, so an old copy of a removed file showing up again
+    /// changes nothing. A file that grew since it was read is kept.
+    fn squash(&self) -> Result<Compacted, StoreError> {
+        let read = self.read()?;
+        let mut lines = String::new();
+        let mut after = 0;
+        // In the order they're numbered in, which the line order keeps for
+        // tasks added together.
+        let mut uuids: Vec<&Uuid> = read.records.keys().collect();
+        uuids.sort_by_key(|uuid| (&read.records[*uuid].first, *uuid));
+        for uuid in uuids {
+            let record = &read.records[uuid];
+            let deleted = record
+                .fields
+                .get("deleted")
+                .is_some_and(|(value, _)| value == &Value::Bool(true));
+            let mut groups: BTreeMap<&Stamp, Map<String, Value>> = BTreeMap::new();
+            // Keep the first stamp even when every field has been set again
+            // since, so the task keeps its place in the list.
+            if let Some((first, _)) = &record.first {
+                groups.insert(first, Map::new());
+            }
+            for (key, (value, stamp)) in &record.fields {
+                // All that matters of a deleted task is that it is.
+                if !deleted || key == "deleted" {
+                    groups.entry(stamp).or_default().insert(key.clone(), value.clone());
+                }
+            }
+            for ((at, device), set) in groups {
+                let op = Op {
+                    at: *at,
+                    device: device.clone(),
+                    task: *uuid,
+                    set,
+                };
+                lines.push_str(&serde_json::to_string(&op).map_err(|e| self.write_error(e.into()))?);
+                lines.push('\n');
+                after += 1;
+            }
+        }
+
+        let own = self.own_file();
+        write_atomic(&own, lines.as_bytes(), || Ok(()))?;
+        for (path, len, modified) in &read.files {
+            if *path == own {
+                continue;
+            }
+            let unchanged = std::fs::metadata(path).is_ok_and(|m| m.len() == *len && m.modified().ok() == *modified);
+            if unchanged {
+                std::fs::remove_file(path).map_err(|source| StoreError::Write {
+                    path: path.clone(),
+                    source,
+                })?;
+            }
+        }
+        Ok(Compacted {
+            files: read.files.len(),
+            before: read.ops,
+            after,
+        })
+    }
+
+    fn read(&self) -> Result<Read, StoreError> {
+        let mut read = Read {
+            records: HashMap::new(),
+            ops: 0,
+            latest: None,
+            files: Vec::new(),
+        };
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(read),
+            Err(source) => {
+                return Err(StoreError::Read {
+                    path: self.dir.clone(),
+                    source,
+                })
+            }
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|e| e == "jsonl"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let read_error = |source| StoreError::Read {
+                path: path.clone(),
+                source,
+            };
+            let metadata = std::fs::metadata(&path).map_err(read_error)?;
+            let data = std::fs::read_to_string(&path).map_err(read_error)?;
+            read.files
+                .push((path.clone(), metadata.len(), metadata.modified().ok()));
+
+            let lines: Vec<&str> = data.lines().collect();
+            for (index, line) in lines.iter().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let op: Op = match serde_json::from_str(line) {
+                    Ok(op) => op,
+                    // The end of a file still being copied over.
+                    Err(_) if index + 1 == lines.len() && !data.ends_with('\n') => break,
+                    Err(e) => {
+                        return Err(StoreError::Malformed {
+                            path: path.clone(),
+                            backup: back_up_corrupt(&path)?,
+                            reason: format!("line {}: {}", index + 1, e),
+                        })
+                    }
+                };
+                read.ops += 1;
+                read.latest = read.latest.max(Some(op.at));
+                let record = read.records.entry(op.task).or_default();
+                let stamp = (op.at, op.device);
+                let first = (stamp.clone(), index);
+                if record.first.as_ref().is_none_or(|earliest| first < *earliest) {
+                    record.first = Some(first);
+                }
+                for (key, value) in op.set {
+                    match record.fields.get(&key) {
+                        Some((_, existing)) if *existing >= stamp => {}
+                        _ => {
+                            record.fields.insert(key, (value, stamp.clone()));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(read)
+    }
+}
+
+impl TaskStore for Log {
+    fn load(&mut self) -> Result<(Vec<Task>, Vec<Task>), StoreError> {
+        let read = self.read()?;
+        let (active, archived) = materialize(&self.dir, &read.records)?;
+        self.loaded = entries(&active, &archived)
+            .map(|(task, archived)| (task.uuid, fields(task, archived)))
+            .collect();
+        Ok((active, archived))
+    }
+
+    fn insert(&mut self, task: &Task, archived: bool) -> Result<(), StoreError> {
+        let fields = fields(task, archived);
+        self.pending.push((task.uuid, fields.clone()));
+        self.loaded.insert(task.uuid, fields);
+        Ok(())
+    }
+
+    /// Sets the fields that changed, and clears the ones left out now, such
+    /// as a removed due date.
+    fn update(&mut self, task: &Task) -> Result<(), StoreError> {
+        let old = self.loaded.get(&task.uuid).ok_or(StoreError::MissingTask(task.uuid))?;
+        let archived = old.get("archived") == Some(&Value::Bool(true));
+        let new = fields(task, archived);
+        let mut set = Map::new();
+        for (key, value) in &new {
+            if old.get(key) != Some(value) {
+                set.insert(key.clone(), value.clone());
+            }
+        }
+        for key in old.keys().filter(|key| !new.contains_key(*key)) {
+            set.insert(key.clone(), Value::Null);
+        }
+        // Only its id changed, which isn't logged.
+        if !set.is_empty() {
+            self.pending.push((task.uuid, set));
+        }
+        self.loaded.insert(task.uuid, new);
+        Ok(())
+    }
+
+    fn delete(&mut self, uuid: Uuid) -> Result<(), StoreError> {
+        self.loaded.remove(&uuid).ok_or(StoreError::MissingTask(uuid))?;
+        let mut set = Map::new();
+        set.insert("deleted".into(), Value::Bool(true));
+        self.pending.push((uuid, set));
+        Ok(())
+    }
+
+    fn archive(&mut self, uuid: Uuid, archived: bool) -> Result<(), StoreError> {
+        let fields = self.loaded.get_mut(&uuid).ok_or(StoreError::MissingTask(uuid))?;
+        fields.insert("archived".into(), Value::Bool(archived));
+        let mut set = Map::new();
+        set.insert("archived".into(), Value::Bool(archived));
+        self.pending.push((uuid, set));
+        Ok(())
+    }
+
+    fn query(&mut self, filter: &Filter) -> Result<Vec<Task>, StoreError> {
+        let (active, archived) = materialize(&self.dir, &self.read()?.records)?;
+        let list = if filter.archived { archived } else { active };
+        Ok(list.into_iter().filter(|task| filter.matches(task)).collect())
+    }
+
+    /// Appends the changes to this device's file, one operation per task.
+    fn commit(&mut self) -> Result<(), StoreError> {
+        let mut ops: Vec<(Uuid, Map<String, Value>)> = Vec::new();
+        for (uuid, set) in std::mem::take(&mut self.pending) {
+            match ops.iter_mut().find(|(u, _)| *u == uuid) {
+                Some((_, fields)) => fields.extend(set),
+                None => ops.push((uuid, set)),
+            }
+        }
+        // With nothing to write the log is still created, so that an empty
+        // list copied into it by `migrate` is there afterwards.
+        if ops.is_empty() {
+            return self.append(b"");
+        }
+
+        // Never stamp a write earlier than one already seen, whatever this
+        // device's clock says.
+        let now = Utc::now();
+        let at = match self.read()?.latest {
+            Some(latest) if latest >= now => latest + Duration::nanoseconds(1),
+            _ => now,
+        };
+        let mut lines = String::new();
+        for (task, set) in ops {
+            let op = Op {
+                at,
+                device: self.device.clone(),
+                task,
+                set,
+            };
+            lines.push_str(&serde_json::to_string(&op).map_err(|e| self.write_error(e.into()))?);
+            lines.push('\n');
+        }
+        self.append(lines.as_bytes())
+    }
+
+    fn compact(&mut self) -> Result<Compacted, StoreError> {
+        self.squash()
+    }
+}
+
+/// A task's fields, the way the log stores them.
+fn fields(task: &Task, archived: bool) -> Map<String, Value> {
+    let mut fields = match serde_json::to_value(task) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    };
+    fields.remove("uuid");
+    fields.remove("id");
+    fields.insert("archived".into(), Value::Bool(archived));
+    fields.insert("deleted".into(), Value::Bool(false));
+    fields
+}
+
+/// The lists the records describe, each in the order its tasks were added.
+fn materialize(dir: &Path, records: &HashMap<Uuid, Record>) -> Result<(Vec<Task>, Vec<Task>), StoreError> {
+    let mut active = Vec::new();
+    let mut archived = Vec::new();
+    for (uuid, record) in records {
+        let flag = |key: &str| {
+            record
+                .fields
+                .get(key)
+                .is_some_and(|(value, _)| value == &Value::Bool(true))
+        };
+        if flag("deleted") {
+            continue;
+        }
+        // Start from the defaults, so a task with some operations missing
+        // still reads.
+        let mut object = match serde_json::to_value(Task::default()) {
+            Ok(Value::Object(object)) => object,
+            _ => Map::new(),
+        };
+        for (key, (value, _)) in &record.fields {
+            match (key.as_str(), value) {
+                // Logs written before ids were derived still have them.
+                ("archived" | "deleted" | "id", _) => {}
+                (_, Value::Null) => {
+                    object.remove(key);
+                }
+                _ => {
+                    object.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        object.insert("uuid".into(), Value::String(uuid.to_string()));
+        let first = record.first.clone();
+        let task: Task = serde_json::from_value(Value::Object(object)).map_err(|source| StoreError::InvalidRecord {
+            path: dir.to_path_buf(),
+            uuid: *uuid,
+            source,
+        })?;
+        let list = if flag("archived") { &mut archived } else { &mut active };
+        list.push((task, first));
+    }
+    let finish = |mut list: Vec<(Task, Option<(Stamp, usize)>)>| {
+        list.sort_by(|(a, a_first), (b, b_first)| (a_first, a.uuid).cmp(&(b_first, b.uuid)));
+        let mut tasks: Vec<Task> = list.into_iter().map(|(task, _)| task).collect();
+        renumber(&mut tasks);
+        tasks
+    };
+    Ok((finish(active), finish(archived)))
+}
+
+/// The id is the name of this device's log file, so it can't point
+/// anywhere else.
+fn check_device(device: String) -> Result<String, StoreError> {
+    if device.is_empty() || device.contains('/') || device.contains("..") {
+        return Err(StoreError::InvalidDevice(device));
+    }
+    Ok(device)
+}
+
+/// This device's id, made up on first use and kept in the local data
+/// directory, which file-sync tools are not pointed at.
+fn device_id() -> Result<String, StoreError> {
+    if let Ok(device) = std::env::var(DEVICE_ENV) {
+        return check_device(device);
+    }
+    let dirs = BaseDirs::new().ok_or(StoreError::NoHome)?;
+    let path = dirs.data_local_dir().join(APP_DIR).join("device-id");
+    match std::fs::read_to_string(&path) {
+        Ok(id) if !id.trim().is_empty() => return Ok(id.trim().to_string()),
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(source) => return Err(StoreError::Read { path, source }),
+    }
+    let id = Uuid::new_v4().simple().to_string()[..12].to_string();
+    write_atomic(&path, id.as_bytes(), || Ok(()))?;
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::Priority;
+
+    fn dir() -> PathBuf {
+        std::env::temp_dir().join(format!("todosage-oplog-{}", Uuid::new_v4()))
+    }
+
+    fn log(dir: &Path, device: &str) -> Log {
+        Log {
+            dir: dir.to_path_buf(),
+            device: device.into(),
+            loaded: Fields::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// An operation written at `secs` seconds past a fixed time.
+    fn op(secs: i64, device: &str, task: Uuid, set: Value) -> String {
+        let Value::Object(set) = set else {
+            panic!("not an object")
+        };
+        let op = Op {
+            at: Utc.with_ymd_and_hms(2026, 10, 14, 9, 0, 0).unwrap() + Duration::seconds(secs),
+            device: device.into(),
+            task,
+            set,
+        };
+        serde_json::to_string(&op).unwrap() + "\n"
+    }
+
+    fn write(dir: &Path, file: &str, ops: &[String]) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join(format!("{}.jsonl", file)), ops.concat()).unwrap();
+    }
+
+    fn load(dir: &Path) -> (Vec<Task>, Vec<Task>) {
+        log(dir, "reader").load().unwrap()
+    }
+
+    fn added(secs: i64, device: &str, task: Uuid, description: &str) -> String {
+        op(
+            secs,
+            device,
+            task,
+            json!({"description": description, "tags": [], "due_date": null, "priority": "Low",
+                   "completed": false, "archived": false, "deleted": false}),
+        )
+    }
+
+    #[test]
+    fn files_read_in_either_order_give_the_same_tasks() {
+        let (shared, phones, laptops) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let phone = [
+            added(0, "phone", shared, "buy milk"),
+            added(1, "phone", phones, "call mum"),
+            op(5, "phone", shared, json!({"description": "buy oat milk"})),
+        ];
+        let laptop = [
+            added(2, "laptop", laptops, "file taxes"),
+            op(
+                3,
+                "laptop",
+                shared,
+                json!({"priority": "High", "description": "buy milk today"}),
+            ),
+            op(4, "laptop", phones, json!({"archived": true})),
+        ];
+        // Files are read in name order, so swapping the names swaps the order.
+        let (first, second) = (dir(), dir());
+        write(&first, "1", &phone);
+        write(&first, "2", &laptop);
+        write(&second, "1", &laptop);
+        write(&second, "2", &phone);
+
+        let (active, archived) = load(&first);
+        assert_eq!((active.clone(), archived.clone()), load(&second));
+        let descriptions: Vec<&str> = active.iter().map(|t| t.description.as_str()).collect();
+        assert_eq!(descriptions, ["buy oat milk", "file taxes"]);
+        assert_eq!(active[0].priority, Priority::High);
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].uuid, phones);
+    }
+
+    #[test]
+    fn the_later_stamp_wins_and_the_device_breaks_ties() {
+        let task = Uuid::new_v4();
+        let dir = dir();
+        write(
+            &dir,
+            "a",
+            &[
+                added(0, "a", task, "first"),
+                op(10, "a", task, json!({"description": "second", "priority": "Medium"})),
+            ],
+        );
+        write(
+            &dir,
+            "b",
+            &[
+                // Same time as a's edit: b sorts after a, so b's value wins.
+                op(10, "b", task, json!({"priority": "High"})),
+                // Earlier than a's edit, though read after it.
+                op(5, "b", task, json!({"description": "stale"})),
+            ],
+        );
+        // A device sorting last doesn't win with an earlier stamp.
+        write(&dir, "z", &[op(9, "z", task, json!({"priority": "Low"}))]);
+
+        let (active, _) = load(&dir);
+        assert_eq!(active[0].description, "second");
+        assert_eq!(active[0].priority, Priority::High);
+    }
+
+    #[test]
+    fn a_delete_racing_an_edit_stays_deleted() {
+        for edit_at in [15, 25] {
+            let (task, other) = (Uuid::new_v4(), Uuid::new_v4());
+            let dir = dir();
+            write(
+                &dir,
+                "a",
+                &[
+                    added(0, "a", task, "doomed"),
+                    added(1, "a", other, "kept"),
+                    op(20, "a", task, json!({"deleted": true})),
+                ],
+            );
+            write(&dir, "b", &[op(edit_at, "b", task, json!({"description": "edited"}))]);
+
+            let (active, archived) = load(&dir);
+            assert!(archived.is_empty());
+            assert_eq!(active.len(), 1, "edit at {}", edit_at);
+            assert_eq!(active[0].uuid, other);
+            assert_eq!(active[0].id, 1);
+        }
+    }
+
+    #[test]
+    fn squash_is_idempotent() {
+        let (milk, taxes, gone) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let dir = dir();
+        let a = [
+            added(0, "a", milk, "buy milk"),
+            added(0, "a", taxes, "file taxes"),
+            op(3, "a", milk, json!({"description": "buy oat milk"})),
+        ];
+        let b = [
+            added(1, "b", gone, "old"),
+            op(
+                2,
+                "b",
+                milk,
+                json!({"priority": "High", "description": "buy milk today"}),
+            ),
+            op(4, "b", gone, json!({"deleted": true})),
+        ];
+        write(&dir, "a", &a);
+        write(&dir, "b", &b);
+        let before = load(&dir);
+
+        let squashed = log(&dir, "a").squash().unwrap();
+        assert_eq!((squashed.files, squashed.before), (2, 6));
+        assert!(!dir.join("b.jsonl").exists());
+        assert_eq!(load(&dir), before);
+        let once = std::fs::read_to_string(dir.join("a.jsonl")).unwrap();
+
+        log(&dir, "a").squash().unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("a.jsonl")).unwrap(), once);
+
+        // A sync tool brings back the file that was removed.
+        write(&dir, "b", &b);
+        assert_eq!(load(&dir), before);
+        log(&dir, "a").squash().unwrap();
+        assert_eq!(load(&dir), before);
+        assert_eq!(std::fs::read_to_string(dir.join("a.jsonl")).unwrap(), once);
+        assert!(!dir.join("b.jsonl").exists());
+    }
+
+    #[test]
+    fn device_ids_must_be_file_names() {
+        assert_eq!(check_device("laptop-2".into()).unwrap(), "laptop-2");
+        for bad in ["", "../tasks", "a/b", ".."] {
+            assert!(
+                matches!(check_device(bad.into()), Err(StoreError::InvalidDevice(_))),
+                "{:?}",
+                bad
+            );
+        }
+    }
+}
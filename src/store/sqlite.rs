@@ -0,0 +1,343 @@
+// src/store/sqlite.rs
+//
+// The tasks in an SQLite database, `tasks.db`. Each task is a row holding
+// its JSON, plus the columns that queries filter on, so a change writes
+// one row instead of the whole store. Changes made between two commits
+// go in one transaction.
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use uuid::Uuid;
+
+use super::{Filter, StoreError, TaskStore};
+use crate::models::Task;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS tasks (
+    uuid TEXT PRIMARY KEY,
+    archived INTEGER NOT NULL,
+    id INTEGER NOT NULL,
+    completed INTEGER NOT NULL,
+    due_date TEXT,
+    data TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS tags (
+    uuid TEXT NOT NULL,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (uuid, tag)
+);
+CREATE INDEX IF NOT EXISTS tasks_due_date ON tasks (due_date);
+CREATE INDEX IF NOT EXISTS tasks_status ON tasks (archived, completed);
+CREATE INDEX IF NOT EXISTS tags_tag ON tags (tag);
+";
+
+pub struct Database {
+    path: PathBuf,
+    conn: Connection,
+    /// Whether a transaction was begun since the last commit.
+    writing: bool,
+}
+
+impl Database {
+    /// Opens the database at `path`, creating it and its tables if needed.
+    pub fn open(path: &Path) -> Result<Database, StoreError> {
+        let error = |source| StoreError::Database {
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|source| StoreError::Write {
+                path: dir.to_path_buf(),
+                source,
+            })?;
+        }
+        let conn = Connection::open(path).map_err(error)?;
+        conn.execute_batch(SCHEMA).map_err(error)?;
+        Ok(Database {
+            path: path.to_path_buf(),
+            conn,
+            writing: false,
+        })
+    }
+
+    fn error(&self, source: rusqlite::Error) -> StoreError {
+        StoreError::Database {
+            path: self.path.clone(),
+            source,
+        }
+    }
+
+    /// Begins the transaction the next commit ends, if there isn't one yet.
+    fn begin(&mut self) -> Result<(), StoreError> {
+        if !self.writing {
+            self.conn.execute_batch("BEGIN").map_err(|e| self.error(e))?;
+            self.writing = true;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, task: &Task, archived: bool) -> Result<(), StoreError> {
+        self.begin()?;
+        let data = serde_json::to_string(task).map_err(|e| StoreError::Write {
+            path: self.path.clone(),
+            source: e.into(),
+        })?;
+        let uuid = task.uuid.to_string();
+        let write = || -> rusqlite::Result<()> {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO tasks (uuid, archived, id, completed, due_date, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![uuid, archived, task.id, task.completed, task.due_date.map(due), data],
+            )?;
+            self.conn.execute("DELETE FROM tags WHERE uuid = ?1", [&uuid])?;
+            for tag in &task.tags {
+                self.conn.execute(
+                    "INSERT OR IGNORE INTO tags (uuid, tag) VALUES (?1, ?2)",
+                    params![uuid, tag],
+                )?;
+            }
+            Ok(())
+        };
+        write().map_err(|e| self.error(e))
+    }
+
+    /// Whether the task is in the database, and if so in the archive.
+    fn archived(&self, uuid: Uuid) -> Result<bool, StoreError> {
+        self.conn
+            .query_row(
+                "SELECT archived FROM tasks WHERE uuid = ?1",
+                [uuid.to_string()],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| self.error(e))?
+            .ok_or(StoreError::MissingTask(uuid))
+    }
+
+    /// The tasks `SELECT uuid, data FROM tasks <rest>` finds.
+    fn select(&self, rest: &str, values: Vec<Value>) -> Result<Vec<(Task, bool)>, StoreError> {
+        let sql = format!("SELECT uuid, data, archived FROM tasks {}", rest);
+        let rows = || -> rusqlite::Result<Vec<(String, String, bool)>> {
+            let mut statement = self.conn.prepare(&sql)?;
+            let rows = statement.query_map(params_from_iter(values), |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+            rows.collect()
+        };
+        let rows = rows().map_err(|e| self.error(e))?;
+
+        rows.into_iter()
+            .map(|(uuid, data, archived)| {
+                let task = serde_json::from_str(&data).map_err(|source| StoreError::InvalidRecord {
+                    path: self.path.clone(),
+                    uuid: uuid.parse().unwrap_or_default(),
+                    source,
+                })?;
+                Ok((task, archived))
+            })
+            .collect()
+    }
+}
+
+/// Due dates are compared as text, so they're all written the same way.
+fn due(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+impl TaskStore for Database {
+    fn load(&mut self) -> Result<(Vec<Task>, Vec<Task>), StoreError> {
+        let mut active = Vec::new();
+        let mut archived = Vec::new();
+        for (task, is_archived) in self.select("ORDER BY archived, id", Vec::new())? {
+            if is_archived { &mut archived } else { &mut active }.push(task);
+        }
+        Ok((active, archived))
+    }
+
+    fn insert(&mut self, task: &Task, archived: bool) -> Result<(), StoreError> {
+        self.write(task, archived)
+    }
+
+    fn update(&mut self, task: &Task) -> Result<(), StoreError> {
+        let archived = self.archived(task.uuid)?;
+        self.write(task, archived)
+    }
+
+    fn delete(&mut self, uuid: Uuid) -> Result<(), StoreError> {
+        self.archived(uuid)?;
+        self.begin()?;
+        let uuid = uuid.to_string();
+        let delete = || -> rusqlite::Result<()> {
+            self.conn.execute("DELETE FROM tasks WHERE uuid = ?1", [&uuid])?;
+            self.conn.execute("DELETE FROM tags WHERE uuid = ?1", [&uuid])?;
+            Ok(())
+        };
+        delete().map_err(|e| self.error(e))
+    }
+
+    fn archive(&mut self, uuid: Uuid, archived: bool) -> Result<(), StoreError> {
+        self.archived(uuid)?;
+        self.begin()?;
+        self.conn
+            .execute(
+                "UPDATE tasks SET archived = ?1 WHERE uuid = ?2",
+                params![archived, uuid.to_string()],
+            )
+            .map_err(|e| self.error(e))?;
+        Ok(())
+    }
+
+    fn query(&mut self, filter: &Filter) -> Result<Vec<Task>, StoreError> {
+        let mut conditions = vec!["archived = ?"];
+        let mut values = vec![Value::from(filter.archived)];
+        for tag in &filter.tags {
+            conditions.push("uuid IN (SELECT uuid FROM tags WHERE tag = ?)");
+            values.push(Value::from(tag.clone()));
+        }
+        if let Some(completed) = filter.completed {
+            conditions.push("completed = ?");
+            values.push(Value::from(completed));
+        }
+        if let Some(before) = filter.due_before {
+            conditions.push("due_date < ?");
+            values.push(Value::from(due(before)));
+        }
+        let rest = format!("WHERE {} ORDER BY id", conditions.join(" AND "));
+        Ok(self.select(&rest, values)?.into_iter().map(|(task, _)| task).collect())
+    }
+
+    fn commit(&mut self) -> Result<(), StoreError> {
+        if self.writing {
+            self.conn.execute_batch("COMMIT").map_err(|e| self.error(e))?;
+            self.writing = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::store::{Backend, Store};
+
+    fn dir() -> PathBuf {
+        std::env::temp_dir().join(format!("todosage-sqlite-{}", Uuid::new_v4()))
+    }
+
+    fn task(id: u32, description: &str, tags: &[&str], due_day: Option<u32>) -> Task {
+        Task {
+            uuid: Uuid::new_v4(),
+            id,
+            description: description.into(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            due_date: due_day.map(|day| Utc.with_ymd_and_hms(2026, 10, day, 9, 0, 0).unwrap()),
+            ..Task::default()
+        }
+    }
+
+    fn ids(tasks: &[Task]) -> Vec<u32> {
+        tasks.iter().map(|t| t.id).collect()
+    }
+
+    #[test]
+    fn changes_round_trip() {
+        let path = dir().join("tasks.db");
+        let mut milk = task(1, "buy milk", &["home"], None);
+        let taxes = task(2, "file taxes", &["work"], Some(20));
+        let old = task(3, "old", &[], None);
+
+        let mut db = Database::open(&path).unwrap();
+        for task in [&milk, &taxes, &old] {
+            db.insert(task, false).unwrap();
+        }
+        db.commit().unwrap();
+        assert_eq!(
+            db.load().unwrap(),
+            (vec![milk.clone(), taxes.clone(), old.clone()], vec![])
+        );
+
+        milk.description = "buy oat milk".into();
+        milk.tags = vec!["shop".into()];
+        db.update(&milk).unwrap();
+        db.archive(old.uuid, true).unwrap();
+        db.delete(taxes.uuid).unwrap();
+        db.commit().unwrap();
+
+        // Read back through a new connection, from what was written.
+        let mut db = Database::open(&path).unwrap();
+        assert_eq!(db.load().unwrap(), (vec![milk.clone()], vec![old.clone()]));
+        assert!(db
+            .query(&Filter {
+                tags: vec!["home".into()],
+                ..Default::default()
+            })
+            .unwrap()
+            .is_empty());
+
+        db.archive(old.uuid, false).unwrap();
+        db.commit().unwrap();
+        assert_eq!(db.load().unwrap(), (vec![milk, old], vec![]));
+        assert!(matches!(db.delete(taxes.uuid), Err(StoreError::MissingTask(_))));
+        assert!(matches!(db.update(&taxes), Err(StoreError::MissingTask(_))));
+    }
+
+    #[test]
+    fn queries_by_tag_and_due_date() {
+        let mut db = Database::open(&dir().join("tasks.db")).unwrap();
+        db.insert(&task(1, "report", &["work", "urgent"], Some(15)), false)
+            .unwrap();
+        db.insert(&task(2, "email", &["work"], Some(25)), false).unwrap();
+        db.insert(&task(3, "milk", &["home"], Some(10)), false).unwrap();
+        db.insert(&task(4, "someday", &["work"], None), false).unwrap();
+        db.insert(&task(1, "archived", &["work"], Some(1)), true).unwrap();
+        db.commit().unwrap();
+
+        let mut query = |tags: &[&str], due_day: Option<u32>, archived| {
+            let filter = Filter {
+                archived,
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                due_before: due_day.map(|day| Utc.with_ymd_and_hms(2026, 10, day, 0, 0, 0).unwrap()),
+                ..Default::default()
+            };
+            ids(&db.query(&filter).unwrap())
+        };
+        assert_eq!(query(&["work"], None, false), [1, 2, 4]);
+        assert_eq!(query(&["work", "urgent"], None, false), [1]);
+        assert_eq!(query(&[], Some(20), false), [1, 3]);
+        assert_eq!(query(&["work"], Some(20), false), [1]);
+        assert_eq!(query(&["work"], None, true), [1]);
+        assert!(query(&["nothing"], None, false).is_empty());
+    }
+
+    #[test]
+    fn migrate_to_sqlite_and_back() {
+        let active = vec![
+            task(1, "buy milk", &["home"], Some(20)),
+            task(2, "file taxes", &[], None),
+        ];
+        let archived = vec![task(1, "old", &["work"], None)];
+
+        let json = Store::open(Some(dir().join("tasks.json")), Backend::Json, 3).unwrap();
+        json.load().unwrap();
+        json.save(&active, &archived).unwrap();
+        assert_eq!(json.migrate(Backend::Sqlite).unwrap(), (2, 1));
+        let sqlite = Store::open(Some(json.location_of(Backend::Json)), Backend::Sqlite, 3).unwrap();
+        assert_eq!(sqlite.load().unwrap(), (active.clone(), archived.clone()));
+        // The target must be empty.
+        assert!(matches!(
+            json.migrate(Backend::Sqlite),
+            Err(StoreError::NotEmpty { .. })
+        ));
+
+        let sqlite = Store::open(Some(dir().join("tasks.json")), Backend::Sqlite, 3).unwrap();
+        sqlite.load().unwrap();
+        sqlite.save(&active, &archived).unwrap();
+        assert_eq!(sqlite.migrate(Backend::Json).unwrap(), (2, 1));
+        let json = Store::open(Some(sqlite.location_of(Backend::Json)), Backend::Json, 3).unwrap();
+        assert_eq!(json.load().unwrap(), (active, archived));
+    }
+}
@@ -0,0 +1,14 @@
+// src/sync.rs
+//
+// Two-way sync of the active task list with other places.
+pub mod caldav;
+pub mod git;
+
+use clap::ValueEnum;
+
+/// Which side wins when a task was changed on both since the last sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Prefer {
+    Local,
+    Remote,
+}
@@ -0,0 +1,1004 @@
+// src/sync/caldav.rs
+//
+// Two-way sync of the active tasks with a CalDAV calendar collection, one
+// VTODO resource per task (see exchange/ical.rs).
+//
+// The sync state (`tasks.caldav.json`) remembers, per UID, the resource's
+// href, the ETag it had and the task as it was when both sides last agreed.
+// Comparing against that tells which side changed a task since: changes on
+// one side are copied to the other, changes on both are conflicts, left
+// alone unless `--prefer` picks a side. Writes carry `If-Match` (or
+// `If-None-Match: *` for new resources), so a task changed on the server
+// while we were syncing is a conflict too rather than being overwritten.
+//
+// Archiving a task counts as deleting it here: the calendar mirrors the
+// active list.
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+use xml::reader::{EventReader, XmlEvent};
+
+use super::Prefer;
+use crate::actions::Action;
+use crate::config::CaldavConfig;
+use crate::exchange::ical;
+use crate::models::{self, renumber, Task};
+use crate::store::{write_atomic, StoreError};
+
+#[derive(Debug, Error)]
+pub enum SyncError {
+    #[error("CalDAV sync isn't configured; set sync.caldav.url in the config file")]
+    NotConfigured,
+    #[error("could not get the CalDAV password from {command:?}: {reason}")]
+    Password { command: String, reason: String },
+    #[error("{method} {url}: {reason}")]
+    Http {
+        method: &'static str,
+        url: String,
+        reason: String,
+    },
+    #[error("{url}: invalid response: {reason}")]
+    Response { url: String, reason: String },
+    #[error("{path} is not a valid sync state: {source}")]
+    State { path: PathBuf, source: serde_json::Error },
+    #[error(transparent)]
+    Store(#[from] StoreError),
+}
+
+/// Where one task was on the server when both sides last agreed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Item {
+    href: String,
+    etag: String,
+    task: Task,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    url: String,
+    /// By UID.
+    items: BTreeMap<String, Item>,
+}
+
+impl State {
+    /// The state for `url`. State saved for another collection is ignored:
+    /// the first sync with a new one matches tasks by UID.
+    fn load(path: &Path, url: &str) -> Result<State, SyncError> {
+        let state = match std::fs::read_to_string(path) {
+            Ok(data) => serde_json::from_str(&data).map_err(|source| SyncError::State {
+                path: path.to_path_buf(),
+                source,
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(source) => {
+                return Err(StoreError::Read {
+                    path: path.to_path_buf(),
+                    source,
+                }
+                .into())
+            }
+        };
+        if state.url == url {
+            return Ok(state);
+        }
+        Ok(State {
+            url: url.to_string(),
+            items: BTreeMap::new(),
+        })
+    }
+
+    fn save(&self, path: &Path) -> Result<(), SyncError> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| StoreError::Write {
+            path: path.to_path_buf(),
+            source: e.into(),
+        })?;
+        write_atomic(path, json.as_bytes(), || Ok(()))?;
+        Ok(())
+    }
+}
+
+/// A VTODO resource on the server.
+struct Remote {
+    uid: String,
+    href: String,
+    etag: String,
+    /// `None` when the resource isn't a task we can read, or is CANCELLED.
+    task: Option<Task>,
+}
+
+/// What happened, for the report.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub created: usize,
+    pub pushed: usize,
+    pub deleted_remote: usize,
+    pub added: usize,
+    pub pulled: usize,
+    pub deleted_local: usize,
+    pub conflicts: Vec<String>,
+    /// Resources that couldn't be read, with why.
+    pub skipped: Vec<String>,
+}
+
+/// One step of a sync.
+enum Step {
+    /// Write the local task to the server; `etag` is the version we expect
+    /// to replace, `None` for a new resource.
+    Put {
+        uid: String,
+        href: String,
+        etag: Option<String>,
+    },
+    DeleteRemote {
+        uid: String,
+        href: String,
+        etag: String,
+    },
+    /// Add the server's task to the active list.
+    Add {
+        uid: String,
+    },
+    /// Replace the local task with the server's.
+    Pull {
+        uid: String,
+    },
+    DeleteLocal {
+        uid: String,
+    },
+    /// Both sides agree already; just remember it.
+    Remember {
+        uid: String,
+    },
+    Forget {
+        uid: String,
+    },
+    Conflict {
+        uid: String,
+        reason: &'static str,
+    },
+}
+
+struct Client {
+    agent: ureq::Agent,
+    /// `https://host:port`
+    origin: String,
+    /// The collection's path, ending with `/`.
+    path: String,
+    auth: Option<String>,
+}
+
+impl Client {
+    fn new(config: &CaldavConfig) -> Result<Client, SyncError> {
+        let url = config.url.trim();
+        let after_scheme = url.find("://").map(|i| i + 3).ok_or_else(|| SyncError::Response {
+            url: url.to_string(),
+            reason: "expected an http:// or https:// URL".into(),
+        })?;
+        let path_start = url[after_scheme..].find('/').map_or(url.len(), |i| after_scheme + i);
+        let mut path = url[path_start..].to_string();
+        if !path.ends_with('/') {
+            path.push('/');
+        }
+
+        let auth = match &config.username {
+            Some(username) => {
+                let password = password(config)?;
+                let credentials = format!("{}:{}", username, password);
+                Some(format!(
+                    "Basic {}",
+                    base64::engine::general_purpose::STANDARD.encode(credentials)
+                ))
+            }
+            None => None,
+        };
+
+        Ok(Client {
+            agent: ureq::AgentBuilder::new().timeout(Duration::from_secs(30)).build(),
+            origin: url[..path_start].to_string(),
+            path,
+            auth,
+        })
+    }
+
+    fn url(&self, href: &str) -> String {
+        if href.starts_with("http://") || href.starts_with("https://") {
+            href.to_string()
+        } else {
+            format!("{}{}", self.origin, href)
+        }
+    }
+
+    fn request(&self, method: &'static str, href: &str) -> ureq::Request {
+        let request = self.agent.request(method, &self.url(href));
+        match &self.auth {
+            Some(auth) => request.set("Authorization", auth),
+            None => request,
+        }
+    }
+
+    fn http_error(&self, method: &'static str, href: &str, error: ureq::Error) -> SyncError {
+        let reason = match error {
+            ureq::Error::Status(status, response) => format!("{} {}", status, response.status_text()),
+            ureq::Error::Transport(transport) => transport.to_string(),
+        };
+        SyncError::Http {
+            method,
+            url: self.url(href),
+            reason,
+        }
+    }
+
+    /// Every VTODO in the collection, with its ETag.
+    fn list(&self, report: &mut Report) -> Result<Vec<Remote>, SyncError> {
+        const QUERY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><d:getetag/><c:calendar-data/></d:prop>
+  <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VTODO"/></c:comp-filter></c:filter>
+</c:calendar-query>"#;
+        let response = self
+            .request("REPORT", &self.path)
+            .set("Depth", "1")
+            .set("Content-Type", "application/xml; charset=utf-8")
+            .send_string(QUERY)
+            .map_err(|e| self.http_error("REPORT", &self.path, e))?;
+        let body = response.into_string().map_err(|e| SyncError::Response {
+            url: self.url(&self.path),
+            reason: e.to_string(),
+        })?;
+
+        let responses = parse_multistatus(&body).map_err(|reason| SyncError::Response {
+            url: self.url(&self.path),
+            reason,
+        })?;
+        let mut remotes = Vec::new();
+        for response in responses {
+            let (href, Some(etag), Some(data)) = (response.href, response.etag, response.calendar_data) else {
+                continue;
+            };
+            let parsed = ical::parse(&data);
+            let (uid, task) = match (parsed.tasks.into_iter().next(), parsed.archived.first()) {
+                (Some(task), _) => (task.ical_uid(), Some(task)),
+                // CANCELLED, which counts as deleted.
+                (None, Some(cancelled)) => (cancelled.ical_uid(), None),
+                (None, None) => {
+                    let reason = parsed.errors.first().map_or("no VTODO", |e| e.message.as_str());
+                    report.skipped.push(format!("{}: {}", href, reason));
+                    continue;
+                }
+            };
+            remotes.push(Remote { uid, href, etag, task });
+        }
+        Ok(remotes)
+    }
+
+    /// Writes `task` to `href`, returning its new ETag, or `None` when the
+    /// resource changed on the server since `etag`.
+    fn put(&self, href: &str, task: &Task, etag: Option<&str>) -> Result<Option<String>, SyncError> {
+        let request = self
+            .request("PUT", href)
+            .set("Content-Type", "text/calendar; charset=utf-8");
+        let request = match etag {
+            Some(etag) => request.set("If-Match", etag),
+            None => request.set("If-None-Match", "*"),
+        };
+        match request.send_string(&ical::calendar([(task, false)])) {
+            Ok(response) => match response.header("ETag") {
+                Some(etag) => Ok(Some(etag.to_string())),
+                // Some servers only tell on request.
+                None => self.etag(href).map(Some),
+            },
+            Err(ureq::Error::Status(412, _)) => Ok(None),
+            Err(e) => Err(self.http_error("PUT", href, e)),
+        }
+    }
+
+    fn etag(&self, href: &str) -> Result<String, SyncError> {
+        let response = self
+            .request("GET", href)
+            .call()
+            .map_err(|e| self.http_error("GET", href, e))?;
+        response
+            .header("ETag")
+            .map(str::to_string)
+            .ok_or_else(|| SyncError::Response {
+                url: self.url(href),
+                reason: "no ETag".into(),
+            })
+    }
+
+    /// Deletes `href` unless it changed since `etag`. Returns whether it's gone.
+    fn delete(&self, href: &str, etag: &str) -> Result<bool, SyncError> {
+        match self.request("DELETE", href).set("If-Match", etag).call() {
+            Ok(_) | Err(ureq::Error::Status(404, _)) => Ok(true),
+            Err(ureq::Error::Status(412, _)) => Ok(false),
+            Err(e) => Err(self.http_error("DELETE", href, e)),
+        }
+    }
+
+    /// Where a new task goes: `<collection>/<uid>.ics`.
+    fn href_for(&self, uid: &str) -> String {
+        let name: String = uid
+            .bytes()
+            .map(|b| match b {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' => (b as char).to_string(),
+                _ => format!("%{:02X}", b),
+            })
+            .collect();
+        format!("{}{}.ics", self.path, name)
+    }
+}
+
+fn password(config: &CaldavConfig) -> Result<String, SyncError> {
+    if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        return Ok(password);
+    }
+    if let Some(password) = &config.password {
+        return Ok(password.clone());
+    }
+    let Some(command) = &config.password_command else {
+        return Ok(String::new());
+    };
+    let error = |reason: String| SyncError::Password {
+        command: command.clone(),
+        reason,
+    };
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .map_err(|e| error(e.to_string()))?;
+    if !output.status.success() {
+        return Err(error(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    let stdout = String::from_utf8(output.stdout).map_err(|e| error(e.to_string()))?;
+    Ok(stdout.lines().next().unwrap_or_default().to_string())
+}
+
+/// Overrides the configured password.
+pub const PASSWORD_ENV: &str = "TODOSAGE_CALDAV_PASSWORD";
+
+/// A `<response>` of a WebDAV multistatus.
+#[derive(Default)]
+struct Response {
+    href: String,
+    etag: Option<String>,
+    calendar_data: Option<String>,
+}
+
+/// Reads a multistatus. Properties only count from a `<propstat>` whose
+/// status is 200.
+fn parse_multistatus(body: &str) -> Result<Vec<Response>, String> {
+    let mut responses = Vec::new();
+    let mut current: Option<Response> = None;
+    let mut propstat: Option<(bool, Response)> = None;
+    let mut text = String::new();
+
+    for event in EventReader::from_str(body) {
+        match event.map_err(|e| e.to_string())? {
+            XmlEvent::StartElement { name, .. } => {
+                match name.local_name.as_str() {
+                    "response" => current = Some(Response::default()),
+                    "propstat" => propstat = Some((true, Response::default())),
+                    _ => {}
+                }
+                text.clear();
+            }
+            XmlEvent::Characters(chars) | XmlEvent::CData(chars) => text.push_str(&chars),
+            XmlEvent::EndElement { name } => {
+                let value = std::mem::take(&mut text);
+                match (name.local_name.as_str(), &mut current, &mut propstat) {
+                    ("href", Some(response), None) => response.href = value.trim().to_string(),
+                    ("status", _, Some((ok, _))) => *ok = value.split_whitespace().nth(1) == Some("200"),
+                    ("getetag", _, Some((_, props))) => props.etag = Some(value.trim().to_string()),
+                    ("calendar-data", _, Some((_, props))) => props.calendar_data = Some(value),
+                    ("propstat", Some(response), Some(_)) => {
+                        if let Some((true, props)) = propstat.take() {
+                            response.etag = response.etag.take().or(props.etag);
+                            response.calendar_data = response.calendar_data.take().or(props.calendar_data);
+                        }
+                    }
+                    ("response", Some(_), _) => responses.extend(current.take()),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(responses)
+}
+
+/// The fields a VTODO carries. A task counts as changed when these differ.
+fn synced(task: &Task) -> impl PartialEq + '_ {
+    (
+        &task.description,
+        task.due_date,
+        &task.priority,
+        &task.tags,
+        task.completed,
+        &task.recurrence,
+        task.parent,
+    )
+}
+
+/// The local task with the server's version of the synced fields.
+fn pulled(local: &Task, remote: &Task) -> Task {
+    Task {
+        description: remote.description.clone(),
+        due_date: remote.due_date,
+        priority: remote.priority.clone(),
+        tags: remote.tags.clone(),
+        completed: remote.completed,
+        completed_at: if remote.completed {
+            remote.completed_at.or(local.completed_at)
+        } else {
+            None
+        },
+        recurrence: remote.recurrence.clone(),
+        parent: remote.parent,
+        ..local.clone()
+    }
+}
+
+/// Syncs `tasks` with the configured collection. Local changes are
+/// returned as one action to record. With `dry_run` nothing is changed on
+/// either side, and the report says what would have been.
+pub fn sync(
+    config: Option<&CaldavConfig>,
+    state_path: PathBuf,
+    tasks: &mut Vec<Task>,
+    archived: &[Task],
+    prefer: Option<Prefer>,
+    dry_run: bool,
+) -> Result<(Report, Option<Action>), SyncError> {
+    let config = config.ok_or(SyncError::NotConfigured)?;
+    let client = Client::new(config)?;
+    let mut state = State::load(&state_path, &config.url)?;
+    let mut report = Report::default();
+
+    let remotes: BTreeMap<String, Remote> = client
+        .list(&mut report)?
+        .into_iter()
+        .map(|remote| (remote.uid.clone(), remote))
+        .collect();
+    let locals: BTreeMap<String, usize> = tasks.iter().enumerate().map(|(i, t)| (t.ical_uid(), i)).collect();
+    let archived_uids: BTreeSet<String> = archived.iter().map(Task::ical_uid).collect();
+
+    let steps = plan(&client, &state, &locals, &remotes, &archived_uids, tasks, prefer);
+    if dry_run {
+        for step in &steps {
+            tally(&mut report, step, tasks, &remotes);
+        }
+        return Ok((report, None));
+    }
+
+    // Indices shift as tasks are deleted; uuids don't.
+    let uuids: BTreeMap<&String, Uuid> = locals.iter().map(|(uid, &i)| (uid, tasks[i].uuid)).collect();
+    let local = |uid: &String| uuids.get(uid).copied().expect("a local task");
+    let mut actions = Vec::new();
+    let mut added = Vec::new();
+    for mut step in steps {
+        // A write refused because of the ETag: someone else was quicker.
+        let raced = |uid: &String| Step::Conflict {
+            uid: uid.clone(),
+            reason: "changed on the server during the sync",
+        };
+        match &step {
+            Step::Put { uid, href, etag } => {
+                let index = models::position(tasks, local(uid)).expect("a local task");
+                match client.put(href, &tasks[index], etag.as_deref())? {
+                    Some(new_etag) => {
+                        state.items.insert(
+                            uid.clone(),
+                            Item {
+                                href: href.clone(),
+                                etag: new_etag,
+                                task: tasks[index].clone(),
+                            },
+                        );
+                    }
+                    None => step = raced(uid),
+                }
+            }
+            Step::DeleteRemote { uid, href, etag } => match client.delete(href, etag)? {
+                true => {
+                    state.items.remove(uid);
+                }
+                false => step = raced(uid),
+            },
+            Step::Add { uid } => {
+                let remote = &remotes[uid];
+                let task = remote.task.clone().expect("a readable remote task");
+                state.items.insert(
+                    uid.clone(),
+                    Item {
+                        href: remote.href.clone(),
+                        etag: remote.etag.clone(),
+                        task: task.clone(),
+                    },
+                );
+                added.push(task);
+            }
+            Step::Pull { uid } => {
+                let remote = &remotes[uid];
+                let index = models::position(tasks, local(uid)).expect("a local task");
+                let before = tasks[index].clone();
+                tasks[index] = pulled(&before, remote.task.as_ref().expect("a readable remote task"));
+                actions.extend(Action::for_edit(&before, &tasks[index]));
+                state.items.insert(
+                    uid.clone(),
+                    Item {
+                        href: remote.href.clone(),
+                        etag: remote.etag.clone(),
+                        task: tasks[index].clone(),
+                    },
+                );
+            }
+            Step::DeleteLocal { uid } => {
+                let index = models::position(tasks, local(uid)).expect("a local task");
+                actions.push(Action::Delete(tasks.remove(index)));
+                renumber(tasks);
+                state.items.remove(uid);
+            }
+            Step::Remember { uid } => {
+                let remote = &remotes[uid];
+                let index = models::position(tasks, local(uid)).expect("a local task");
+                state.items.insert(
+                    uid.clone(),
+                    Item {
+                        href: remote.href.clone(),
+                        etag: remote.etag.clone(),
+                        task: tasks[index].clone(),
+                    },
+                );
+            }
+            Step::Forget { uid } => {
+                state.items.remove(uid);
+            }
+            Step::Conflict { .. } => {}
+        }
+        tally(&mut report, &step, tasks, &remotes);
+    }
+
+    if !added.is_empty() {
+        for task in &mut added {
+            task.created.get_or_insert_with(chrono::Utc::now);
+        }
+        let start = tasks.len();
+        tasks.extend(added);
+        renumber(tasks);
+        actions.push(Action::Import {
+            tasks: tasks[start..].to_vec(),
+            archived: Vec::new(),
+        });
+    }
+    state.save(&state_path)?;
+
+    let action = match actions.len() {
+        0 => None,
+        1 => actions.pop(),
+        _ => Some(Action::Batch(actions)),
+    };
+    Ok((report, action))
+}
+
+/// Decides what to do with every UID known to either side or the state.
+fn plan(
+    client: &Client,
+    state: &State,
+    locals: &BTreeMap<String, usize>,
+    remotes: &BTreeMap<String, Remote>,
+    archived: &BTreeSet<String>,
+    tasks: &[Task],
+    prefer: Option<Prefer>,
+) -> Vec<Step> {
+    let uids: BTreeSet<&String> = locals.keys().chain(remotes.keys()).chain(state.items.keys()).collect();
+    let mut steps = Vec::new();
+
+    for uid in uids {
+        let uid = uid.clone();
+        let local = locals.get(&uid).map(|&i| &tasks[i]);
+        let remote = remotes.get(&uid);
+        let item = state.items.get(&uid);
+        let local_changed = match (local, item) {
+            (Some(local), Some(item)) => synced(local) != synced(&item.task),
+            _ => true,
+        };
+        let remote_changed = match (remote, item) {
+            (Some(remote), Some(item)) => remote.etag != item.etag,
+            _ => true,
+        };
+        let readable = remote.is_some_and(|r| r.task.is_some());
+
+        let step = match (local, item, remote) {
+            // New here.
+            (Some(_), None, None) => Step::Put {
+                href: client.href_for(&uid),
+                uid,
+                etag: None,
+            },
+            // On both sides already, never synced.
+            (Some(local), None, Some(remote)) => match &remote.task {
+                Some(task) if synced(task) == synced(local) => Step::Remember { uid },
+                _ => resolve(uid, "differs here and on the server", remote, prefer),
+            },
+            // New on the server. Tasks archived here stay there.
+            (None, None, Some(_)) if readable && !archived.contains(&uid) => Step::Add { uid },
+            (None, None, Some(_)) => continue,
+            (Some(_), Some(item), Some(remote)) => match (local_changed, remote_changed) {
+                (false, false) => continue,
+                (true, false) => Step::Put {
+                    uid,
+                    href: item.href.clone(),
+                    etag: Some(item.etag.clone()),
+                },
+                (false, true) if readable => Step::Pull { uid },
+                (false, true) => Step::DeleteLocal { uid },
+                (true, true) => match &remote.task {
+                    Some(task) if synced(task) == synced(local.expect("a local task")) => Step::Remember { uid },
+                    _ => resolve(uid, "changed here and on the server", remote, prefer),
+                },
+            },
+            // Deleted on the server.
+            (Some(_), Some(_), None) => match (local_changed, prefer) {
+                (false, _) | (true, Some(Prefer::Remote)) => Step::DeleteLocal { uid },
+                (true, Some(Prefer::Local)) => Step::Put {
+                    href: client.href_for(&uid),
+                    uid,
+                    etag: None,
+                },
+                (true, None) => Step::Conflict {
+                    uid,
+                    reason: "changed here but deleted on the server",
+                },
+            },
+            // Deleted (or archived) here.
+            (None, Some(item), Some(remote)) => match (remote_changed, prefer) {
+                (false, _) | (true, Some(Prefer::Local)) => Step::DeleteRemote {
+                    uid,
+                    href: item.href.clone(),
+                    etag: remote.etag.clone(),
+                },
+                (true, Some(Prefer::Remote)) if readable && !archived.contains(&uid) => Step::Add { uid },
+                (true, Some(Prefer::Remote)) => Step::Forget { uid },
+                (true, None) => Step::Conflict {
+                    uid,
+                    reason: "deleted here but changed on the server",
+                },
+            },
+            (None, Some(_), None) => Step::Forget { uid },
+            (None, None, None) => continue,
+        };
+        steps.push(step);
+    }
+    steps
+}
+
+/// A task that differs on both sides: `prefer` decides, or it's a conflict.
+fn resolve(uid: String, reason: &'static str, remote: &Remote, prefer: Option<Prefer>) -> Step {
+    match prefer {
+        Some(Prefer::Local) => Step::Put {
+            uid,
+            href: remote.href.clone(),
+            etag: Some(remote.etag.clone()),
+        },
+        Some(Prefer::Remote) if remote.task.is_some() => Step::Pull { uid },
+        Some(Prefer::Remote) => Step::DeleteLocal { uid },
+        None => Step::Conflict { uid, reason },
+    }
+}
+
+fn tally(report: &mut Report, step: &Step, tasks: &[Task], remotes: &BTreeMap<String, Remote>) {
+    match step {
+        Step::Put { etag: None, .. } => report.created += 1,
+        Step::Put { .. } => report.pushed += 1,
+        Step::DeleteRemote { .. } => report.deleted_remote += 1,
+        Step::Add { .. } => report.added += 1,
+        Step::Pull { .. } => report.pulled += 1,
+        Step::DeleteLocal { .. } => report.deleted_local += 1,
+        Step::Remember { .. } | Step::Forget { .. } => {}
+        Step::Conflict { uid, reason } => {
+            let name = tasks
+                .iter()
+                .find(|t| t.ical_uid() == *uid)
+                .map(|t| format!("{} ({}) {:?}", t.id, t.short_id(), t.description))
+                .or_else(|| {
+                    let remote = remotes.get(uid)?;
+                    Some(format!("{:?}", remote.task.as_ref()?.description))
+                })
+                .unwrap_or_else(|| uid.clone());
+            report.conflicts.push(format!("{}: {}", name, reason));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    use super::*;
+
+    /// A REPORT response as Radicale sends it, plus a resource whose
+    /// calendar data the server couldn't give and one in CDATA, as
+    /// Nextcloud writes them.
+    const MULTISTATUS: &str = r#"<?xml version='1.0' encoding='utf-8'?>
+<multistatus xmlns="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <response>
+    <href>/user/tasks/a.ics</href>
+    <propstat>
+      <prop>
+        <getetag>"e1"</getetag>
+        <C:calendar-data>BEGIN:VCALENDAR
+VERSION:2.0
+BEGIN:VTODO
+UID:task-a
+SUMMARY:Water the plants
+STATUS:NEEDS-ACTION
+END:VTODO
+END:VCALENDAR
+</C:calendar-data>
+      </prop>
+      <status>HTTP/1.1 200 OK</status>
+    </propstat>
+  </response>
+  <response>
+    <href>/user/tasks/b.ics</href>
+    <propstat>
+      <prop><getetag>"e2"</getetag></prop>
+      <status>HTTP/1.1 200 OK</status>
+    </propstat>
+    <propstat>
+      <prop><C:calendar-data/></prop>
+      <status>HTTP/1.1 404 Not Found</status>
+    </propstat>
+  </response>
+  <response>
+    <href>/user/tasks/c.ics</href>
+    <propstat>
+      <prop>
+        <getetag>"e3"</getetag>
+        <C:calendar-data><![CDATA[BEGIN:VCALENDAR
+BEGIN:VEVENT
+UID:event-c
+END:VEVENT
+END:VCALENDAR
+]]></C:calendar-data>
+      </prop>
+      <status>HTTP/1.1 200 OK</status>
+    </propstat>
+  </response>
+</multistatus>
+"#;
+
+    fn reply(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            headers,
+            body.len(),
+            body
+        )
+    }
+
+    /// Answers one connection with each of `replies` in turn, and returns
+    /// the collection's URL and the requests it got.
+    fn serve(replies: Vec<String>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/user/tasks/", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for reply in replies {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    request.push_str(&line);
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                request.push_str(&String::from_utf8_lossy(&body));
+                stream.write_all(reply.as_bytes()).unwrap();
+                requests.push(request);
+            }
+            requests
+        });
+        (url, server)
+    }
+
+    fn client(url: &str) -> Client {
+        Client::new(&CaldavConfig {
+            url: url.into(),
+            ..CaldavConfig::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn multistatus_keeps_the_properties_that_were_found() {
+        let responses = parse_multistatus(MULTISTATUS).unwrap();
+        let hrefs: Vec<&str> = responses.iter().map(|r| r.href.as_str()).collect();
+        assert_eq!(hrefs, ["/user/tasks/a.ics", "/user/tasks/b.ics", "/user/tasks/c.ics"]);
+        let etags: Vec<Option<&str>> = responses.iter().map(|r| r.etag.as_deref()).collect();
+        assert_eq!(etags, [Some("\"e1\""), Some("\"e2\""), Some("\"e3\"")]);
+        assert!(responses[0].calendar_data.as_ref().unwrap().contains("UID:task-a"));
+        assert_eq!(responses[1].calendar_data, None);
+        assert!(responses[2]
+            .calendar_data
+            .as_ref()
+            .unwrap()
+            .starts_with("BEGIN:VCALENDAR"));
+
+        assert!(parse_multistatus("<multistatus><response>").is_err());
+    }
+
+    #[test]
+    fn list_reads_the_tasks_of_a_report() {
+        let (url, server) = serve(vec![reply("207 Multi-Status", "", MULTISTATUS)]);
+        let mut report = Report::default();
+        let remotes = client(&url).list(&mut report).unwrap();
+
+        let request = &server.join().unwrap()[0];
+        assert!(request.starts_with("REPORT /user/tasks/ HTTP/1.1\r\n"), "{}", request);
+        assert!(request.contains("Depth: 1\r\n"));
+        assert_eq!(remotes.len(), 1);
+        assert_eq!(
+            (remotes[0].uid.as_str(), remotes[0].etag.as_str()),
+            ("task-a", "\"e1\"")
+        );
+        assert_eq!(remotes[0].task.as_ref().unwrap().description, "Water the plants");
+        assert_eq!(report.skipped, ["/user/tasks/c.ics: no VTODO"]);
+    }
+
+    #[test]
+    fn put_skips_when_the_etag_is_stale() {
+        let (url, server) = serve(vec![
+            reply("412 Precondition Failed", "", ""),
+            reply("201 Created", "ETag: \"e9\"\r\n", ""),
+        ]);
+        let client = client(&url);
+        let task = Task {
+            description: "Water the plants".into(),
+            ..Task::default()
+        };
+        assert_eq!(client.put("/user/tasks/a.ics", &task, Some("\"e1\"")).unwrap(), None);
+        let href = client.href_for("new task");
+        assert_eq!(href, "/user/tasks/new%20task.ics");
+        assert_eq!(client.put(&href, &task, None).unwrap().as_deref(), Some("\"e9\""));
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("PUT /user/tasks/a.ics "));
+        assert!(requests[0].contains("If-Match: \"e1\"\r\n"));
+        assert!(requests[1].starts_with("PUT /user/tasks/new%20task.ics "));
+        assert!(requests[1].contains("If-None-Match: *\r\n"));
+        assert!(requests[1].contains("SUMMARY:Water the plants"));
+    }
+
+    /// What `plan` decided, in short.
+    fn planned(steps: &[Step]) -> Vec<String> {
+        steps
+            .iter()
+            .map(|step| match step {
+                Step::Put {
+                    href, etag: Some(etag), ..
+                } => format!("put {} If-Match {}", href, etag),
+                Step::Put { href, etag: None, .. } => format!("put {} If-None-Match *", href),
+                Step::DeleteRemote { href, etag, .. } => format!("delete {} {}", href, etag),
+                Step::Add { uid } => format!("add {}", uid),
+                Step::Pull { uid } => format!("pull {}", uid),
+                Step::DeleteLocal { uid } => format!("delete local {}", uid),
+                Step::Remember { uid } => format!("remember {}", uid),
+                Step::Forget { uid } => format!("forget {}", uid),
+                Step::Conflict { uid, reason } => format!("conflict {}: {}", uid, reason),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plan_uploads_only_over_the_etag_it_saw() {
+        let client = client("http://localhost:5232/user/tasks/");
+        let synced = Task {
+            uuid: Uuid::new_v4(),
+            description: "Water the plants".into(),
+            ..Task::default()
+        };
+        let uid = synced.ical_uid();
+        let state = State {
+            url: client.url(&client.path),
+            items: BTreeMap::from([(
+                uid.clone(),
+                Item {
+                    href: "/user/tasks/a.ics".into(),
+                    etag: "\"e1\"".into(),
+                    task: synced.clone(),
+                },
+            )]),
+        };
+        let edited = Task {
+            description: "Water the roses".into(),
+            ..synced.clone()
+        };
+        let remote = |etag: &str, task: &Task| {
+            BTreeMap::from([(
+                uid.clone(),
+                Remote {
+                    uid: uid.clone(),
+                    href: "/user/tasks/a.ics".into(),
+                    etag: etag.into(),
+                    task: Some(task.clone()),
+                },
+            )])
+        };
+        let locals = BTreeMap::from([(uid.clone(), 0)]);
+        let archived = BTreeSet::new();
+        let decide = |tasks: &[Task], remotes: &BTreeMap<String, Remote>, prefer| {
+            planned(&plan(&client, &state, &locals, remotes, &archived, tasks, prefer))
+        };
+
+        let tasks = [synced.clone()];
+        assert!(decide(&tasks, &remote("\"e1\"", &synced), None).is_empty());
+        assert_eq!(
+            decide(&tasks, &remote("\"e2\"", &edited), None),
+            [format!("pull {}", uid)]
+        );
+
+        let tasks = [edited.clone()];
+        assert_eq!(
+            decide(&tasks, &remote("\"e1\"", &synced), None),
+            [r#"put /user/tasks/a.ics If-Match "e1""#]
+        );
+        let other = Task {
+            description: "Water the lawn".into(),
+            ..synced.clone()
+        };
+        assert_eq!(
+            decide(&tasks, &remote("\"e2\"", &other), None),
+            [format!("conflict {}: changed here and on the server", uid)]
+        );
+        assert_eq!(
+            decide(&tasks, &remote("\"e2\"", &other), Some(Prefer::Local)),
+            [r#"put /user/tasks/a.ics If-Match "e2""#]
+        );
+        // The same edit on both sides needs no upload.
+        assert_eq!(
+            decide(&tasks, &remote("\"e2\"", &edited), None),
+            [format!("remember {}", uid)]
+        );
+
+        let tasks = [Task {
+            uuid: Uuid::new_v4(),
+            description: "Repot the fern".into(),
+            ..Task::default()
+        }];
+        let new = tasks[0].ical_uid();
+        let locals = BTreeMap::from([(new.clone(), 0)]);
+        let steps = plan(
+            &client,
+            &State::default(),
+            &locals,
+            &BTreeMap::new(),
+            &archived,
+            &tasks,
+            None,
+        );
+        assert_eq!(
+            planned(&steps),
+            [format!("put /user/tasks/{}.ics If-None-Match *", new)]
+        );
+    }
+}
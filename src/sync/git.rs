@@ -0,0 +1,730 @@
+// src/sync/git.rs
+//
+// Sync through a git repository: the store is committed to a file in a
+// local clone, which is pulled from and pushed to its remote.
+//
+// Git's own textual merge doesn't understand the store, so the file is
+// merged here, task by task: the sync state (`tasks.git.json`) remembers
+// the commit of the last sync, whose copy of the file is the common
+// ancestor of the tasks here and on the remote. A field changed on one side
+// since then takes that side's value; a field changed differently on both
+// is a conflict, settled for the side `--prefer` picks, or this one. A task
+// deleted on one side and changed on the other is kept unless `--prefer`
+// picks the side that deleted it.
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::Prefer;
+use crate::actions::Action;
+use crate::config::GitConfig;
+use crate::models::{renumber, Task};
+use crate::store::{write_atomic, StoreError};
+
+#[derive(Debug, Error)]
+pub enum SyncError {
+    #[error("git sync isn't configured; set sync.git.repo in the config file")]
+    NotConfigured,
+    #[error("could not run git: {0}")]
+    NoGit(std::io::Error),
+    #[error("git {command}: {reason}")]
+    Git { command: String, reason: String },
+    #[error("{file} in {commit} is not a task store: {source}")]
+    File {
+        file: String,
+        commit: String,
+        source: serde_json::Error,
+    },
+    #[error("{path} is not a valid sync state: {source}")]
+    State { path: PathBuf, source: serde_json::Error },
+    #[error(transparent)]
+    Store(#[from] StoreError),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    repo: PathBuf,
+    file: String,
+    /// The commit made by the last sync.
+    commit: Option<String>,
+}
+
+impl State {
+    /// The state for `file` in `repo`. State saved for another file is
+    /// ignored: the first sync with a new one has no common ancestor.
+    fn load(path: &Path, config: &GitConfig) -> Result<State, SyncError> {
+        let state = match std::fs::read_to_string(path) {
+            Ok(data) => serde_json::from_str(&data).map_err(|source| SyncError::State {
+                path: path.to_path_buf(),
+                source,
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(source) => {
+                return Err(StoreError::Read {
+                    path: path.to_path_buf(),
+                    source,
+                }
+                .into())
+            }
+        };
+        if state.repo == config.repo && state.file == config.file {
+            return Ok(state);
+        }
+        Ok(State {
+            repo: config.repo.clone(),
+            file: config.file.clone(),
+            commit: None,
+        })
+    }
+
+    fn save(&self, path: &Path) -> Result<(), SyncError> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| StoreError::Write {
+            path: path.to_path_buf(),
+            source: e.into(),
+        })?;
+        write_atomic(path, json.as_bytes(), || Ok(()))?;
+        Ok(())
+    }
+}
+
+/// Both lists, as the store file holds them.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Lists {
+    #[serde(default)]
+    active: Vec<Task>,
+    #[serde(default)]
+    archived: Vec<Task>,
+}
+
+/// What happened, for the report.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub added: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    /// The commit pushed, when there was anything to push.
+    pub pushed: Option<String>,
+    pub conflicts: Vec<String>,
+}
+
+struct Git<'a> {
+    repo: &'a Path,
+}
+
+impl Git<'_> {
+    fn output(&self, args: &[&str]) -> Result<Output, SyncError> {
+        Command::new("git")
+            .arg("-C")
+            .arg(self.repo)
+            .args(args)
+            .output()
+            .map_err(SyncError::NoGit)
+    }
+
+    fn error(&self, args: &[&str], output: &Output) -> SyncError {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        SyncError::Git {
+            command: args.join(" "),
+            reason: stderr
+                .lines()
+                .find(|l| !l.trim().is_empty())
+                .unwrap_or("failed")
+                .trim()
+                .to_string(),
+        }
+    }
+
+    /// Runs git, returning what it printed.
+    fn run(&self, args: &[&str]) -> Result<String, SyncError> {
+        let output = self.output(args)?;
+        if !output.status.success() {
+            return Err(self.error(args, &output));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Runs a git command that answers with its exit status.
+    fn test(&self, args: &[&str]) -> Result<bool, SyncError> {
+        let output = self.output(args)?;
+        match output.status.code() {
+            Some(0) => Ok(true),
+            Some(1) => Ok(false),
+            _ => Err(self.error(args, &output)),
+        }
+    }
+
+    /// `file` as it is in `commit`, if it's there.
+    fn read(&self, commit: &str, file: &str) -> Result<Option<Lists>, SyncError> {
+        if self.run(&["ls-tree", "--name-only", commit, "--", file])?.is_empty() {
+            return Ok(None);
+        }
+        let data = self.run(&["show", &format!("{}:{}", commit, file)])?;
+        let lists = serde_json::from_str(&data).map_err(|source| SyncError::File {
+            file: file.to_string(),
+            commit: commit[..commit.len().min(8)].to_string(),
+            source,
+        })?;
+        Ok(Some(lists))
+    }
+}
+
+/// Merges the tasks with the copy on the remote, then commits the result
+/// and pushes it. Local changes are returned as one action to record. With
+/// `dry_run` nothing is changed, and the report says what would have been.
+pub fn sync(
+    config: Option<&GitConfig>,
+    state_path: PathBuf,
+    tasks: &mut Vec<Task>,
+    archived: &mut Vec<Task>,
+    prefer: Option<Prefer>,
+    dry_run: bool,
+) -> Result<(Report, Option<Action>), SyncError> {
+    let config = config.ok_or(SyncError::NotConfigured)?;
+    let git = Git { repo: &config.repo };
+    git.run(&["rev-parse", "--git-dir"])?;
+    let mut state = State::load(&state_path, config)?;
+    let branch = match &config.branch {
+        Some(branch) => branch.clone(),
+        None => git.run(&["symbolic-ref", "--short", "HEAD"])?,
+    };
+
+    // A new, empty remote has no branch to fetch yet.
+    let remote_commit = if git.run(&["ls-remote", "--heads", &config.remote, &branch])?.is_empty() {
+        None
+    } else {
+        git.run(&["fetch", "--quiet", &config.remote, &branch])?;
+        Some(git.run(&["rev-parse", "FETCH_HEAD"])?)
+    };
+    let theirs = match &remote_commit {
+        Some(commit) => git.read(commit, &config.file)?.unwrap_or_default(),
+        None => Lists::default(),
+    };
+    // The last sync's commit may be gone, say after a history rewrite.
+    let base = match &state.commit {
+        Some(commit)
+            if git
+                .output(&["cat-file", "-e", &format!("{}^{{commit}}", commit)])?
+                .status
+                .success() =>
+        {
+            git.read(commit, &config.file)?
+        }
+        _ => None,
+    };
+    let ours = Lists {
+        active: tasks.clone(),
+        archived: archived.clone(),
+    };
+
+    let mut report = Report::default();
+    let merged = merge(base.as_ref(), &ours, &theirs, prefer, &mut report);
+    if dry_run {
+        return Ok((report, None));
+    }
+
+    // Bring in the remote's commits first, for the rest of the repository;
+    // the task file is overwritten with the merged tasks right after.
+    if let Some(commit) = &remote_commit {
+        if !git
+            .test(&["merge-base", "--is-ancestor", commit, "HEAD"])
+            .unwrap_or(false)
+        {
+            if let Err(e) = git.run(&["merge", "--quiet", "--no-edit", "-X", "ours", commit]) {
+                let _ = git.run(&["merge", "--abort"]);
+                return Err(e);
+            }
+        }
+    }
+    let json = serde_json::to_string_pretty(&merged).map_err(|e| StoreError::Write {
+        path: config.repo.join(&config.file),
+        source: e.into(),
+    })?;
+    let path = config.repo.join(&config.file);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|source| StoreError::Write {
+            path: dir.to_path_buf(),
+            source,
+        })?;
+    }
+    write_atomic(&path, format!("{}\n", json).as_bytes(), || Ok(()))?;
+    git.run(&["add", "--", &config.file])?;
+    if !git.test(&["diff", "--cached", "--quiet", "--", &config.file])? {
+        git.run(&["commit", "--quiet", "-m", "Sync tasks", "--", &config.file])?;
+    }
+    let head = git.run(&["rev-parse", "HEAD"])?;
+    if remote_commit.as_deref() != Some(head.as_str()) {
+        git.run(&[
+            "push",
+            "--quiet",
+            &config.remote,
+            &format!("HEAD:refs/heads/{}", branch),
+        ])?;
+        report.pushed = Some(head[..head.len().min(8)].to_string());
+    }
+    state.commit = Some(head);
+    state.save(&state_path)?;
+
+    let action = (report.added + report.updated + report.deleted > 0).then(|| Action::Merge {
+        before: ours.active,
+        before_archived: ours.archived,
+        after: merged.active.clone(),
+        after_archived: merged.archived.clone(),
+    });
+    *tasks = merged.active;
+    *archived = merged.archived;
+    Ok((report, action))
+}
+
+/// A task as one side has it, and whether it's archived.
+type Entry<'a> = (&'a Task, bool);
+
+fn entries(lists: &Lists) -> HashMap<Uuid, Entry<'_>> {
+    let active = lists.active.iter().map(|t| (t.uuid, (t, false)));
+    let archived = lists.archived.iter().map(|t| (t.uuid, (t, true)));
+    active.chain(archived).collect()
+}
+
+/// The three-way merge of the lists, by uuid. Counts the changes to `ours`
+/// and the conflicts in `report`.
+fn merge(base: Option<&Lists>, ours: &Lists, theirs: &Lists, prefer: Option<Prefer>, report: &mut Report) -> Lists {
+    let empty = Lists::default();
+    let base = entries(base.unwrap_or(&empty));
+    let (ours_entries, theirs_entries) = (entries(ours), entries(theirs));
+
+    let mut merged: HashMap<Uuid, (Task, bool)> = HashMap::new();
+    let uuids: HashSet<Uuid> = ours_entries.keys().chain(theirs_entries.keys()).copied().collect();
+    for uuid in uuids {
+        let ours = ours_entries.get(&uuid).copied();
+        let result = merge_task(
+            base.get(&uuid).copied(),
+            ours,
+            theirs_entries.get(&uuid).copied(),
+            prefer,
+        );
+        if let Some(fields) = &result.conflict {
+            let (task, _) = ours.or(theirs_entries.get(&uuid).copied()).expect("on one side");
+            report.conflicts.push(format!(
+                "{} ({}) \"{}\": {}",
+                task.id,
+                short(&uuid),
+                task.description,
+                fields
+            ));
+        }
+        match (ours, &result.entry) {
+            (None, Some(_)) => report.added += 1,
+            (Some(_), None) => report.deleted += 1,
+            (Some(ours), Some(merged)) if !same(ours, (&merged.0, merged.1)) => report.updated += 1,
+            _ => {}
+        }
+        if let Some(entry) = result.entry {
+            merged.insert(uuid, entry);
+        }
+    }
+
+    // Tasks keep their order here; the remote's new ones follow theirs.
+    let mut lists = Lists::default();
+    let order = |first: &[Task], second: &[Task], third: &[Task], fourth: &[Task]| {
+        let mut seen = HashSet::new();
+        first
+            .iter()
+            .chain(second)
+            .chain(third)
+            .chain(fourth)
+            .map(|t| t.uuid)
+            .filter(|uuid| seen.insert(*uuid))
+            .collect::<Vec<_>>()
+    };
+    for uuid in order(&ours.active, &theirs.active, &ours.archived, &theirs.archived) {
+        if let Some((task, false)) = merged.get(&uuid) {
+            lists.active.push(task.clone());
+        }
+    }
+    for uuid in order(&ours.archived, &theirs.archived, &ours.active, &theirs.active) {
+        if let Some((task, true)) = merged.get(&uuid) {
+            lists.archived.push(task.clone());
+        }
+    }
+    renumber(&mut lists.active);
+    renumber(&mut lists.archived);
+    lists
+}
+
+struct Merged {
+    entry: Option<(Task, bool)>,
+    /// What both sides changed, when they did.
+    conflict: Option<String>,
+}
+
+fn merge_task(base: Option<Entry>, ours: Option<Entry>, theirs: Option<Entry>, prefer: Option<Prefer>) -> Merged {
+    let owned = |entry: Option<Entry>| entry.map(|(task, archived)| (task.clone(), archived));
+    let unchanged = |side: Option<Entry>| match (base, side) {
+        (Some(base), Some(side)) => same(base, side),
+        (None, None) => true,
+        _ => false,
+    };
+    let settled = |entry| Merged { entry, conflict: None };
+
+    match (ours, theirs) {
+        (Some(o), Some(t)) if same(o, t) => settled(owned(ours)),
+        _ if unchanged(ours) => settled(owned(theirs)),
+        _ if unchanged(theirs) => settled(owned(ours)),
+        (Some(o), Some(t)) => {
+            let (entry, fields) = merge_fields(base, o, t, prefer);
+            let side = if prefer == Some(Prefer::Remote) {
+                "there"
+            } else {
+                "here"
+            };
+            Merged {
+                entry: Some(entry),
+                conflict: (!fields.is_empty())
+                    .then(|| format!("{} changed on both sides; kept the one {}", fields.join(", "), side)),
+            }
+        }
+        // Deleted on one side, changed on the other.
+        (Some(_), None) => Merged {
+            entry: if prefer == Some(Prefer::Remote) {
+                None
+            } else {
+                owned(ours)
+            },
+            conflict: Some(if prefer == Some(Prefer::Remote) {
+                "changed here but deleted there; deleted it".into()
+            } else {
+                "changed here but deleted there; kept it".into()
+            }),
+        },
+        (None, Some(_)) => Merged {
+            entry: if prefer == Some(Prefer::Local) {
+                None
+            } else {
+                owned(theirs)
+            },
+            conflict: Some(if prefer == Some(Prefer::Local) {
+                "deleted here but changed there; deleted it".into()
+            } else {
+                "deleted here but changed there; kept it".into()
+            }),
+        },
+        (None, None) => settled(None),
+    }
+}
+
+/// Merges field by field, returning the fields both sides changed.
+fn merge_fields(
+    base: Option<Entry>,
+    ours: Entry,
+    theirs: Entry,
+    prefer: Option<Prefer>,
+) -> ((Task, bool), Vec<&'static str>) {
+    let mut conflicts = Vec::new();
+    let (o, t) = (ours.0, theirs.0);
+    let b = base.map(|(task, _)| task);
+    macro_rules! field {
+        ($name:literal, $get:expr) => {{
+            let get = $get;
+            let (ours, theirs) = (get(o), get(t));
+            if ours == theirs || b.map(get).as_ref() == Some(&theirs) {
+                ours
+            } else if b.map(get).as_ref() == Some(&ours) {
+                theirs
+            } else {
+                conflicts.push($name);
+                if prefer == Some(Prefer::Remote) {
+                    theirs
+                } else {
+                    ours
+                }
+            }
+        }};
+    }
+
+    let description = field!("description", |t: &Task| t.description.clone());
+    let tags = field!("tags", |t: &Task| t.tags.clone());
+    let due_date = field!("due date", |t: &Task| t.due_date);
+    let (priority, no_priority) = field!("priority", |t: &Task| (t.priority.clone(), t.no_priority));
+    let (completed, completed_at) = field!("completion", |t: &Task| (t.completed, t.completed_at));
+    let recurrence = field!("recurrence", |t: &Task| t.recurrence.clone());
+    let parent = field!("parent", |t: &Task| t.parent);
+    let depends_on = field!("dependencies", |t: &Task| t.depends_on.clone());
+    let created = field!("creation date", |t: &Task| t.created);
+    let annotations = field!("annotations", |t: &Task| t.annotations.clone());
+    let ical_uid = field!("iCalendar UID", |t: &Task| t.ical_uid.clone());
+    let archived = {
+        let (ours, theirs) = (ours.1, theirs.1);
+        match base.map(|(_, archived)| archived) {
+            _ if ours == theirs => ours,
+            Some(base) => {
+                if base == ours {
+                    theirs
+                } else {
+                    ours
+                }
+            }
+            None => {
+                conflicts.push("archiving");
+                if prefer == Some(Prefer::Remote) {
+                    theirs
+                } else {
+                    ours
+                }
+            }
+        }
+    };
+
+    let task = Task {
+        uuid: o.uuid,
+        id: o.id,
+        description,
+        tags,
+        due_date,
+        priority,
+        completed,
+        recurrence,
+        parent,
+        depends_on,
+        created,
+        completed_at,
+        annotations,
+        ical_uid,
+        no_priority,
+    };
+    ((task, archived), conflicts)
+}
+
+/// Whether two versions of a task are the same, whatever their position.
+fn same((a, a_archived): Entry, (b, b_archived): Entry) -> bool {
+    a_archived == b_archived
+        && a.description == b.description
+        && a.tags == b.tags
+        && a.due_date == b.due_date
+        && a.priority == b.priority
+        && a.no_priority == b.no_priority
+        && a.completed == b.completed
+        && a.completed_at == b.completed_at
+        && a.recurrence == b.recurrence
+        && a.parent == b.parent
+        && a.depends_on == b.depends_on
+        && a.created == b.created
+        && a.annotations == b.annotations
+        && a.ical_uid == b.ical_uid
+}
+
+fn short(uuid: &Uuid) -> String {
+    uuid.simple().to_string()[..8].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Priority;
+
+    fn task(description: &str) -> Task {
+        Task {
+            uuid: Uuid::new_v4(),
+            description: description.into(),
+            ..Task::default()
+        }
+    }
+
+    fn active(tasks: &[&Task]) -> Lists {
+        Lists {
+            active: tasks.iter().map(|t| (*t).clone()).collect(),
+            archived: Vec::new(),
+        }
+    }
+
+    fn descriptions(tasks: &[Task]) -> Vec<&str> {
+        tasks.iter().map(|t| t.description.as_str()).collect()
+    }
+
+    #[test]
+    fn edits_on_both_sides() {
+        let base = task("Water the plants");
+        let ours = Task {
+            description: "Water the roses".into(),
+            tags: vec!["garden".into()],
+            ..base.clone()
+        };
+        let theirs = Task {
+            description: "Water the lawn".into(),
+            priority: Priority::High,
+            ..base.clone()
+        };
+
+        let mut report = Report::default();
+        let merged = merge(
+            Some(&active(&[&base])),
+            &active(&[&ours]),
+            &active(&[&theirs]),
+            None,
+            &mut report,
+        );
+        let task = &merged.active[0];
+        assert_eq!(task.description, "Water the roses");
+        assert_eq!(task.tags, ["garden"]);
+        assert_eq!(task.priority, Priority::High);
+        assert_eq!(report.updated, 1);
+        assert_eq!(report.conflicts.len(), 1);
+        assert!(report.conflicts[0].ends_with("description changed on both sides; kept the one here"));
+
+        let mut report = Report::default();
+        let merged = merge(
+            Some(&active(&[&base])),
+            &active(&[&ours]),
+            &active(&[&theirs]),
+            Some(Prefer::Remote),
+            &mut report,
+        );
+        assert_eq!(merged.active[0].description, "Water the lawn");
+        assert_eq!(merged.active[0].tags, ["garden"]);
+        assert!(report.conflicts[0].ends_with("kept the one there"));
+    }
+
+    #[test]
+    fn deleted_on_one_side_and_edited_on_the_other() {
+        let base = task("Water the plants");
+        let edited = Task {
+            description: "Water the roses".into(),
+            ..base.clone()
+        };
+        let base = active(&[&base]);
+
+        let mut report = Report::default();
+        let merged = merge(Some(&base), &active(&[]), &active(&[&edited]), None, &mut report);
+        assert_eq!(descriptions(&merged.active), ["Water the roses"]);
+        assert_eq!(report.added, 1);
+        assert!(report.conflicts[0].ends_with("deleted here but changed there; kept it"));
+
+        let mut report = Report::default();
+        let merged = merge(
+            Some(&base),
+            &active(&[]),
+            &active(&[&edited]),
+            Some(Prefer::Local),
+            &mut report,
+        );
+        assert!(merged.active.is_empty());
+        assert!(report.conflicts[0].ends_with("deleted here but changed there; deleted it"));
+
+        let mut report = Report::default();
+        let merged = merge(
+            Some(&base),
+            &active(&[&edited]),
+            &active(&[]),
+            Some(Prefer::Remote),
+            &mut report,
+        );
+        assert!(merged.active.is_empty());
+        assert_eq!(report.deleted, 1);
+        assert!(report.conflicts[0].ends_with("changed here but deleted there; deleted it"));
+
+        // Deleted on one side and untouched on the other is no conflict.
+        let mut report = Report::default();
+        let merged = merge(Some(&base), &base, &active(&[]), None, &mut report);
+        assert!(merged.active.is_empty());
+        assert!(report.conflicts.is_empty());
+    }
+
+    #[test]
+    fn adds_on_both_sides() {
+        let kept = task("Water the plants");
+        let ours = task("Repot the fern");
+        let theirs = task("Mow the lawn");
+        let both = task("Buy compost");
+
+        let mut report = Report::default();
+        let merged = merge(
+            Some(&active(&[&kept])),
+            &active(&[&kept, &ours, &both]),
+            &active(&[&theirs, &kept, &both]),
+            None,
+            &mut report,
+        );
+        assert_eq!(
+            descriptions(&merged.active),
+            ["Water the plants", "Repot the fern", "Buy compost", "Mow the lawn"]
+        );
+        let ids: Vec<u32> = merged.active.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [1, 2, 3, 4]);
+        assert_eq!((report.added, report.updated, report.deleted), (1, 0, 0));
+        assert!(report.conflicts.is_empty());
+
+        // Without a last sync, the same task on both sides isn't doubled.
+        let mut report = Report::default();
+        let merged = merge(None, &active(&[&both]), &active(&[&both, &theirs]), None, &mut report);
+        assert_eq!(descriptions(&merged.active), ["Buy compost", "Mow the lawn"]);
+        assert!(report.conflicts.is_empty());
+    }
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git").arg("-C").arg(dir).args(args).output().unwrap();
+        assert!(
+            status.status.success(),
+            "git {:?}: {}",
+            args,
+            String::from_utf8_lossy(&status.stderr)
+        );
+    }
+
+    #[test]
+    fn two_clones_sync_through_a_bare_repository() {
+        let dir = std::env::temp_dir().join(format!("todosage-git-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        git(&dir, &["init", "--quiet", "--bare", "-b", "main", "remote.git"]);
+        let clone = |name: &str| {
+            git(&dir, &["clone", "--quiet", "remote.git", name]);
+            let repo = dir.join(name);
+            git(&repo, &["config", "user.name", "Test"]);
+            git(&repo, &["config", "user.email", "test@example.com"]);
+            git(&repo, &["config", "commit.gpgsign", "false"]);
+            GitConfig {
+                repo,
+                file: "todo/tasks.json".into(),
+                remote: "origin".into(),
+                branch: Some("main".into()),
+            }
+        };
+        let (laptop, phone) = (clone("laptop"), clone("phone"));
+        let run = |config: &GitConfig, tasks: &mut Vec<Task>| {
+            let state = config.repo.with_extension("git.json");
+            sync(Some(config), state, tasks, &mut Vec::new(), None, false).unwrap()
+        };
+
+        let mut laptop_tasks = vec![task("Water the plants")];
+        let (report, _) = run(&laptop, &mut laptop_tasks);
+        assert!(report.pushed.is_some());
+
+        let mut phone_tasks = vec![task("Mow the lawn")];
+        let (report, action) = run(&phone, &mut phone_tasks);
+        assert_eq!(report.added, 1);
+        assert!(action.is_some());
+        assert_eq!(descriptions(&phone_tasks), ["Mow the lawn", "Water the plants"]);
+
+        // Both edit the same task; the laptop's edit wins there.
+        laptop_tasks[0].description = "Water the roses".into();
+        phone_tasks[1].description = "Water the ferns".into();
+        let (report, _) = run(&phone, &mut phone_tasks);
+        assert!(report.conflicts.is_empty());
+        let (report, _) = run(&laptop, &mut laptop_tasks);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(descriptions(&laptop_tasks), ["Water the roses", "Mow the lawn"]);
+
+        let (report, _) = run(&phone, &mut phone_tasks);
+        assert!(report.conflicts.is_empty());
+        assert_eq!(report.updated, 1);
+        assert_eq!(descriptions(&phone_tasks), ["Mow the lawn", "Water the roses"]);
+
+        let remote = Git {
+            repo: &dir.join("remote.git"),
+        };
+        let lists = remote.read("main", "todo/tasks.json").unwrap().unwrap();
+        assert_eq!(descriptions(&lists.active), descriptions(&phone_tasks));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,16 +1,29 @@
-use ratatui::{
-    backend::CrosstermBackend,
-    widgets::{Block, Borders, List, ListItem, Tabs, Paragraph, Clear},
-    Terminal, layout::{Layout, Constraint, Direction},
-    style::{Style, Color},
-    text::{Text, Line, Span},
-};
+use crate::actions::{self, Action};
+use crate::config::{Config, KeyAction};
+use crate::dates;
+use crate::journal::Journal;
+use crate::models::{self, renumber, Task};
+use crate::query::Query;
+use crate::urgency;
+use chrono::{Local, Utc};
 use crossterm::{
     event::{self, Event, KeyCode},
-    terminal::{enable_raw_mode, disable_raw_mode}
+    terminal::{disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, Paragraph, Tabs},
+    Terminal,
 };
-use crate::models::{Task, Priority};
-use chrono::NaiveDate;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+mod form;
+
+use form::{FormEvent, FormMode, TaskForm, TextInput};
 
 #[derive(PartialEq)]
 enum TabMode {
@@ -18,22 +31,38 @@ enum TabMode {
     Archived,
 }
 
-#[derive(PartialEq)]
 enum InputMode {
     Normal,
-    AddingTask(AddTaskState),
-}
-
-#[derive(PartialEq)]
-enum AddTaskState {
-    Description,
-    Tags,
-    DueDate,
-    Priority,
+    Form(TaskForm),
+    /// Asking whether completing a task should also complete its subtasks.
+    ConfirmDone {
+        uuid: Uuid,
+        unfinished: usize,
+    },
+    /// Typing a filter expression after '/'.
+    Filter {
+        input: TextInput,
+        error: Option<String>,
+    },
 }
 
+/// Undoes (`true`) or redoes the other list's half of a move, in memory;
+/// it is saved along with this list.
+pub type OtherHalf<'a> = dyn FnMut(&Action, bool) -> Result<(), Box<dyn std::error::Error>> + 'a;
 
-pub fn run_tui(tasks: &mut Vec<Task>, archived: &mut Vec<Task>) -> Result<(), Box<dyn std::error::Error>> {
+/// Runs the TUI on the tasks of `list`, one of `lists`. Returns the list to
+/// switch to next, or `None` when the user quit. `other_half` is called
+/// for every move undone or redone here.
+pub fn run_tui(
+    tasks: &mut Vec<Task>,
+    archived: &mut Vec<Task>,
+    journal: &mut Journal,
+    config: &Config,
+    lists: &[String],
+    list: &str,
+    other_half: &mut OtherHalf<'_>,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let colors = &config.colors;
     enable_raw_mode()?;
     let stdout = std::io::stdout();
     let backend = CrosstermBackend::new(stdout);
@@ -45,34 +74,61 @@ pub fn run_tui(tasks: &mut Vec<Task>, archived: &mut Vec<Task>) -> Result<(), Bo
     let mut selected_tab = TabMode::Active;
     let mut selected_index = 0;
     let mut input_mode = InputMode::Normal;
-
-    // Task creation state
-    let mut new_task_description = String::new();
-    let mut new_task_tags = String::new();
-    let mut new_task_due_date = String::new();
-    let mut new_task_priority = Priority::Low;
+    let mut status = String::new();
+    // Tasks whose subtasks are hidden, and a task to move the cursor to.
+    let mut collapsed: HashSet<Uuid> = HashSet::new();
+    let mut focus: Option<Uuid> = None;
+    // The filter text as typed, and parsed.
+    let mut filter: Option<(String, Query)> = None;
+    let sort_modes = config.sort.cycle();
+    let mut sort_mode = 0;
+    let mut next_list = None;
 
     loop {
-        let (current_list, list_len) = match selected_tab {
-            TabMode::Active => (&tasks, tasks.len()),
-            TabMode::Archived => (&archived, archived.len()),
+        let current_list: &Vec<Task> = match selected_tab {
+            TabMode::Active => tasks,
+            TabMode::Archived => archived,
         };
+        let order = sort_modes[sort_mode].order(current_list);
+        let mut rows = models::sorted_tree(current_list, &order, &collapsed);
+        if let Some((_, query)) = &filter {
+            rows.retain(|row| query.matches(&current_list[row.index], current_list));
+        }
+        if let Some(uuid) = focus.take() {
+            if let Some(row) = rows.iter().position(|r| current_list[r.index].uuid == uuid) {
+                selected_index = row;
+            }
+        }
+        // Undo and redo can shrink the list under the cursor.
+        let list_len = rows.len();
+        selected_index = selected_index.min(list_len.saturating_sub(1));
+        // Index into the task list of the task under the cursor.
+        let selected = rows.get(selected_index).map(|r| r.index);
 
         terminal.draw(|f| {
             let main_chunks = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints([Constraint::Length(3), Constraint::Min(0)])
+                .constraints([Constraint::Length(3), Constraint::Min(0), Constraint::Length(1)])
                 .split(f.size());
 
-            // Tabs
+            // Tabs, with the lists beside them
+            let tab_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Length(23), Constraint::Min(0)])
+                .split(main_chunks[0]);
             let tabs = Tabs::new(vec!["Active", "Archived"])
                 .block(Block::default().borders(Borders::ALL))
-                .highlight_style(Style::default().fg(Color::Yellow))
+                .highlight_style(Style::default().fg(colors.primary))
                 .select(match selected_tab {
                     TabMode::Active => 0,
                     TabMode::Archived => 1,
                 });
-            f.render_widget(tabs, main_chunks[0]);
+            f.render_widget(tabs, tab_chunks[0]);
+            let list_tabs = Tabs::new(lists.iter().map(|name| name.as_str()).collect())
+                .block(Block::default().borders(Borders::ALL).title("Lists"))
+                .highlight_style(Style::default().fg(colors.primary))
+                .select(lists.iter().position(|name| name == list).unwrap_or(0));
+            f.render_widget(list_tabs, tab_chunks[1]);
 
             // Content
             let content_chunks = Layout::default()
@@ -81,36 +137,109 @@ pub fn run_tui(tasks: &mut Vec<Task>, archived: &mut Vec<Task>) -> Result<(), Bo
                 .split(main_chunks[1]);
 
             // Task List
-            let items: Vec<ListItem> = current_list
+            let items: Vec<ListItem> = rows
                 .iter()
                 .enumerate()
-                .map(|(i, task)| {
+                .map(|(i, row)| {
+                    let task = &current_list[row.index];
                     let style = if i == selected_index {
-                        Style::default().fg(Color::Yellow)
+                        Style::default().fg(colors.primary)
                     } else {
                         Style::default()
                     };
+                    let progress = models::progress(current_list, task.uuid);
+                    let fold = match progress {
+                        Some(_) if collapsed.contains(&task.uuid) => "▸ ",
+                        Some(_) => "▾ ",
+                        None => "  ",
+                    };
+                    let mark = if task.completed {
+                        "✓"
+                    } else if models::is_blocked(current_list, task) {
+                        "⊘"
+                    } else {
+                        " "
+                    };
+                    let score = if task.completed {
+                        "     ".to_string()
+                    } else {
+                        format!("{:>5.1}", urgency::score(task, current_list, &config.urgency))
+                    };
                     ListItem::new(format!(
-                        "{} [{}] {}",
-                        if task.completed { "✓" } else { " " },
+                        "{} {}{} {}[{}] {}{}",
+                        score,
+                        mark,
+                        "  ".repeat(row.depth),
+                        fold,
                         task.id,
-                        task.description
-                    )).style(style)
+                        task.description,
+                        progress
+                            .map(|(done, total)| format!(" {}/{}", done, total))
+                            .unwrap_or_default()
+                    ))
+                    .style(style)
                 })
                 .collect();
 
-            let list = List::new(items)
-                .block(Block::default().borders(Borders::ALL).title("Tasks"));
+            let mut title = "Tasks".to_string();
+            if sort_mode != 0 {
+                title.push_str(&format!(" (sort: {})", sort_modes[sort_mode]));
+            }
+            if let Some((text, _)) = &filter {
+                title.push_str(&format!(" (filter: {})", text));
+            }
+            let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
             f.render_widget(list, content_chunks[0]);
 
             // Task Details
-            if let Some(task) = current_list.get(selected_index) {
-                let mut details = Vec::new();
-                
+            if let Some(task) = selected.map(|i| &current_list[i]) {
+                let mut details = vec![Line::from(vec![
+                    Span::styled("ID: ", Style::default().fg(Color::Gray)),
+                    Span::raw(task.uuid.to_string()),
+                ])];
+
                 if let Some(due_date) = &task.due_date {
+                    let due_style = if !task.completed && dates::is_overdue(due_date) {
+                        Style::default().fg(colors.error)
+                    } else {
+                        Style::default()
+                    };
+                    details.push(Line::from(vec![
+                        Span::styled("Due: ", Style::default().fg(colors.warning)),
+                        Span::styled(dates::format(due_date), due_style),
+                    ]));
+                }
+
+                if let Some(recurrence) = &task.recurrence {
+                    details.push(Line::from(vec![
+                        Span::styled("Repeats: ", Style::default().fg(colors.warning)),
+                        Span::raw(recurrence.describe()),
+                    ]));
+                }
+
+                let blockers = models::blockers(current_list, task);
+                if !blockers.is_empty() && !task.completed {
+                    let names: Vec<String> = blockers
+                        .iter()
+                        .map(|t| format!("[{}] {}", t.id, t.description))
+                        .collect();
                     details.push(Line::from(vec![
-                        Span::styled("Due: ", Style::default().fg(Color::Magenta)),
-                        Span::raw(due_date.format("%Y-%m-%d %H:%M").to_string()),
+                        Span::styled("Blocked by: ", Style::default().fg(colors.error)),
+                        Span::raw(names.join(", ")),
+                    ]));
+                }
+
+                if let Some(parent) = task.parent.and_then(|p| models::position(current_list, p)) {
+                    details.push(Line::from(vec![
+                        Span::styled("Subtask of: ", Style::default().fg(Color::Gray)),
+                        Span::raw(&current_list[parent].description),
+                    ]));
+                }
+
+                if let Some((done, total)) = models::progress(current_list, task.uuid) {
+                    details.push(Line::from(vec![
+                        Span::styled("Subtasks: ", Style::default().fg(Color::Gray)),
+                        Span::raw(format!("{}/{} done", done, total)),
                     ]));
                 }
 
@@ -126,181 +255,280 @@ pub fn run_tui(tasks: &mut Vec<Task>, archived: &mut Vec<Task>) -> Result<(), Bo
                     Span::raw(format!("{:?}", task.priority)),
                 ]));
 
+                if !task.completed {
+                    details.push(Line::from(vec![
+                        Span::styled("Urgency: ", Style::default().fg(Color::Blue)),
+                        Span::raw(format!("{:.2}", urgency::score(task, current_list, &config.urgency))),
+                    ]));
+                }
+
                 details.push(Line::from(vec![
                     Span::styled("Description:\n", Style::default().fg(Color::Green)),
                     Span::raw(&task.description),
                 ]));
 
+                for annotation in &task.annotations {
+                    details.push(Line::from(vec![
+                        Span::styled(
+                            format!("{}: ", annotation.entry.with_timezone(&Local).format("%Y-%m-%d %H:%M")),
+                            Style::default().fg(Color::Gray),
+                        ),
+                        Span::raw(&annotation.description),
+                    ]));
+                }
+
                 let details_block = Paragraph::new(details)
                     .block(Block::default().borders(Borders::ALL).title("Details"))
                     .wrap(ratatui::widgets::Wrap { trim: true });
                 f.render_widget(details_block, content_chunks[1]);
             }
 
-            // Add Task Form
-            if let InputMode::AddingTask(state) = &input_mode {
-                let form_content = match state {
-                    AddTaskState::Description => vec![
-                        Line::from("Add New Task (Press Enter to skip)"),
-                        Line::from("Description:"),
-                        Line::from(new_task_description.as_str()),
-                    ],
-                    AddTaskState::Tags => vec![
-                        Line::from("Add Tags (comma separated, Enter to skip):"),
-                        Line::from(new_task_tags.as_str()),
-                    ],
-                    AddTaskState::DueDate => vec![
-                        Line::from("Due Date (YYYY-MM-DD, Enter to skip):"),
-                        Line::from(new_task_due_date.as_str()),
-                    ],
-                    AddTaskState::Priority => vec![
-                        Line::from("Priority (1=Low, 2=Medium, 3=High):"),
-                        Line::from(format!("{:?}", new_task_priority)),
-                    ],
-                };
-
-                let popup = Paragraph::new(Text::from(form_content))
-                    .block(Block::default().borders(Borders::ALL).title("New Task"))
-                    .style(Style::default().bg(Color::DarkGray));
-
-                let area = Layout::default()
-                    .constraints([Constraint::Percentage(50)])
-                    .split(f.size())[0];
-
-                f.render_widget(Clear, area);
-                f.render_widget(popup, area);
+            // Status line
+            let status_line = match &input_mode {
+                InputMode::ConfirmDone { unfinished, .. } => format!(
+                    "Also mark the {} unfinished subtasks as done? [y]es / [n]o, just this one / Esc: cancel",
+                    unfinished
+                ),
+                InputMode::Filter { input, error } => {
+                    f.set_cursor(main_chunks[2].x + 1 + input.cursor() as u16, main_chunks[2].y);
+                    match error {
+                        Some(error) => format!("/{}   {}", input.value(), error),
+                        None => format!("/{}", input.value()),
+                    }
+                }
+                _ => status.clone(),
+            };
+            f.render_widget(Paragraph::new(status_line), main_chunks[2]);
+
+            // Add/Edit Task Form
+            if let InputMode::Form(form) = &input_mode {
+                form.render(f, centered_rect(60, 10, f.size()), colors);
             }
         })?;
 
         if let Event::Key(key) = event::read()? {
+            status.clear();
             match &mut input_mode {
-                InputMode::Normal => match key.code {
+                InputMode::Normal => match config.keybindings.action(key.code) {
                     // Navigation
-                    KeyCode::Char('j') | KeyCode::Down => {
+                    Some(KeyAction::NavigateDown) => {
                         selected_index = (selected_index + 1).min(list_len.saturating_sub(1));
                     }
-                    KeyCode::Char('k') | KeyCode::Up => {
+                    Some(KeyAction::NavigateUp) => {
                         selected_index = selected_index.saturating_sub(1);
                     }
-                    KeyCode::Char('h') => {
+                    Some(KeyAction::SwitchTabLeft) => {
                         selected_tab = TabMode::Active;
                         selected_index = 0;
                     }
-                    KeyCode::Char('l') => {
+                    Some(KeyAction::SwitchTabRight) => {
                         selected_tab = TabMode::Archived;
                         selected_index = 0;
                     }
+                    Some(KeyAction::NextList) => {
+                        if lists.len() > 1 {
+                            let index = lists
+                                .iter()
+                                .position(|name| name == list)
+                                .map_or(0, |i| (i + 1) % lists.len());
+                            next_list = Some(lists[index].clone());
+                            break;
+                        }
+                        status = "No other lists yet; start one with todo -l NAME add".into();
+                    }
 
                     // Task Management
-                    KeyCode::Char('d') => { // Delete
+                    Some(KeyAction::DeleteTask) => {
                         if let TabMode::Active = selected_tab {
-                            if selected_index < tasks.len() {
-                                tasks.remove(selected_index);
+                            if let Some(index) = selected {
+                                let task = tasks.remove(index);
+                                journal.record(Action::Delete(task));
                                 selected_index = selected_index.saturating_sub(1);
-                                for (index, task) in tasks.iter_mut().enumerate() {                 
-                                  task.id = (index + 1) as u32;                                     
-                                }                                                                   
+                                renumber(tasks);
                             }
                         }
                     }
-                    KeyCode::Char('D') => { // Mark as Done
+                    Some(KeyAction::MarkDone) => {
                         if let TabMode::Active = selected_tab {
-                            if let Some(task) = tasks.get_mut(selected_index) {
-                                task.completed = true;
+                            if let Some(index) = selected {
+                                let uuid = tasks[index].uuid;
+                                match actions::unfinished_subtasks(tasks, uuid) {
+                                    0 => {
+                                        if let Some(action) = actions::complete_tree(tasks, index, false) {
+                                            if let Action::Recur { .. } = action {
+                                                status = action.describe();
+                                            }
+                                            journal.record(action);
+                                        }
+                                    }
+                                    unfinished => {
+                                        input_mode = InputMode::ConfirmDone { uuid, unfinished };
+                                    }
+                                }
                             }
                         }
                     }
-                    KeyCode::Char('a') => { // Add Task
-                        input_mode = InputMode::AddingTask(AddTaskState::Description);
-                        new_task_description.clear();
-                        new_task_tags.clear();
-                        new_task_due_date.clear();
-                        new_task_priority = Priority::Low;
+                    Some(KeyAction::NewTask) => {
+                        input_mode = InputMode::Form(TaskForm::new_task(None));
+                    }
+                    Some(KeyAction::NewSubtask) => {
+                        if let (TabMode::Active, Some(index)) = (&selected_tab, selected) {
+                            input_mode = InputMode::Form(TaskForm::new_task(Some(tasks[index].uuid)));
+                        }
+                    }
+                    Some(KeyAction::EditTask) => {
+                        if let (TabMode::Active, Some(index)) = (&selected_tab, selected) {
+                            input_mode = InputMode::Form(TaskForm::edit(&tasks[index]));
+                        }
+                    }
+                    Some(KeyAction::ToggleFold) => {
+                        if let Some(uuid) = selected.map(|i| current_list[i].uuid) {
+                            if models::progress(current_list, uuid).is_some() && !collapsed.remove(&uuid) {
+                                collapsed.insert(uuid);
+                            }
+                        }
                     }
 
-                    KeyCode::Char('r') => {
-                      let mut new_archived = Vec::new();
-                      tasks.retain(|task| {
-                        if task.completed {
-                          new_archived.push(task.clone());
-                          false
-                        } else {
-                          true
+                    Some(KeyAction::ArchiveTasks) => {
+                        if let Some(action) = actions::archive(tasks, archived) {
+                            status = action.describe();
+                            journal.record(action);
                         }
-                      });
-                      archived.extend(new_archived.clone());
-                      for (index, task) in tasks.iter_mut().enumerate() {          
-                        task.id = (index + 1) as u32;                                  
-                      }                                                                  
-                      for (index, task) in archived.iter_mut().enumerate() { 
-                        task.id = (index + 1) as u32;                                  
-                      }                                             
                     }
 
-                    // Quit
-                    KeyCode::Char('q') => break,
-                    _ => {}
+                    Some(KeyAction::Undo) => {
+                        status = match journal.undo(tasks, archived) {
+                            Ok(Some(action)) => match other_half(&action, true) {
+                                Ok(()) => format!("Undone: {}", action.describe()),
+                                Err(e) => match journal.redo(tasks, archived) {
+                                    Ok(_) => format!("Can't undo: {}", e),
+                                    Err(redo) => format!("Can't undo: {}; redoing it here failed too: {}", e, redo),
+                                },
+                            },
+                            Ok(None) => "Nothing to undo".into(),
+                            Err(e) => format!("Can't undo: {}", e),
+                        };
+                    }
+                    Some(KeyAction::Redo) => {
+                        status = match journal.redo(tasks, archived) {
+                            Ok(Some(action)) => match other_half(&action, false) {
+                                Ok(()) => format!("Redone: {}", action.describe()),
+                                Err(e) => match journal.undo(tasks, archived) {
+                                    Ok(_) => format!("Can't redo: {}", e),
+                                    Err(undo) => format!("Can't redo: {}; undoing it here failed too: {}", e, undo),
+                                },
+                            },
+                            Ok(None) => "Nothing to redo".into(),
+                            Err(e) => format!("Can't redo: {}", e),
+                        };
+                    }
+
+                    Some(KeyAction::Filter) => {
+                        let text = filter.as_ref().map(|(text, _)| text.as_str()).unwrap_or("");
+                        input_mode = InputMode::Filter {
+                            input: TextInput::new(text),
+                            error: None,
+                        };
+                    }
+
+                    Some(KeyAction::CycleSort) => {
+                        sort_mode = (sort_mode + 1) % sort_modes.len();
+                        status = format!("Sorted by {}", sort_modes[sort_mode]);
+                        focus = selected.map(|i| current_list[i].uuid);
+                    }
+
+                    Some(KeyAction::Quit) => break,
+                    None => {}
                 },
 
-                InputMode::AddingTask(state) => match key.code {
-                    KeyCode::Enter => {
-                        match state {
-                            AddTaskState::Description => {
-                                input_mode = InputMode::AddingTask(AddTaskState::Tags);
-                            }
-                            AddTaskState::Tags => {
-                                input_mode = InputMode::AddingTask(AddTaskState::DueDate);
-                            }
-                            AddTaskState::DueDate => {
-                                input_mode = InputMode::AddingTask(AddTaskState::Priority);
-                            }
-                            AddTaskState::Priority => {
-                                // Create new task
-                                let new_task = Task {
+                InputMode::Form(form) => match form.handle_key(key) {
+                    FormEvent::None => {}
+                    FormEvent::Cancel => input_mode = InputMode::Normal,
+                    FormEvent::Submit(draft) => {
+                        match form.mode {
+                            FormMode::Add { parent } => {
+                                let mut new_task = Task {
+                                    uuid: Uuid::new_v4(),
                                     id: tasks.len() as u32 + 1,
-                                    description: new_task_description.clone(),
-                                    tags: new_task_tags.split(',').map(|s| s.trim().to_string()).collect(),
-                                    due_date: NaiveDate::parse_from_str(&new_task_due_date, "%Y-%m-%d")
-                                        .ok()
-                                        .map(|d| d.and_hms_opt(0, 0, 0).unwrap().and_utc()),
-                                    priority: new_task_priority.clone(),
-                                    completed: false,
+                                    parent,
+                                    created: Some(Utc::now()),
+                                    ..Task::default()
                                 };
+                                draft.apply_to(&mut new_task);
+                                if let Some(parent) = parent {
+                                    collapsed.remove(&parent);
+                                }
+                                focus = Some(new_task.uuid);
+                                journal.record(Action::Add(new_task.clone()));
                                 tasks.push(new_task);
-                                input_mode = InputMode::Normal;
+                            }
+                            FormMode::Edit(uuid) => {
+                                if let Some(index) = models::position(tasks, uuid) {
+                                    let before = tasks[index].clone();
+                                    draft.apply_to(&mut tasks[index]);
+                                    if let Some(action) = Action::for_edit(&before, &tasks[index]) {
+                                        status = action.describe();
+                                        journal.record(action);
+                                    }
+                                }
                             }
                         }
-                    }
-                    KeyCode::Esc => {
                         input_mode = InputMode::Normal;
                     }
-                    KeyCode::Char('1') if *state == AddTaskState::Priority => {
-                        new_task_priority = Priority::Low;
-                    }
-                    KeyCode::Char('2') if *state == AddTaskState::Priority => {
-                        new_task_priority = Priority::Medium;
+                },
+
+                InputMode::Filter { input, error } => match key.code {
+                    KeyCode::Esc => input_mode = InputMode::Normal,
+                    KeyCode::Enter => {
+                        let text = input.value().trim().to_string();
+                        match Query::parse(&text, Local::now().naive_local()) {
+                            Ok(_) if text.is_empty() => {
+                                filter = None;
+                                input_mode = InputMode::Normal;
+                            }
+                            Ok(query) => {
+                                filter = Some((text, query));
+                                selected_index = 0;
+                                input_mode = InputMode::Normal;
+                            }
+                            Err(e) => *error = Some(e.summary()),
+                        }
                     }
-                    KeyCode::Char('3') if *state == AddTaskState::Priority => {
-                        new_task_priority = Priority::High;
+                    code => {
+                        input.handle_key(code);
                     }
-                    KeyCode::Char(c) => match state {
-                        AddTaskState::Description => new_task_description.push(c),
-                        AddTaskState::Tags => new_task_tags.push(c),
-                        AddTaskState::DueDate => new_task_due_date.push(c),
-                        _ => {}
-                    },
-                    KeyCode::Backspace => match state {
-                        AddTaskState::Description => { new_task_description.pop(); }
-                        AddTaskState::Tags => { new_task_tags.pop(); }
-                        AddTaskState::DueDate => { new_task_due_date.pop(); }
-                        _ => {}
-                    },
-                    _ => {}
                 },
+
+                InputMode::ConfirmDone { uuid, .. } => {
+                    let subtasks = match key.code {
+                        KeyCode::Char('y') => Some(true),
+                        KeyCode::Char('n') => Some(false),
+                        _ => None,
+                    };
+                    if let (Some(subtasks), Some(index)) = (subtasks, models::position(tasks, *uuid)) {
+                        if let Some(action) = actions::complete_tree(tasks, index, subtasks) {
+                            status = action.describe();
+                            journal.record(action);
+                        }
+                    }
+                    input_mode = InputMode::Normal;
+                }
             }
         }
     }
 
     disable_raw_mode()?;
-    Ok(())
+    Ok(next_list)
+}
+
+/// A `width` percent wide, `height` rows tall area in the middle of `area`.
+fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = area.width * width / 100;
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
 }
@@ -0,0 +1,371 @@
+// src/tui/form.rs
+use chrono::{DateTime, Utc};
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use uuid::Uuid;
+
+use crate::config::Colors;
+use crate::dates;
+use crate::models::{Priority, Task};
+use crate::recurrence::Recurrence;
+
+/// A single line text field with a cursor.
+#[derive(Debug, Default)]
+pub struct TextInput {
+    value: String,
+    /// Cursor position in characters, not bytes.
+    cursor: usize,
+}
+
+impl TextInput {
+    pub fn new(value: &str) -> Self {
+        TextInput {
+            value: value.to_string(),
+            cursor: value.chars().count(),
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    fn byte_offset(&self) -> usize {
+        self.value
+            .char_indices()
+            .nth(self.cursor)
+            .map(|(i, _)| i)
+            .unwrap_or(self.value.len())
+    }
+
+    /// Applies an editing key. Returns false for keys it doesn't handle.
+    pub fn handle_key(&mut self, code: KeyCode) -> bool {
+        let len = self.value.chars().count();
+        match code {
+            KeyCode::Char(c) => {
+                let at = self.byte_offset();
+                self.value.insert(at, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                let at = self.byte_offset();
+                self.value.remove(at);
+            }
+            KeyCode::Delete if self.cursor < len => {
+                let at = self.byte_offset();
+                self.value.remove(at);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(len),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = len,
+            KeyCode::Backspace | KeyCode::Delete => {}
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Description,
+    Tags,
+    DueDate,
+    Repeat,
+    Priority,
+}
+
+impl Field {
+    const ALL: [Field; 5] = [
+        Field::Description,
+        Field::Tags,
+        Field::DueDate,
+        Field::Repeat,
+        Field::Priority,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Field::Description => "Description",
+            Field::Tags => "Tags",
+            Field::DueDate => "Due date",
+            Field::Repeat => "Repeat",
+            Field::Priority => "Priority",
+        }
+    }
+
+    fn hint(self) -> &'static str {
+        match self {
+            Field::Description => "",
+            Field::Tags => "comma separated",
+            Field::DueDate => "2024-12-31, tomorrow, fri, +3d, in 2 weeks...",
+            Field::Repeat => "daily, weekly on mon,thu, monthly on 15, every 3 days...",
+            Field::Priority => "←/→ or 1-3",
+        }
+    }
+
+    fn offset(self, by: isize) -> Field {
+        let index = Field::ALL.iter().position(|f| *f == self).unwrap_or(0) as isize;
+        let len = Field::ALL.len() as isize;
+        Field::ALL[(index + by).rem_euclid(len) as usize]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormMode {
+    /// A new task, as a subtask of `parent` if given.
+    Add {
+        parent: Option<Uuid>,
+    },
+    Edit(Uuid),
+}
+
+/// The validated contents of the form.
+#[derive(Debug)]
+pub struct TaskDraft {
+    pub description: String,
+    pub tags: Vec<String>,
+    pub due_date: Option<DateTime<Utc>>,
+    pub priority: Priority,
+    pub recurrence: Option<Recurrence>,
+}
+
+impl TaskDraft {
+    /// Copies the draft onto `task`, leaving its identity and status alone.
+    pub fn apply_to(self, task: &mut Task) {
+        task.description = self.description;
+        task.tags = self.tags;
+        task.due_date = self.due_date;
+        if task.priority != self.priority {
+            task.no_priority = false;
+        }
+        task.priority = self.priority;
+        task.recurrence = self.recurrence;
+    }
+}
+
+pub enum FormEvent {
+    None,
+    Cancel,
+    Submit(TaskDraft),
+}
+
+/// Popup form used both to create a task and to edit an existing one.
+pub struct TaskForm {
+    pub mode: FormMode,
+    description: TextInput,
+    tags: TextInput,
+    due_date: TextInput,
+    repeat: TextInput,
+    priority: Priority,
+    focus: Field,
+    error: Option<String>,
+}
+
+impl TaskForm {
+    pub fn new_task(parent: Option<Uuid>) -> Self {
+        TaskForm {
+            mode: FormMode::Add { parent },
+            description: TextInput::default(),
+            tags: TextInput::default(),
+            due_date: TextInput::default(),
+            repeat: TextInput::default(),
+            priority: Priority::Low,
+            focus: Field::Description,
+            error: None,
+        }
+    }
+
+    pub fn edit(task: &Task) -> Self {
+        TaskForm {
+            mode: FormMode::Edit(task.uuid),
+            description: TextInput::new(&task.description),
+            tags: TextInput::new(&task.tags.join(", ")),
+            due_date: TextInput::new(&task.due_date.map(|d| dates::format(&d)).unwrap_or_default()),
+            repeat: TextInput::new(&task.recurrence.as_ref().map(|r| r.to_string()).unwrap_or_default()),
+            priority: task.priority.clone(),
+            focus: Field::Description,
+            error: None,
+        }
+    }
+
+    fn input_mut(&mut self, field: Field) -> Option<&mut TextInput> {
+        match field {
+            Field::Description => Some(&mut self.description),
+            Field::Tags => Some(&mut self.tags),
+            Field::DueDate => Some(&mut self.due_date),
+            Field::Repeat => Some(&mut self.repeat),
+            Field::Priority => None,
+        }
+    }
+
+    fn input(&self, field: Field) -> Option<&TextInput> {
+        match field {
+            Field::Description => Some(&self.description),
+            Field::Tags => Some(&self.tags),
+            Field::DueDate => Some(&self.due_date),
+            Field::Repeat => Some(&self.repeat),
+            Field::Priority => None,
+        }
+    }
+
+    /// Tab/Shift-Tab (or Down/Up) move between fields, Enter moves on and
+    /// submits from the last field, Esc cancels.
+    pub fn handle_key(&mut self, key: KeyEvent) -> FormEvent {
+        match key.code {
+            KeyCode::Esc => return FormEvent::Cancel,
+            KeyCode::Tab | KeyCode::Down => self.move_focus(1),
+            KeyCode::BackTab | KeyCode::Up => self.move_focus(-1),
+            KeyCode::Enter if self.focus == Field::Priority => match self.validate() {
+                Ok(draft) => return FormEvent::Submit(draft),
+                Err((field, message)) => {
+                    self.focus = field;
+                    self.error = Some(message);
+                }
+            },
+            KeyCode::Enter => self.move_focus(1),
+            code if self.focus == Field::Priority => {
+                self.priority = match code {
+                    KeyCode::Char('1') => Priority::Low,
+                    KeyCode::Char('2') => Priority::Medium,
+                    KeyCode::Char('3') => Priority::High,
+                    KeyCode::Left | KeyCode::Char('h') => match self.priority {
+                        Priority::High => Priority::Medium,
+                        _ => Priority::Low,
+                    },
+                    KeyCode::Right | KeyCode::Char('l') => match self.priority {
+                        Priority::Low => Priority::Medium,
+                        _ => Priority::High,
+                    },
+                    _ => self.priority.clone(),
+                };
+            }
+            code => {
+                let focus = self.focus;
+                if let Some(input) = self.input_mut(focus) {
+                    input.handle_key(code);
+                }
+            }
+        }
+        FormEvent::None
+    }
+
+    fn move_focus(&mut self, by: isize) {
+        // Point out a bad date or rule as soon as the user leaves the field.
+        self.error = match self.focus {
+            Field::DueDate => parse_due_date(self.due_date.value()).err(),
+            Field::Repeat => parse_recurrence(self.repeat.value()).err(),
+            _ => None,
+        };
+        self.focus = self.focus.offset(by);
+    }
+
+    fn validate(&self) -> Result<TaskDraft, (Field, String)> {
+        let description = self.description.value().trim();
+        if description.is_empty() {
+            return Err((Field::Description, "The description can't be empty".into()));
+        }
+        let due_date = parse_due_date(self.due_date.value()).map_err(|e| (Field::DueDate, e))?;
+        let recurrence = parse_recurrence(self.repeat.value()).map_err(|e| (Field::Repeat, e))?;
+        let tags = self
+            .tags
+            .value()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        Ok(TaskDraft {
+            description: description.to_string(),
+            tags,
+            due_date,
+            priority: self.priority.clone(),
+            recurrence,
+        })
+    }
+
+    pub fn render<B: Backend>(&self, f: &mut Frame<B>, area: Rect, colors: &Colors) {
+        let title = match self.mode {
+            FormMode::Add { parent: None } => "New Task",
+            FormMode::Add { parent: Some(_) } => "New Subtask",
+            FormMode::Edit(_) => "Edit Task",
+        };
+
+        let mut lines = Vec::new();
+        let mut cursor = None;
+        for field in Field::ALL {
+            let focused = field == self.focus;
+            let label_style = if focused {
+                Style::default().fg(colors.primary).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            let label = format!("{:<12}", format!("{}:", field.label()));
+            let value = match self.input(field) {
+                Some(input) => {
+                    if focused {
+                        cursor = Some((label.chars().count() + input.cursor(), lines.len()));
+                    }
+                    input.value().to_string()
+                }
+                None => format!("{:?}", self.priority),
+            };
+            let mut spans = vec![Span::styled(label, label_style), Span::raw(value)];
+            if focused && !field.hint().is_empty() {
+                spans.push(Span::styled(
+                    format!("  ({})", field.hint()),
+                    Style::default().fg(colors.warning),
+                ));
+            }
+            lines.push(Line::from(spans));
+        }
+
+        lines.push(Line::from(""));
+        match &self.error {
+            Some(error) => lines.push(Line::from(Span::styled(
+                error.as_str(),
+                Style::default().fg(colors.error),
+            ))),
+            None => lines.push(Line::from(
+                "Tab/Shift-Tab: move  Enter: next, save on the last field  Esc: cancel",
+            )),
+        }
+
+        let popup = Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .style(Style::default().bg(colors.background));
+        f.render_widget(Clear, area);
+        f.render_widget(popup, area);
+
+        if let Some((x, y)) = cursor {
+            f.set_cursor(area.x + 1 + x as u16, area.y + 1 + y as u16);
+        }
+    }
+}
+
+fn parse_due_date(input: &str) -> Result<Option<DateTime<Utc>>, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    dates::parse_due(input).map(Some).map_err(|e| e.to_string())
+}
+
+fn parse_recurrence(input: &str) -> Result<Option<Recurrence>, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    input.parse::<Recurrence>().map(Some).map_err(|e| e.to_string())
+}
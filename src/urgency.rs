@@ -0,0 +1,103 @@
+// src/urgency.rs
+//
+// A Taskwarrior-style urgency score: a sum of coefficient * factor terms,
+// where each factor is between 0 and 1. The defaults are Taskwarrior's.
+use std::collections::HashMap;
+
+use chrono::{Local, NaiveDateTime, Utc};
+
+use crate::models::{self, Priority, Task};
+
+#[derive(Debug, Clone)]
+pub struct Coefficients {
+    pub priority_high: f64,
+    pub priority_medium: f64,
+    pub priority_low: f64,
+    /// Scaled by how close the due date is, see `due_factor`.
+    pub due: f64,
+    /// Scaled by age up to `age_max` days.
+    pub age: f64,
+    pub age_max: f64,
+    /// Scaled by the number of tags: 0.8 for one, 0.9 for two, 1 for more.
+    pub tags: f64,
+    /// Added to tasks waiting for others (negative by default).
+    pub blocked: f64,
+    /// Added to tasks others are waiting for.
+    pub blocking: f64,
+    /// Extra score for individual tags, e.g. `next`.
+    pub tag: HashMap<String, f64>,
+}
+
+impl Default for Coefficients {
+    fn default() -> Self {
+        Coefficients {
+            priority_high: 6.0,
+            priority_medium: 3.9,
+            priority_low: 1.8,
+            due: 12.0,
+            age: 2.0,
+            age_max: 365.0,
+            tags: 1.0,
+            blocked: -5.0,
+            blocking: 8.0,
+            tag: HashMap::from([("next".to_string(), 15.0)]),
+        }
+    }
+}
+
+/// The urgency of `task`, 0 once it is done. `tasks` is the list it is in.
+pub fn score(task: &Task, tasks: &[Task], coefficients: &Coefficients) -> f64 {
+    if task.completed {
+        return 0.0;
+    }
+    let c = coefficients;
+
+    let priority = match task.priority {
+        Priority::High => c.priority_high,
+        Priority::Medium => c.priority_medium,
+        Priority::Low => c.priority_low,
+    };
+
+    // Due dates are floating local times, see dates.rs.
+    let now = Utc::now();
+    let local_now = now.with_timezone(&Local).naive_local();
+    let due = task.due_date.map_or(0.0, |d| due_factor(d.naive_utc(), local_now));
+
+    let age = match task.created {
+        Some(created) if c.age_max > 0.0 => {
+            let days = (now - created).num_seconds() as f64 / 86_400.0;
+            (days / c.age_max).clamp(0.0, 1.0)
+        }
+        _ => 0.0,
+    };
+
+    let tags = match task.tags.len() {
+        0 => 0.0,
+        1 => 0.8,
+        2 => 0.9,
+        _ => 1.0,
+    };
+    let tag_bonus: f64 = task.tags.iter().filter_map(|t| c.tag.get(t)).sum();
+
+    let blocked = if models::is_blocked(tasks, task) { 1.0 } else { 0.0 };
+    let blocking = if models::dependents(tasks, task.uuid).any(|t| !t.completed) {
+        1.0
+    } else {
+        0.0
+    };
+
+    priority + c.due * due + c.age * age + c.tags * tags + tag_bonus + c.blocked * blocked + c.blocking * blocking
+}
+
+/// 1 for tasks a week or more overdue, falling linearly to 0.2 for tasks
+/// due in two weeks, and 0.2 beyond that.
+fn due_factor(due: NaiveDateTime, now: NaiveDateTime) -> f64 {
+    let days_overdue = (now - due).num_seconds() as f64 / 86_400.0;
+    if days_overdue >= 7.0 {
+        1.0
+    } else if days_overdue >= -14.0 {
+        (days_overdue + 14.0) * 0.8 / 21.0 + 0.2
+    } else {
+        0.2
+    }
+}